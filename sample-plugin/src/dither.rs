@@ -1,12 +1,13 @@
-use std::ffi::{CStr, c_void};
+use std::ffi::CStr;
 
 use vapoursynth4_rs::{
     core::CoreRef,
     frame::{FrameContext, VideoFrame},
-    key,
-    map::{AppendMode, MapRef, Value},
+    key, map,
+    map::MapRef,
     node::{
-        ActivationReason, Dependencies, Filter, FilterDependency, Node, RequestPattern, VideoNode,
+        ActivationReason, Dependencies, Filter, FilterDependency, FrameStateSlot, Instance, Node,
+        RequestPattern, VideoNode, mode::Parallel,
     },
 };
 
@@ -18,9 +19,11 @@ pub(crate) struct DitherFilter {
 }
 
 impl Filter for DitherFilter {
+    type Mode = Parallel;
     type Error = &'static CStr;
     type FrameType = VideoFrame;
     type FilterData = ();
+    type FrameState = ();
 
     fn create(
         input: MapRef,
@@ -39,17 +42,7 @@ impl Filter for DitherFilter {
         let Some(fmtc_plugin) = core.get_plugin_by_namespace(c"fmtc") else {
             return Err(c"Failed to find the fmtconv plugin.");
         };
-        let mut args = core.create_map();
-        args.set(
-            key!(c"clip"),
-            Value::VideoNode(node.clone()),
-            AppendMode::Replace,
-        )
-        .unwrap();
-        args.set(key!(c"bits"), Value::Int(bits as i64), AppendMode::Replace)
-            .unwrap();
-        args.set(key!(c"dmode"), Value::Int(8), AppendMode::Replace)
-            .unwrap();
+        let args = map!(core; "clip" => &node, "bits" => bits, "dmode" => 8).unwrap();
         let ret = fmtc_plugin.invoke(c"bitdepth", &args);
         let Ok(dithered_node) = ret.get_video_node(key!(c"clip"), 0) else {
             return Err(c"Failed to dither the clip.");
@@ -86,10 +79,10 @@ impl Filter for DitherFilter {
     }
 
     fn get_frame(
-        &self,
+        this: Instance<'_, Self>,
         n: i32,
         activation_reason: ActivationReason,
-        _frame_data: *mut *mut c_void,
+        _frame_state: FrameStateSlot<()>,
         mut ctx: FrameContext,
         core: CoreRef,
     ) -> Result<Option<VideoFrame>, Self::Error> {
@@ -97,10 +90,10 @@ impl Filter for DitherFilter {
 
         match activation_reason {
             r::Initial => {
-                ctx.request_frame_filter(n, &self.node);
+                ctx.request_frame_filter(n, &this.node);
             }
             r::AllFramesReady => {
-                let src = self.node.get_frame_filter(n, &mut ctx);
+                let src = this.node.get_frame_filter(n, &mut ctx);
                 let dst = core.copy_frame(&src);
 
                 // Do whatever frame processing here, in the new bit depth.
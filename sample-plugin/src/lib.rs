@@ -1,18 +1,16 @@
 mod dither;
 
-use std::ffi::{CStr, c_void};
+use std::ffi::CStr;
 
 use dither::DitherFilter;
 use vapoursynth4_rs::{
     SampleType,
     core::CoreRef,
     declare_plugin,
-    frame::{FrameContext, VideoFrame},
+    frame::{VideoFrame, VideoFrameMut},
     key,
     map::MapRef,
-    node::{
-        ActivationReason, Dependencies, Filter, FilterDependency, Node, RequestPattern, VideoNode,
-    },
+    node::{Simple, SimpleVideoFilter, VideoNode},
 };
 
 struct DumbFilter {
@@ -20,22 +18,18 @@ struct DumbFilter {
     enabled: bool,
 }
 
-impl Filter for DumbFilter {
+impl SimpleVideoFilter for DumbFilter {
     type Error = &'static CStr;
-    type FrameType = VideoFrame;
-    type FilterData = ();
-
-    fn create(
-        input: MapRef,
-        output: MapRef,
-        _data: Option<Box<Self::FilterData>>,
-        mut core: CoreRef,
-    ) -> Result<(), Self::Error> {
+
+    const NAME: &'static CStr = c"Filter";
+    const NODE_NAME: &'static CStr = c"Invert";
+    const ARGS: &'static CStr = c"clip:vnode;enabled:int:opt;";
+
+    fn create(input: MapRef, _core: CoreRef) -> Result<Self, Self::Error> {
         let Ok(node) = input.get_video_node(key!(c"clip"), 0) else {
             return Err(c"Failed to get clip");
         };
-        let n = node.clone();
-        let vi = n.info();
+        let vi = node.info();
 
         if !vapoursynth4_rs::utils::is_constant_video_format(vi)
             || vi.format.sample_type != SampleType::Integer
@@ -44,85 +38,52 @@ impl Filter for DumbFilter {
             return Err(c"Invert: only constant format 8bit integer input supported");
         }
 
-        let filter = DumbFilter {
+        Ok(DumbFilter {
             node,
             enabled: input
                 .get_int(key!(c"enabled"), 0)
                 .map(|v| v != 0)
                 .unwrap_or(true),
-        };
-
-        let deps = [FilterDependency {
-            source: filter.node.as_ptr(),
-            request_pattern: RequestPattern::StrictSpatial,
-        }];
-
-        core.create_video_filter(
-            output,
-            c"Invert",
-            vi,
-            Box::new(filter),
-            Dependencies::new(&deps).unwrap(),
-        );
+        })
+    }
 
-        Ok(())
+    fn input(&self) -> &VideoNode {
+        &self.node
     }
 
-    fn get_frame(
+    fn process(
         &self,
-        n: i32,
-        activation_reason: ActivationReason,
-        _frame_data: *mut *mut c_void,
-        mut ctx: FrameContext,
-        core: CoreRef,
-    ) -> Result<Option<VideoFrame>, Self::Error> {
-        use ActivationReason as r;
-
-        match activation_reason {
-            r::Initial => {
-                ctx.request_frame_filter(n, &self.node);
-            }
-            r::AllFramesReady => {
-                let src = self.node.get_frame_filter(n, &mut ctx);
-                if !self.enabled {
-                    return Err(c"Not enabled");
-                }
-
-                let fi = src.get_video_format();
-                let height = src.frame_height(0);
-                let width = src.frame_width(0);
-
-                let mut dst = core.new_video_frame(fi, width, height, Some(&src));
-
-                for plane in 0..fi.num_planes {
-                    let mut src_p = src.plane(plane);
-                    let src_stride = src.stride(plane);
-                    let mut dst_p = dst.plane_mut(plane);
-                    let dst_stride = dst.stride(plane);
+        _n: i32,
+        src: &VideoFrame,
+        dst: &mut VideoFrameMut,
+        _core: CoreRef,
+    ) -> Result<(), Self::Error> {
+        if !self.enabled {
+            return Err(c"Not enabled");
+        }
 
-                    let h = src.frame_height(plane);
-                    let w = src.frame_width(plane);
+        let fi = src.get_video_format();
+        for plane in 0..fi.num_planes {
+            let mut src_p = src.plane(plane);
+            let src_stride = src.stride(plane);
+            let mut dst_p = dst.plane_mut(plane);
+            let dst_stride = dst.stride(plane);
 
-                    for _ in 0..h {
-                        for x in 0..w as usize {
-                            unsafe { *dst_p.wrapping_add(x) = !*src_p.wrapping_add(x) };
-                        }
+            let h = src.frame_height(plane);
+            let w = src.frame_width(plane);
 
-                        src_p = src_p.wrapping_offset(src_stride);
-                        dst_p = dst_p.wrapping_offset(dst_stride);
-                    }
+            for _ in 0..h {
+                for x in 0..w as usize {
+                    unsafe { *dst_p.wrapping_add(x) = !*src_p.wrapping_add(x) };
                 }
-                return Ok(Some(dst));
+
+                src_p = src_p.wrapping_offset(src_stride);
+                dst_p = dst_p.wrapping_offset(dst_stride);
             }
-            _ => {}
         }
 
-        Ok(None)
+        Ok(())
     }
-
-    const NAME: &'static CStr = c"Filter";
-    const ARGS: &'static CStr = c"clip:vnode;enabled:int:opt;";
-    const RETURN_TYPE: &'static CStr = c"clip:vnode;";
 }
 
 declare_plugin!(
@@ -132,23 +93,6 @@ declare_plugin!(
     (1, 0),
     vapoursynth4_rs::VAPOURSYNTH_API_VERSION,
     0,
-    (DumbFilter, None),
+    (Simple<DumbFilter>, None),
     (DitherFilter, None)
 );
-
-#[cfg(test)]
-mod tests {
-    use testresult::TestResult;
-    use vapoursynth4_rs::sciprt::Script;
-
-    #[test]
-    fn test_vsscript_works() -> TestResult {
-        let vss = Script::default();
-        vss.evaluate_file(c"test.vpy")?;
-        let node = vss.get_output(0)?;
-        let vn = node.as_video().ok_or("Expected video node")?;
-        let _info = vn.info();
-
-        Ok(())
-    }
-}
@@ -0,0 +1,57 @@
+use testresult::TestResult;
+use vapoursynth4_rs::{
+    ffi, map,
+    node::VideoNode,
+    testing::{
+        PluginHarness, assert_error_contains, assert_frame_eq, assert_props, cdylib_path,
+        get_frame, get_frames,
+    },
+};
+
+fn blank_clip(harness: &PluginHarness, color: [f64; 3]) -> TestResult<VideoNode> {
+    let args = map!(harness.core(); "length" => 3, "color" => color)?;
+    Ok(harness.invoke_clip(c"std", c"BlankClip", args)?)
+}
+
+#[test]
+fn invert() -> TestResult {
+    let harness = PluginHarness::load(cdylib_path(env!("CARGO_PKG_NAME")))?;
+    let core = harness.core();
+
+    let clip = blank_clip(&harness, [16.0, 32.0, 64.0])?;
+    let inverted = harness.invoke_clip(c"invert", c"Filter", map!(core; "clip" => clip)?)?;
+    let expected = blank_clip(&harness, [239.0, 223.0, 191.0])?;
+
+    for (n, frame) in (0..).zip(get_frames(&inverted, 0..3)?) {
+        assert_frame_eq(&frame, &get_frame(&expected, n)?);
+        assert_props(
+            &frame,
+            &map!(core; "_DurationNum" => 1, "_DurationDen" => 24)?,
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn errors() -> TestResult {
+    let harness = PluginHarness::load(cdylib_path(env!("CARGO_PKG_NAME")))?;
+    let core = harness.core();
+
+    let clip = blank_clip(&harness, [0.0; 3])?;
+    let disabled = harness.invoke_clip(
+        c"invert",
+        c"Filter",
+        map!(core; "clip" => &clip, "enabled" => 0)?,
+    )?;
+    assert_error_contains(get_frame(&disabled, 0), "Not enabled");
+
+    let format = ffi::VSPresetVideoFormat::Gray16 as i32;
+    let gray = harness.invoke_clip(c"std", c"BlankClip", map!(core; "format" => format)?)?;
+    assert_error_contains(
+        harness.invoke_clip(c"invert", c"Filter", map!(core; "clip" => gray)?),
+        "only constant format 8bit integer input supported",
+    );
+
+    Ok(())
+}
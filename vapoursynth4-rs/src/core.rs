@@ -22,7 +22,7 @@ use crate::{
     frame::{
         AudioFormat, AudioFrame, FormatName, Frame, VideoFormat, VideoFrame, internal::FrameFromPtr,
     },
-    function::Function,
+    function::{Function, call_closure, free_closure},
     map::{Map, MapRef},
     node::{Dependencies, Filter, internal::FilterExtern},
     plugin::{Plugin, Plugins},
@@ -357,6 +357,29 @@ impl Core {
         }
     }
 
+    /// Wraps a closure as a [`Function`], e.g. for passing callbacks to other plugins.
+    ///
+    /// A returned error or a panic is reported as the error of the output map,
+    /// see [`Function::invoke`].
+    #[must_use]
+    pub fn function_from_closure<F, E>(&self, func: F) -> Function
+    where
+        F: Fn(&Map, &mut Map, CoreRef) -> Result<(), E> + Send + Sync + 'static,
+        E: AsRef<CStr>,
+    {
+        unsafe {
+            Function::from_ptr(
+                (self.api.createFunction)(
+                    call_closure::<F, E>,
+                    Box::into_raw(Box::new(func)).cast(),
+                    Some(free_closure::<F>),
+                    self.as_ptr(),
+                ),
+                self.api,
+            )
+        }
+    }
+
     #[must_use]
     pub fn get_plugin_by_id(&self, id: &CStr) -> Option<Plugin> {
         unsafe {
@@ -385,6 +408,12 @@ impl Core {
     }
 }
 
+impl AsRef<Core> for Core {
+    fn as_ref(&self) -> &Core {
+        self
+    }
+}
+
 impl Drop for Core {
     fn drop(&mut self) {
         unsafe {
@@ -4,13 +4,25 @@
  file, You can obtain one at http://mozilla.org/MPL/2.0/.
 */
 
+use std::ops::{Deref, DerefMut};
+
 use crate::{api::Api, ffi, map::MapRef};
 
+#[cfg(feature = "ndarray")]
+mod array;
 mod context;
 mod format;
-
+#[cfg(feature = "image")]
+mod image;
+mod rgb;
+
+#[cfg(feature = "image")]
+pub use self::image::ImageConversionError;
+#[cfg(feature = "ndarray")]
+pub use array::Sample;
 pub use context::*;
 pub use format::*;
+pub use rgb::{Matrix, RgbBuffer, RgbConversionError};
 
 pub trait Frame: Sized + Send + internal::FrameFromPtr {
     fn api(&self) -> Api;
@@ -138,6 +150,36 @@ impl Drop for VideoFrame {
     }
 }
 
+/// A newly allocated video frame that is not shared yet, so its planes can be written.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct VideoFrameMut(VideoFrame);
+
+impl VideoFrameMut {
+    /// `frame` must be newly allocated and not shared.
+    pub(crate) fn new(frame: VideoFrame) -> Self {
+        Self(frame)
+    }
+
+    #[must_use]
+    pub fn into_frame(self) -> VideoFrame {
+        self.0
+    }
+}
+
+impl Deref for VideoFrameMut {
+    type Target = VideoFrame;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for VideoFrameMut {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub struct AudioFrame {
     handle: *const ffi::VSFrame,
@@ -185,4 +227,34 @@ impl Drop for AudioFrame {
     }
 }
 
+/// A newly allocated audio frame that is not shared yet, so its channels can be written.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct AudioFrameMut(AudioFrame);
+
+impl AudioFrameMut {
+    /// `frame` must be newly allocated and not shared.
+    pub(crate) fn new(frame: AudioFrame) -> Self {
+        Self(frame)
+    }
+
+    #[must_use]
+    pub fn into_frame(self) -> AudioFrame {
+        self.0
+    }
+}
+
+impl Deref for AudioFrameMut {
+    type Target = AudioFrame;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for AudioFrameMut {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
 pub type MediaType = ffi::VSMediaType;
@@ -0,0 +1,175 @@
+use ndarray::{ArrayView2, ArrayViewMut2, ShapeBuilder};
+
+use crate::{SampleType, core::Core};
+
+use super::{VideoFormat, VideoFrame, VideoFrameMut};
+
+/// A type that the samples of a video plane can be viewed as.
+pub trait Sample: Copy + crate::_private::Sealed {
+    #[doc(hidden)]
+    const SAMPLE_TYPE: SampleType;
+}
+
+macro_rules! impl_sample {
+    ($($t:ty => $st:ident),*) => {$(
+        impl crate::_private::Sealed for $t {}
+        impl Sample for $t {
+            const SAMPLE_TYPE: SampleType = SampleType::$st;
+        }
+    )*};
+}
+
+impl_sample!(u8 => Integer, u16 => Integer, u32 => Integer, f32 => Float);
+
+/// Whether samples of `format` can be viewed as `T`.
+fn matches<T: Sample>(format: &VideoFormat) -> bool {
+    format.sample_type == T::SAMPLE_TYPE
+        && usize::try_from(format.bytes_per_sample).is_ok_and(|b| b == size_of::<T>())
+}
+
+impl VideoFrame {
+    /// Returns the shape and the row stride in samples of `plane`.
+    fn plane_layout<T: Sample>(&self, plane: i32) -> Option<((usize, usize), usize)> {
+        let format = self.get_video_format();
+        if !(0..format.num_planes).contains(&plane) || !matches::<T>(format) {
+            return None;
+        }
+
+        let stride = self.stride(plane).unsigned_abs();
+        if !stride.is_multiple_of(size_of::<T>()) {
+            return None;
+        }
+        let height = usize::try_from(self.frame_height(plane)).ok()?;
+        let width = usize::try_from(self.frame_width(plane)).ok()?;
+
+        Some(((height, width), stride / size_of::<T>()))
+    }
+
+    /// Views `plane` as a `height × width` array of `T` without copying.
+    ///
+    /// Returns `None` if `plane` does not exist or `T` does not match the sample type
+    /// and size of the format.
+    #[must_use]
+    pub fn plane_array<T: Sample>(&self, plane: i32) -> Option<ArrayView2<'_, T>> {
+        let (shape, stride) = self.plane_layout::<T>(plane)?;
+        // SAFETY: Each of the `height` rows holds `width` samples of `T`, `stride` samples apart
+        Some(unsafe {
+            ArrayView2::from_shape_ptr(shape.strides((stride, 1)), self.plane(plane).cast())
+        })
+    }
+}
+
+impl VideoFrameMut {
+    /// Views `plane` as a writable `height × width` array of `T` without copying.
+    ///
+    /// See [`VideoFrame::plane_array`].
+    #[must_use]
+    pub fn plane_array_mut<T: Sample>(&mut self, plane: i32) -> Option<ArrayViewMut2<'_, T>> {
+        let (shape, stride) = self.plane_layout::<T>(plane)?;
+        // SAFETY: See `plane_array`, and the frame is not shared
+        Some(unsafe {
+            ArrayViewMut2::from_shape_ptr(shape.strides((stride, 1)), self.plane_mut(plane).cast())
+        })
+    }
+}
+
+impl Core {
+    /// Creates a video frame of `format` from one array per plane.
+    ///
+    /// The size of the frame is taken from the first plane.
+    ///
+    /// Returns `None` if the number of planes, the sample type or the plane sizes
+    /// do not match `format`, or the size of the first plane is not a multiple of
+    /// the chroma subsampling.
+    #[must_use]
+    pub fn video_frame_from_arrays<T: Sample>(
+        &self,
+        format: &VideoFormat,
+        planes: &[ArrayView2<'_, T>],
+    ) -> Option<VideoFrame> {
+        let (height, width) = planes.first()?.dim();
+        if usize::try_from(format.num_planes).ok()? != planes.len() || !matches::<T>(format) {
+            return None;
+        }
+        if width % (1 << format.sub_sampling_w) != 0 || height % (1 << format.sub_sampling_h) != 0 {
+            return None;
+        }
+
+        let mut frame = VideoFrameMut::new(self.new_video_frame(
+            format,
+            i32::try_from(width).ok()?,
+            i32::try_from(height).ok()?,
+            None,
+        ));
+        for (i, src) in (0..).zip(planes) {
+            let mut dst = frame.plane_array_mut::<T>(i)?;
+            if dst.dim() != src.dim() {
+                return None;
+            }
+            dst.assign(src);
+        }
+
+        Some(frame.into_frame())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array2;
+    use testresult::TestResult;
+
+    use super::*;
+    use crate::{ColorFamily, mock};
+
+    #[test]
+    #[cfg(feature = "link-vs")]
+    fn arrays() -> TestResult {
+        let core = Core::builder().build();
+        let format = core.query_video_format(ColorFamily::YUV, SampleType::Integer, 16, 1, 1);
+
+        let luma = Array2::from_shape_fn((4, 6), |(y, x)| u16::try_from(y * 6 + x).unwrap());
+        let chroma = Array2::from_elem((2, 3), 512u16);
+        let frame = core
+            .video_frame_from_arrays(&format, &[luma.view(), chroma.view(), chroma.view()])
+            .ok_or("failed to create frame")?;
+
+        let view = frame.plane_array::<u16>(0).ok_or("no luma")?;
+        assert_eq!(view, luma);
+        assert_eq!(view.strides()[0], frame.stride(0) / 2);
+        assert_eq!(frame.plane_array::<u16>(2).ok_or("no chroma")?, chroma);
+        assert!(frame.plane_array::<u8>(0).is_none());
+        assert!(frame.plane_array::<u16>(3).is_none());
+
+        assert!(
+            core.video_frame_from_arrays(&format, &[luma.view(), luma.view(), luma.view()])
+                .is_none()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn subsampling() -> TestResult {
+        let core = Core::builder().api(mock::api()).build();
+        let format = core.query_video_format(ColorFamily::YUV, SampleType::Integer, 8, 1, 1);
+
+        let luma = Array2::from_shape_fn((4, 6), |(y, x)| u8::try_from(y * 6 + x).unwrap());
+        let chroma = Array2::from_elem((2, 3), 128u8);
+        let frame = core
+            .video_frame_from_arrays(&format, &[luma.view(), chroma.view(), chroma.view()])
+            .ok_or("failed to create frame")?;
+        assert_eq!(frame.plane_array::<u8>(0).ok_or("no luma")?, luma);
+        assert_eq!(frame.plane_array::<u8>(1).ok_or("no chroma")?, chroma);
+
+        for (luma, chroma) in [((3, 6), (1, 3)), ((4, 5), (2, 2))] {
+            let luma = Array2::zeros(luma);
+            let chroma = Array2::<u8>::zeros(chroma);
+            assert!(
+                core.video_frame_from_arrays(&format, &[luma.view(), chroma.view(), chroma.view()])
+                    .is_none()
+            );
+        }
+
+        Ok(())
+    }
+}
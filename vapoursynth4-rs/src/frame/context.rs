@@ -1,11 +1,6 @@
 use std::ffi::CStr;
 
-use crate::{
-    api::Api,
-    ffi,
-    frame::Frame,
-    node::{Node, VideoNode},
-};
+use crate::{api::Api, ffi, frame::Frame, node::Node};
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct FrameContext {
@@ -27,13 +22,13 @@ impl FrameContext {
         self.handle.cast_mut()
     }
 
-    pub fn request_frame_filter(&mut self, n: i32, node: &VideoNode) {
+    pub fn request_frame_filter(&mut self, n: i32, node: &impl Node) {
         unsafe {
             (self.api.requestFrameFilter)(n, node.as_ptr(), self.as_ptr());
         }
     }
 
-    pub fn release_frame_early(&mut self, n: i32, node: &VideoNode) {
+    pub fn release_frame_early(&mut self, n: i32, node: &impl Node) {
         unsafe {
             (self.api.releaseFrameEarly)(node.as_ptr(), n, self.as_ptr());
         }
@@ -22,7 +22,11 @@ impl FormatName {
     pub fn as_str(&self) -> &str {
         // SAFETY: The buffer is guaranteed to be alphanumeric or underscore and null-terminated.
         unsafe {
-            str::from_utf8_unchecked(CStr::from_bytes_with_nul_unchecked(&self.buffer).to_bytes())
+            str::from_utf8_unchecked(
+                CStr::from_bytes_until_nul(&self.buffer)
+                    .unwrap_unchecked()
+                    .to_bytes(),
+            )
         }
     }
 
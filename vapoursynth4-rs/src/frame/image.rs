@@ -0,0 +1,234 @@
+use image::{ColorType, DynamicImage, ImageBuffer, Pixel};
+use thiserror::Error;
+
+use crate::{ColorFamily, SampleType, core::Core, ffi};
+
+use super::{VideoFormat, VideoFrame, VideoFrameMut};
+
+#[derive(Debug, Error)]
+pub enum ImageConversionError {
+    #[error("Unsupported frame format: {0}")]
+    UnsupportedFormat(String),
+    #[error("Unsupported image color type: {0:?}")]
+    UnsupportedImage(ColorType),
+    #[error("Invalid size: {0}x{1}")]
+    InvalidSize(i64, i64),
+}
+
+impl ImageConversionError {
+    fn format(format: &VideoFormat) -> Self {
+        Self::UnsupportedFormat(format!(
+            "{:?} {:?} {}-bit",
+            format.color_family, format.sample_type, format.bits_per_sample
+        ))
+    }
+}
+
+impl VideoFrame {
+    /// Converts a `GRAY8`, `GRAY16`, `RGB24`, `RGB48` or `RGBS` frame to an image.
+    ///
+    /// # Errors
+    ///
+    /// Return [`ImageConversionError::UnsupportedFormat`] for other formats, including `YUV`,
+    /// which must be converted to `RGB` first, e.g. with [`VideoFrame::to_rgb8`].
+    pub fn to_image(&self) -> Result<DynamicImage, ImageConversionError> {
+        use ffi::VSColorFamily::{Gray, RGB};
+        use ffi::VSSampleType::{Float, Integer};
+
+        let format = self.get_video_format();
+        let image = match (
+            format.color_family,
+            format.sample_type,
+            format.bits_per_sample,
+        ) {
+            (Gray, Integer, 8) => DynamicImage::ImageLuma8(self.to_buffer()?),
+            (Gray, Integer, 16) => DynamicImage::ImageLuma16(self.to_buffer()?),
+            (RGB, Integer, 8) => DynamicImage::ImageRgb8(self.to_buffer()?),
+            (RGB, Integer, 16) => DynamicImage::ImageRgb16(self.to_buffer()?),
+            (RGB, Float, 32) => DynamicImage::ImageRgb32F(self.to_buffer()?),
+            _ => return Err(ImageConversionError::format(format)),
+        };
+
+        Ok(image)
+    }
+
+    /// Interleaves the planes of the frame into pixels of `P`.
+    fn to_buffer<P: Pixel>(
+        &self,
+    ) -> Result<ImageBuffer<P, Vec<P::Subpixel>>, ImageConversionError> {
+        let (width, height) = (self.frame_width(0), self.frame_height(0));
+        let invalid = || ImageConversionError::InvalidSize(width.into(), height.into());
+        let w = usize::try_from(width).map_err(|_| invalid())?;
+        let h = usize::try_from(height).map_err(|_| invalid())?;
+        let channels = usize::from(P::CHANNEL_COUNT);
+
+        let mut data = Vec::with_capacity(w * h * channels);
+        for y in 0..h {
+            let rows: Vec<_> = (0..i32::from(P::CHANNEL_COUNT))
+                .map(|plane| {
+                    let offset = self.stride(plane) * y.cast_signed();
+                    self.plane(plane)
+                        .wrapping_offset(offset)
+                        .cast::<P::Subpixel>()
+                })
+                .collect();
+            for x in 0..w {
+                // SAFETY: Each row of a plane holds `w` samples of the format
+                data.extend(rows.iter().map(|row| unsafe { row.add(x).read() }));
+            }
+        }
+
+        ImageBuffer::from_raw(
+            u32::try_from(width).map_err(|_| invalid())?,
+            u32::try_from(height).map_err(|_| invalid())?,
+            data,
+        )
+        .ok_or_else(invalid)
+    }
+}
+
+impl Core {
+    /// Creates a `GRAY8`, `GRAY16`, `RGB24`, `RGB48` or `RGBS` frame from an image.
+    ///
+    /// The alpha channel is dropped if the image has one.
+    ///
+    /// # Errors
+    ///
+    /// Return [`ImageConversionError::UnsupportedImage`] for images with other sample types,
+    /// or [`ImageConversionError::InvalidSize`] for empty images.
+    pub fn video_frame_from_image(
+        &self,
+        image: &DynamicImage,
+    ) -> Result<VideoFrame, ImageConversionError> {
+        use ffi::VSColorFamily::{Gray, RGB};
+        use ffi::VSSampleType::{Float, Integer};
+
+        match image {
+            DynamicImage::ImageLuma8(buf) => self.frame_from_buffer(buf, Gray, Integer, 8),
+            DynamicImage::ImageLumaA8(_) => {
+                self.frame_from_buffer(&image.to_luma8(), Gray, Integer, 8)
+            }
+            DynamicImage::ImageLuma16(buf) => self.frame_from_buffer(buf, Gray, Integer, 16),
+            DynamicImage::ImageLumaA16(_) => {
+                self.frame_from_buffer(&image.to_luma16(), Gray, Integer, 16)
+            }
+            DynamicImage::ImageRgb8(buf) => self.frame_from_buffer(buf, RGB, Integer, 8),
+            DynamicImage::ImageRgba8(_) => {
+                self.frame_from_buffer(&image.to_rgb8(), RGB, Integer, 8)
+            }
+            DynamicImage::ImageRgb16(buf) => self.frame_from_buffer(buf, RGB, Integer, 16),
+            DynamicImage::ImageRgba16(_) => {
+                self.frame_from_buffer(&image.to_rgb16(), RGB, Integer, 16)
+            }
+            DynamicImage::ImageRgb32F(buf) => self.frame_from_buffer(buf, RGB, Float, 32),
+            DynamicImage::ImageRgba32F(_) => {
+                self.frame_from_buffer(&image.to_rgb32f(), RGB, Float, 32)
+            }
+            _ => Err(ImageConversionError::UnsupportedImage(image.color())),
+        }
+    }
+
+    /// Splits the pixels of `buf` into the planes of a new frame.
+    fn frame_from_buffer<P: Pixel>(
+        &self,
+        buf: &ImageBuffer<P, Vec<P::Subpixel>>,
+        color_family: ColorFamily,
+        sample_type: SampleType,
+        bits: i32,
+    ) -> Result<VideoFrame, ImageConversionError> {
+        let (width, height) = buf.dimensions();
+        let invalid = || ImageConversionError::InvalidSize(width.into(), height.into());
+        if width == 0 || height == 0 {
+            return Err(invalid());
+        }
+        let format = self.query_video_format(color_family, sample_type, bits, 0, 0);
+        let mut frame = VideoFrameMut::new(self.new_video_frame(
+            &format,
+            i32::try_from(width).map_err(|_| invalid())?,
+            i32::try_from(height).map_err(|_| invalid())?,
+            None,
+        ));
+
+        let channels = usize::from(P::CHANNEL_COUNT);
+        let w = width as usize;
+        for (plane, c) in (0..).zip(0..channels) {
+            let stride = frame.stride(plane);
+            let mut dst = frame.plane_mut(plane);
+            for row in buf.as_raw().chunks_exact(w * channels) {
+                let samples = row.iter().skip(c).step_by(channels);
+                for (x, &sample) in samples.enumerate() {
+                    // SAFETY: Each row of the plane holds `width` samples of the format
+                    unsafe { dst.cast::<P::Subpixel>().add(x).write(sample) };
+                }
+                dst = dst.wrapping_offset(stride);
+            }
+        }
+
+        Ok(frame.into_frame())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, Luma, Rgb, RgbImage};
+    use testresult::TestResult;
+
+    use super::*;
+    use crate::mock;
+
+    #[test]
+    #[cfg(feature = "link-vs")]
+    fn round_trip() -> TestResult {
+        let core = Core::builder().build();
+
+        let image = RgbImage::from_fn(5, 3, |x, y| {
+            Rgb([u8::try_from(x).unwrap(), u8::try_from(y).unwrap(), 128])
+        });
+        let frame = core.video_frame_from_image(&DynamicImage::ImageRgb8(image.clone()))?;
+        let format = frame.get_video_format();
+        assert_eq!(format.color_family, ColorFamily::RGB);
+        assert_eq!((frame.frame_width(0), frame.frame_height(0)), (5, 3));
+        assert_eq!(unsafe { *frame.plane(2) }, 128);
+        assert_eq!(frame.to_image()?, DynamicImage::ImageRgb8(image));
+
+        let yuv = core.query_video_format(ColorFamily::YUV, SampleType::Integer, 8, 1, 1);
+        let frame = core.new_video_frame(&yuv, 4, 4, None);
+        assert!(matches!(
+            frame.to_image(),
+            Err(ImageConversionError::UnsupportedFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn round_trip_mock() -> TestResult {
+        let core = Core::builder().api(mock::api()).build();
+
+        let image = DynamicImage::ImageRgb16(ImageBuffer::from_fn(3, 2, |x, y| {
+            Rgb([
+                u16::try_from(x * 1000).unwrap(),
+                u16::try_from(y).unwrap(),
+                65535,
+            ])
+        }));
+        let frame = core.video_frame_from_image(&image)?;
+        assert_eq!(frame.get_video_format().bits_per_sample, 16);
+        assert_eq!(frame.to_image()?, image);
+
+        let image = DynamicImage::ImageLuma8(GrayImage::from_fn(4, 1, |x, _| {
+            Luma([u8::try_from(x).unwrap()])
+        }));
+        assert_eq!(core.video_frame_from_image(&image)?.to_image()?, image);
+
+        for (width, height) in [(0, 2), (2, 0)] {
+            let image = DynamicImage::ImageRgb8(RgbImage::new(width, height));
+            assert!(matches!(
+                core.video_frame_from_image(&image),
+                Err(ImageConversionError::InvalidSize(..))
+            ));
+        }
+
+        Ok(())
+    }
+}
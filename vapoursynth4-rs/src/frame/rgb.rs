@@ -0,0 +1,411 @@
+use thiserror::Error;
+
+use crate::{ffi, key, map::KeyStr};
+
+use super::{Frame, VideoFrame};
+
+#[derive(Debug, Error)]
+pub enum RgbConversionError {
+    #[error("Unsupported frame format: {0}")]
+    UnsupportedFormat(String),
+    #[error("Unsupported matrix: {0}")]
+    UnsupportedMatrix(i64),
+}
+
+/// `YCbCr` to `RGB` matrices with non-constant luminance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Matrix {
+    Bt601,
+    Bt709,
+    Bt2020Ncl,
+}
+
+impl Matrix {
+    /// Returns the matrix of a `_Matrix` frame property.
+    ///
+    /// An unspecified matrix is guessed from the frame size: BT.709 for HD frames
+    /// and BT.601 otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Return [`RgbConversionError::UnsupportedMatrix`] for other matrices, including `RGB`.
+    pub fn from_prop(
+        value: Option<i64>,
+        width: i32,
+        height: i32,
+    ) -> Result<Self, RgbConversionError> {
+        match value {
+            Some(1) => Ok(Self::Bt709),
+            Some(9) => Ok(Self::Bt2020Ncl),
+            None | Some(2) if width > 1024 || height > 576 => Ok(Self::Bt709),
+            None | Some(2 | 5 | 6) => Ok(Self::Bt601),
+            Some(value) => Err(RgbConversionError::UnsupportedMatrix(value)),
+        }
+    }
+
+    /// Luma coefficients of red and blue.
+    fn coefficients(self) -> (f32, f32) {
+        match self {
+            Self::Bt601 => (0.299, 0.114),
+            Self::Bt709 => (0.2126, 0.0722),
+            Self::Bt2020Ncl => (0.2627, 0.0593),
+        }
+    }
+
+    /// Converts normalized `Y'` in `[0, 1]` and `Cb`, `Cr` in `[-0.5, 0.5]` to `R'G'B'`.
+    #[must_use]
+    pub fn to_rgb(self, y: f32, cb: f32, cr: f32) -> [f32; 3] {
+        let (kr, kb) = self.coefficients();
+        let kg = 1.0 - kr - kb;
+        let r = y + 2.0 * (1.0 - kr) * cr;
+        let b = y + 2.0 * (1.0 - kb) * cb;
+        let g = (y - kr * r - kb * b) / kg;
+        [r, g, b]
+    }
+}
+
+/// Interleaved `RGB` samples of a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbBuffer<T> {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<T>,
+}
+
+impl VideoFrame {
+    /// Converts a `YUV` or `GRAY` frame to 8-bit interleaved `RGB`.
+    ///
+    /// `_Matrix`, `_ColorRange` and `_ChromaLocation` are read from the frame properties,
+    /// defaulting to a matrix guessed from the size, limited range and left chroma siting.
+    /// Subsampled chroma is upsampled bilinearly.
+    ///
+    /// # Errors
+    ///
+    /// Return [`RgbConversionError`] if the format or the matrix is unsupported.
+    pub fn to_rgb8(&self) -> Result<RgbBuffer<u8>, RgbConversionError> {
+        // Clamped to `[0, 255]`
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        self.to_rgb(|v| (v * 255.0).round() as u8)
+    }
+
+    /// Converts a `YUV` or `GRAY` frame to 16-bit interleaved `RGB`.
+    ///
+    /// See [`VideoFrame::to_rgb8`].
+    ///
+    /// # Errors
+    ///
+    /// Return [`RgbConversionError`] if the format or the matrix is unsupported.
+    pub fn to_rgb16(&self) -> Result<RgbBuffer<u16>, RgbConversionError> {
+        // Clamped to `[0, 65535]`
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        self.to_rgb(|v| (v * 65535.0).round() as u16)
+    }
+
+    fn to_rgb<T>(&self, quantize: impl Fn(f32) -> T) -> Result<RgbBuffer<T>, RgbConversionError> {
+        let format = self.get_video_format();
+        let unsupported = || {
+            RgbConversionError::UnsupportedFormat(format!(
+                "{:?} {:?} {}-bit",
+                format.color_family, format.sample_type, format.bits_per_sample
+            ))
+        };
+        let is_yuv = match format.color_family {
+            ffi::VSColorFamily::YUV => true,
+            ffi::VSColorFamily::Gray => false,
+            _ => return Err(unsupported()),
+        };
+        let range = match (format.sample_type, format.bits_per_sample) {
+            (ffi::VSSampleType::Integer, bits @ 8..=16) => {
+                let full = self.prop(key!(c"_ColorRange")) == Some(0);
+                SampleRange::Integer { bits, full }
+            }
+            (ffi::VSSampleType::Float, 32) => SampleRange::Float,
+            _ => return Err(unsupported()),
+        };
+
+        let (width, height) = (self.frame_width(0), self.frame_height(0));
+        let luma = Plane::new(self, 0).ok_or_else(unsupported)?;
+        let chroma = if is_yuv {
+            let matrix = Matrix::from_prop(self.prop(key!(c"_Matrix")), width, height)?;
+            let siting = ChromaSiting::from_prop(self.prop(key!(c"_ChromaLocation")));
+            let u = Plane::new(self, 1).ok_or_else(unsupported)?;
+            let v = Plane::new(self, 2).ok_or_else(unsupported)?;
+            let scale = (1 << format.sub_sampling_w, 1 << format.sub_sampling_h);
+            Some((matrix, siting, scale, u, v))
+        } else {
+            None
+        };
+
+        let mut data = Vec::with_capacity(luma.width * luma.height * 3);
+        for y in 0..luma.height {
+            for x in 0..luma.width {
+                let luma = range.luma(luma.get(x, y));
+                let rgb = match &chroma {
+                    Some((matrix, siting, (sw, sh), u, v)) => {
+                        let cx = position(x, *sw, siting.h);
+                        let cy = position(y, *sh, siting.v);
+                        let cb = range.chroma(u.bilinear(cx, cy));
+                        let cr = range.chroma(v.bilinear(cx, cy));
+                        matrix.to_rgb(luma, cb, cr)
+                    }
+                    None => [luma; 3],
+                };
+                data.extend(rgb.map(|c| quantize(c.clamp(0.0, 1.0))));
+            }
+        }
+
+        Ok(RgbBuffer {
+            width: luma.width,
+            height: luma.height,
+            data,
+        })
+    }
+
+    fn prop(&self, key: &KeyStr) -> Option<i64> {
+        self.properties()
+            .and_then(|props| props.get_as::<i64>(key).ok())
+    }
+}
+
+/// How samples are normalized to `[0, 1]` for luma and `[-0.5, 0.5]` for chroma.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SampleRange {
+    Integer { bits: i32, full: bool },
+    Float,
+}
+
+impl SampleRange {
+    fn luma(self, value: f32) -> f32 {
+        match self {
+            Self::Integer { bits, full: true } => value / max_value(bits),
+            Self::Integer { bits, full: false } => {
+                let scale = scale(bits);
+                (value - 16.0 * scale) / (219.0 * scale)
+            }
+            Self::Float => value,
+        }
+    }
+
+    fn chroma(self, value: f32) -> f32 {
+        match self {
+            Self::Integer { bits, full: true } => (value - 128.0 * scale(bits)) / max_value(bits),
+            Self::Integer { bits, full: false } => {
+                let scale = scale(bits);
+                (value - 128.0 * scale) / (224.0 * scale)
+            }
+            Self::Float => value,
+        }
+    }
+}
+
+/// Scale of 8-bit code values at `bits`.
+#[allow(clippy::cast_precision_loss)]
+fn scale(bits: i32) -> f32 {
+    (1 << (bits - 8)) as f32
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn max_value(bits: i32) -> f32 {
+    ((1 << bits) - 1) as f32
+}
+
+/// Position of the chroma samples relative to the luma samples they cover,
+/// as a fraction of the distance between the first and the last of them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ChromaSiting {
+    h: f32,
+    v: f32,
+}
+
+impl ChromaSiting {
+    fn from_prop(value: Option<i64>) -> Self {
+        let (h, v) = match value {
+            Some(1) => (0.5, 0.5),
+            Some(2) => (0.0, 0.0),
+            Some(3) => (0.5, 0.0),
+            Some(4) => (0.0, 1.0),
+            Some(5) => (0.5, 1.0),
+            // Left
+            _ => (0.0, 0.5),
+        };
+        Self { h, v }
+    }
+}
+
+/// Returns the position of luma sample `i` in chroma samples subsampled by `scale`
+/// with `siting` along the axis.
+#[allow(clippy::cast_precision_loss)]
+fn position(i: usize, scale: usize, siting: f32) -> f32 {
+    let offset = siting * (scale - 1) as f32;
+    (i as f32 - offset) / scale as f32
+}
+
+/// A plane of a frame with samples read as `f32`.
+struct Plane {
+    ptr: *const u8,
+    stride: isize,
+    bytes: usize,
+    float: bool,
+    width: usize,
+    height: usize,
+}
+
+impl Plane {
+    fn new(frame: &VideoFrame, plane: i32) -> Option<Self> {
+        let format = frame.get_video_format();
+        Some(Self {
+            ptr: frame.plane(plane),
+            stride: frame.stride(plane),
+            bytes: usize::try_from(format.bytes_per_sample).ok()?,
+            float: format.sample_type == ffi::VSSampleType::Float,
+            width: usize::try_from(frame.frame_width(plane)).ok()?,
+            height: usize::try_from(frame.frame_height(plane)).ok()?,
+        })
+    }
+
+    // Frame data allocated by VapourSynth is aligned for any sample type
+    #[allow(clippy::cast_ptr_alignment)]
+    fn get(&self, x: usize, y: usize) -> f32 {
+        let row = self.ptr.wrapping_offset(self.stride * y.cast_signed());
+        // SAFETY: `x` and `y` are inside the plane, whose samples are of the format
+        unsafe {
+            match (self.bytes, self.float) {
+                (1, _) => f32::from(row.add(x).read()),
+                (2, _) => f32::from(row.cast::<u16>().add(x).read()),
+                _ => row.cast::<f32>().add(x).read(),
+            }
+        }
+    }
+
+    /// Interpolates the sample at `(x, y)`, clamping to the edges.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    fn bilinear(&self, x: f32, y: f32) -> f32 {
+        let x = x.clamp(0.0, (self.width - 1) as f32);
+        let y = y.clamp(0.0, (self.height - 1) as f32);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x.fract(), y.fract());
+
+        let top = self.get(x0, y0) * (1.0 - fx) + self.get(x1, y0) * fx;
+        let bottom = self.get(x0, y1) * (1.0 - fx) + self.get(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ColorFamily, SampleType, core::Core, frame::VideoFrameMut, mock};
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for (a, e) in actual.into_iter().zip(expected) {
+            assert!((a - e).abs() < 1e-3, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn matrices() -> Result<(), RgbConversionError> {
+        for matrix in [Matrix::Bt601, Matrix::Bt709, Matrix::Bt2020Ncl] {
+            assert_close(matrix.to_rgb(1.0, 0.0, 0.0), [1.0; 3]);
+            assert_close(matrix.to_rgb(0.0, 0.0, 0.0), [0.0; 3]);
+            let (kr, kb) = matrix.coefficients();
+            // Pure red
+            assert_close(
+                matrix.to_rgb(kr, -kr / (2.0 * (1.0 - kb)), 0.5),
+                [1.0, 0.0, 0.0],
+            );
+        }
+
+        assert_eq!(Matrix::from_prop(Some(1), 720, 480)?, Matrix::Bt709);
+        assert_eq!(Matrix::from_prop(Some(2), 720, 480)?, Matrix::Bt601);
+        assert_eq!(Matrix::from_prop(None, 1920, 1080)?, Matrix::Bt709);
+        assert!(Matrix::from_prop(Some(0), 720, 480).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn ranges() {
+        let limited = SampleRange::Integer {
+            bits: 10,
+            full: false,
+        };
+        assert!((limited.luma(64.0) - 0.0).abs() < 1e-6);
+        assert!((limited.luma(940.0) - 1.0).abs() < 1e-6);
+        assert!((limited.chroma(960.0) - 0.5).abs() < 1e-6);
+
+        let full = SampleRange::Integer {
+            bits: 8,
+            full: true,
+        };
+        assert!((full.luma(255.0) - 1.0).abs() < 1e-6);
+        assert!(full.chroma(128.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn siting() {
+        let left = ChromaSiting::from_prop(None);
+        assert!((position(2, 2, left.h) - 1.0).abs() < 1e-6);
+        assert!((position(1, 2, left.v) - 0.25).abs() < 1e-6);
+
+        let top_left = ChromaSiting::from_prop(Some(2));
+        assert!((position(1, 2, top_left.v) - 0.5).abs() < 1e-6);
+
+        let plane = [0u8, 100];
+        let plane = Plane {
+            ptr: plane.as_ptr(),
+            stride: 2,
+            bytes: 1,
+            float: false,
+            width: 2,
+            height: 1,
+        };
+        assert!((plane.bilinear(0.25, 0.0) - 25.0).abs() < 1e-4);
+        assert!((plane.bilinear(-1.0, 3.0) - 0.0).abs() < 1e-4);
+        assert!((plane.bilinear(5.0, 0.0) - 100.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn yuv420() -> Result<(), Box<dyn std::error::Error>> {
+        let core = Core::builder().api(mock::api()).build();
+        let format = core.query_video_format(ColorFamily::YUV, SampleType::Integer, 8, 1, 1);
+        let mut frame = VideoFrameMut::new(core.new_video_frame(&format, 4, 2, None));
+        // Red, interpolated, white and black pixels, with a red and a neutral chroma sample
+        for (plane, row) in [
+            (0, &[63u8, 126, 235, 16][..]),
+            (1, &[102, 128]),
+            (2, &[240, 128]),
+        ] {
+            let stride = frame.stride(plane);
+            let dst = frame.plane_mut(plane);
+            for y in 0..frame.frame_height(plane) {
+                // SAFETY: Each row of the plane holds `row.len()` samples
+                unsafe {
+                    dst.wrapping_offset(stride * y as isize)
+                        .copy_from_nonoverlapping(row.as_ptr(), row.len());
+                }
+            }
+        }
+        let mut props = frame.properties_mut().ok_or("no props")?;
+        props.insert(key!(c"_Matrix"), 1)?;
+        props.insert(key!(c"_ChromaLocation"), 2)?;
+        let frame = frame.into_frame();
+
+        let rgb = frame.to_rgb8()?;
+        assert_eq!((rgb.width, rgb.height), (4, 2));
+        let row = [255, 1, 0, 228, 101, 101, 255, 255, 255, 0, 0, 0];
+        assert_eq!(rgb.data, [row, row].concat());
+
+        let rgb = frame.to_rgb16()?;
+        let row = [
+            65535, 150, 0, 58718, 25960, 25860, 65535, 65535, 65535, 0, 0, 0,
+        ];
+        assert_eq!(rgb.data, [row, row].concat());
+
+        Ok(())
+    }
+}
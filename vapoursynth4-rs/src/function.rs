@@ -1,4 +1,17 @@
-use crate::{api::Api, ffi, map::Map};
+use std::{
+    ffi::{CStr, c_void},
+    panic::AssertUnwindSafe,
+};
+
+use thiserror::Error;
+
+use crate::{
+    api::Api,
+    core::CoreRef,
+    ffi,
+    map::{Map, MapRef},
+    utils::panic_message,
+};
 
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub struct Function {
@@ -23,6 +36,22 @@ impl Function {
             (self.api.callFunction)(self.as_ptr(), in_.as_ptr(), out.as_ptr());
         }
     }
+
+    /// Calls the function with `args` and returns its output map.
+    ///
+    /// # Errors
+    ///
+    /// Return [`FunctionError`] with the message the function set on its output map.
+    pub fn invoke(&self, args: &Map) -> Result<Map, FunctionError> {
+        unsafe {
+            let out = Map::from_ptr((self.api.createMap)(), self.api);
+            (self.api.callFunction)(self.as_ptr(), args.as_ptr(), out.as_ptr());
+            match out.get_error() {
+                Some(msg) => Err(FunctionError(msg.to_string_lossy().into_owned())),
+                None => Ok(out),
+            }
+        }
+    }
 }
 
 impl Drop for Function {
@@ -36,3 +65,81 @@ impl Clone for Function {
         unsafe { Self::from_ptr((self.api.addFunctionRef)(self.as_ptr()), self.api) }
     }
 }
+
+// MARK: Closure
+
+pub(crate) unsafe extern "system-unwind" fn call_closure<F, E>(
+    in_: *const ffi::VSMap,
+    out: *mut ffi::VSMap,
+    user_data: *mut c_void,
+    core: *mut ffi::VSCore,
+    vsapi: *const ffi::VSAPI,
+) where
+    F: Fn(&Map, &mut Map, CoreRef) -> Result<(), E>,
+    E: AsRef<CStr>,
+{
+    unsafe {
+        let api = Api::from_ptr(vsapi);
+        let func = &*user_data.cast::<F>();
+        let input = MapRef::from_ptr(in_, api);
+        let mut output = MapRef::from_ptr(out, api);
+        let core = CoreRef::from_ptr(core, api);
+
+        match std::panic::catch_unwind(AssertUnwindSafe(|| func(&input, &mut output, core))) {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => output.set_error(e.as_ref()),
+            Err(p) => output.set_error(&panic_message(&*p)),
+        }
+    }
+}
+
+pub(crate) unsafe extern "system-unwind" fn free_closure<F>(user_data: *mut c_void) {
+    // There is nowhere to report a panic in the destructor, so it is swallowed
+    // instead of unwinding into `VapourSynth`.
+    let _ = std::panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+        drop(Box::from_raw(user_data.cast::<F>()));
+    }));
+}
+
+// MARK: FunctionError
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
+#[error("Function call failed: {0}")]
+pub struct FunctionError(String);
+
+#[cfg(test)]
+#[cfg(feature = "link-vs")]
+mod tests {
+    use testresult::TestResult;
+
+    use super::*;
+    use crate::{core::Core, key, map};
+
+    #[test]
+    fn closure() -> TestResult {
+        let core = Core::builder().build();
+        let double = core.function_from_closure(|input, output, _core| {
+            let val: i64 = input.get_as(key!(c"val")).map_err(|_| c"val is required")?;
+            output
+                .insert(key!(c"val"), val * 2)
+                .map_err(|_| c"Failed to set val")
+        });
+
+        let out = double.invoke(&map!(core; "val" => 21)?)?;
+        assert_eq!(out.get_as::<i64>(key!(c"val"))?, 42);
+
+        let err = double.invoke(&core.create_map()).unwrap_err();
+        assert_eq!(err.to_string(), "Function call failed: val is required");
+
+        let panics = core.function_from_closure(|_, _, _| -> Result<(), &CStr> {
+            panic!("{} went wrong", "something")
+        });
+        let err = panics.invoke(&core.create_map()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Function call failed: something went wrong"
+        );
+
+        Ok(())
+    }
+}
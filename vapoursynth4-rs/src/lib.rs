@@ -12,9 +12,15 @@ pub mod core;
 pub mod frame;
 pub mod function;
 pub mod map;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 pub mod node;
+pub mod output;
 pub mod plugin;
 pub mod sciprt;
+pub mod source;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod utils;
 
 pub use vapoursynth4_sys as ffi;
@@ -1,5 +1,7 @@
 use std::{
     ffi::{CStr, c_char, c_int},
+    fmt::{self, Debug, Write},
+    iter::FusedIterator,
     mem::ManuallyDrop,
     ops::{Deref, DerefMut},
 };
@@ -14,13 +16,20 @@ use crate::{
     node::{AudioNode, Node, VideoNode},
 };
 
+mod convert;
+#[cfg(feature = "json")]
+mod json;
 mod key;
+
+pub use convert::*;
+#[cfg(feature = "json")]
+pub use json::*;
 pub use key::*;
 
 // MARK: MapRef
 
 /// A borrowed reference to a [`ffi::VSMap`].
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct MapRef<'m> {
     handle: *const ffi::VSMap,
     api: Api,
@@ -62,10 +71,16 @@ impl DerefMut for MapRef<'_> {
     }
 }
 
+impl Debug for MapRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
 // MARK: Map
 
 /// An owned [`ffi::VSMap`].
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(PartialEq, Eq, Hash)]
 pub struct Map {
     handle: *const ffi::VSMap,
     api: Api,
@@ -145,6 +160,14 @@ impl Map {
         if res == -1 { None } else { Some(res) }
     }
 
+    /// Returns the type of the elements associated with `key`,
+    /// or [`PropertyType::Unset`] if there is no such key.
+    #[must_use]
+    pub fn get_type(&self, key: &KeyStr) -> PropertyType {
+        // safety: `self.handle` and `key` are valid pointers
+        unsafe { (self.api.mapGetType)(self.as_ptr(), key.as_ptr()) }
+    }
+
     unsafe fn get_internal<T>(
         &self,
         func: unsafe extern "system-unwind" fn(
@@ -285,7 +308,7 @@ impl Map {
         use ffi::VSPropertyType as t;
 
         unsafe {
-            match (self.api.mapGetType)(self.as_ptr(), key.as_ptr()) {
+            match self.get_type(key) {
                 t::Unset => Err(MapPropertyError::KeyNotFound),
                 t::Int => self.get_int(key, index).map(Value::Int),
                 t::Float => self.get_float(key, index).map(Value::Float),
@@ -319,6 +342,15 @@ impl Map {
         }
     }
 
+    /// Like [`Map::get`], but copies the data so that the value does not borrow the map.
+    ///
+    /// # Errors
+    ///
+    /// Return [`MapPropertyError`] if the underlying API does not success
+    pub fn get_owned(&self, key: &KeyStr, index: i32) -> Result<OwnedValue, MapPropertyError> {
+        self.get(key, index).map(OwnedValue::from)
+    }
+
     /// # Errors
     ///
     /// Return [`MapPropertyError`] if the underlying API does not success
@@ -380,7 +412,7 @@ impl Map {
     pub fn set_empty(&mut self, key: &KeyStr, type_: ffi::VSPropertyType) {
         // safety: `self.handle` is a valid pointer
         let res = unsafe { (self.api.mapSetEmpty)(self.as_ptr(), key.as_ptr(), type_) };
-        assert!(res != 0);
+        assert!(res == 0);
     }
 
     unsafe fn set_internal<T>(
@@ -547,6 +579,313 @@ impl Map {
     }
 }
 
+// MARK: Iter
+
+impl Map {
+    /// Returns an iterator over the keys and their elements, in key order.
+    #[must_use]
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            map: self,
+            index: 0,
+            len: self.len(),
+        }
+    }
+
+    /// Returns an iterator over the keys, in key order.
+    #[must_use]
+    pub fn keys(&self) -> Keys<'_> {
+        Keys { inner: self.iter() }
+    }
+
+    /// Copies every key of `other` accepted by `filter` into `self`,
+    /// replacing the existing elements of that key.
+    ///
+    /// Empty keys keep their type and data keeps its type hint.
+    ///
+    /// # Errors
+    ///
+    /// Return [`MapPropertyError`] if the underlying API does not success
+    pub fn extend_from(
+        &mut self,
+        other: &Map,
+        mut filter: impl FnMut(&KeyStr) -> bool,
+    ) -> Result<(), MapPropertyError> {
+        for key in other.keys() {
+            if !filter(key) {
+                continue;
+            }
+
+            self.delete_key(key);
+            let len = other.num_elements(key).unwrap_or(0);
+            if len == 0 {
+                self.set_empty(key, other.get_type(key));
+            }
+            for index in 0..len {
+                self.copy_element(other, key, index)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Appends element `index` of `key` in `other` to the same key of `self`.
+    fn copy_element(
+        &mut self,
+        other: &Map,
+        key: &KeyStr,
+        index: i32,
+    ) -> Result<(), MapPropertyError> {
+        if other.get_type(key) != PropertyType::Data {
+            return self.set(key, other.get(key, index)?, AppendMode::Append);
+        }
+
+        // safety: The data is valid until `other` is modified, and is copied by `mapSetData`
+        unsafe {
+            let hint = other.get_internal(other.api.mapGetDataTypeHint, key, index)?;
+            let size = other.get_internal(other.api.mapGetDataSize, key, index)?;
+            let ptr = other.get_internal(other.api.mapGetData, key, index)?;
+            handle_set_error((self.api.mapSetData)(
+                self.as_ptr(),
+                key.as_ptr(),
+                ptr,
+                size,
+                hint,
+                AppendMode::Append,
+            ))
+        }
+    }
+
+    /// Copies every key and its elements out of the map, in key order.
+    ///
+    /// # Errors
+    ///
+    /// Return [`MapPropertyError`] if an element can not be read
+    pub fn to_vec(&self) -> Result<Vec<(Key, Vec<OwnedValue>)>, MapPropertyError> {
+        self.iter()
+            .map(|(key, values)| {
+                let values = values
+                    .map(|v| v.map(OwnedValue::from))
+                    .collect::<Result<_, _>>()?;
+                Ok((key.into(), values))
+            })
+            .collect()
+    }
+
+    /// Retains only the keys specified by the predicate.
+    pub fn retain(&mut self, mut f: impl FnMut(&KeyStr) -> bool) {
+        // Deleting a key only shifts the indices of the keys after it
+        for i in (0..self.len()).rev() {
+            let key = self.get_key(i);
+            if !f(key) {
+                let key = Key::from(key);
+                self.delete_key(&key);
+            }
+        }
+    }
+}
+
+impl<'m> IntoIterator for &'m Map {
+    type Item = (&'m KeyStr, Values<'m>);
+    type IntoIter = Iter<'m>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of a [`Map`], created by [`Map::iter`].
+#[derive(Clone)]
+pub struct Iter<'m> {
+    map: &'m Map,
+    index: i32,
+    len: i32,
+}
+
+impl<'m> Iterator for Iter<'m> {
+    type Item = (&'m KeyStr, Values<'m>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.len {
+            return None;
+        }
+
+        let key = self.map.get_key(self.index);
+        self.index += 1;
+        Some((key, Values::new(self.map, key)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        #[allow(clippy::cast_sign_loss)]
+        let len = (self.len - self.index) as usize;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+impl FusedIterator for Iter<'_> {}
+
+/// An iterator over the keys of a [`Map`], created by [`Map::keys`].
+#[derive(Clone)]
+pub struct Keys<'m> {
+    inner: Iter<'m>,
+}
+
+impl<'m> Iterator for Keys<'m> {
+    type Item = &'m KeyStr;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Keys<'_> {}
+impl FusedIterator for Keys<'_> {}
+
+/// An iterator over the elements associated with a key.
+///
+/// Yields an error for an element that can not be read, instead of ending early.
+#[derive(Clone)]
+pub struct Values<'m> {
+    map: &'m Map,
+    key: &'m KeyStr,
+    index: i32,
+    len: i32,
+}
+
+impl<'m> Values<'m> {
+    fn new(map: &'m Map, key: &'m KeyStr) -> Self {
+        Self {
+            map,
+            key,
+            index: 0,
+            len: map.num_elements(key).unwrap_or(0),
+        }
+    }
+
+    /// Returns the key these elements belong to.
+    #[must_use]
+    pub fn key(&self) -> &'m KeyStr {
+        self.key
+    }
+}
+
+impl<'m> Iterator for Values<'m> {
+    type Item = Result<Value<'m>, MapPropertyError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.len {
+            return None;
+        }
+
+        let val = self.map.get(self.key, self.index);
+        self.index += 1;
+        Some(val)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        #[allow(clippy::cast_sign_loss)]
+        let len = (self.len - self.index) as usize;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Values<'_> {}
+impl FusedIterator for Values<'_> {}
+
+// MARK: Debug
+
+impl Debug for Map {
+    /// Formats the map like the `repr` of Python's `vapoursynth.FrameProps`.
+    /// Keys with a single element are printed as a scalar, others as a list.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<vapoursynth.FrameProps {")?;
+        for (i, (key, values)) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "'{key}': ")?;
+
+            let write_value = |f: &mut fmt::Formatter<'_>, val| match val {
+                Ok(val) => write!(f, "{}", PyRepr(&val)),
+                Err(e) => write!(f, "<error: {e}>"),
+            };
+            if values.len() == 1 {
+                for val in values {
+                    write_value(f, val)?;
+                }
+            } else {
+                f.write_char('[')?;
+                for (j, val) in values.enumerate() {
+                    if j > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write_value(f, val)?;
+                }
+                f.write_char(']')?;
+            }
+        }
+        f.write_str("}>")
+    }
+}
+
+/// Formats a [`Value`] like Python's `repr` of the corresponding object.
+struct PyRepr<'a, 'm>(&'a Value<'m>);
+
+impl fmt::Display for PyRepr<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Value::Int(val) => write!(f, "{val}"),
+            Value::Float(val) => write!(f, "{val:?}"),
+            Value::Data(val) => {
+                f.write_str("b'")?;
+                for &b in *val {
+                    match b {
+                        b'\\' => f.write_str("\\\\")?,
+                        b'\'' => f.write_str("\\'")?,
+                        b'\t' => f.write_str("\\t")?,
+                        b'\n' => f.write_str("\\n")?,
+                        b'\r' => f.write_str("\\r")?,
+                        0x20..0x7f => f.write_char(b.into())?,
+                        _ => write!(f, "\\x{b:02x}")?,
+                    }
+                }
+                f.write_char('\'')
+            }
+            Value::Utf8(val) => {
+                f.write_char('\'')?;
+                for c in val.chars() {
+                    match c {
+                        '\'' => f.write_str("\\'")?,
+                        '"' => f.write_char('"')?,
+                        c => write!(f, "{}", c.escape_debug())?,
+                    }
+                }
+                f.write_char('\'')
+            }
+            Value::VideoNode(val) => {
+                write!(f, "<vapoursynth.VideoNode object at {:p}>", val.as_ptr())
+            }
+            Value::AudioNode(val) => {
+                write!(f, "<vapoursynth.AudioNode object at {:p}>", val.as_ptr())
+            }
+            Value::VideoFrame(val) => {
+                write!(f, "<vapoursynth.VideoFrame object at {:p}>", val.as_ptr())
+            }
+            Value::AudioFrame(val) => {
+                write!(f, "<vapoursynth.AudioFrame object at {:p}>", val.as_ptr())
+            }
+            Value::Function(val) => {
+                write!(f, "<vapoursynth.Function object at {:p}>", val.as_ptr())
+            }
+        }
+    }
+}
+
 impl Drop for Map {
     fn drop(&mut self) {
         // safety: `self.handle` is a valid pointer
@@ -601,7 +940,7 @@ fn handle_set_error(res: i32) -> Result<(), MapPropertyError> {
 
 // MARK: Types
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Value<'m> {
     Int(i64),
     Float(f64),
@@ -619,6 +958,75 @@ pub enum Value<'m> {
     Function(Function),
 }
 
+impl Value<'_> {
+    /// Copies the borrowed data out of the map.
+    ///
+    /// Unlike [`Clone`], the result does not borrow the map.
+    #[must_use]
+    pub fn to_owned_value(&self) -> OwnedValue {
+        match self {
+            Value::Int(val) => OwnedValue::Int(*val),
+            Value::Float(val) => OwnedValue::Float(*val),
+            Value::Data(val) => OwnedValue::Data(val.to_vec()),
+            Value::Utf8(val) => OwnedValue::Utf8((*val).to_owned()),
+            Value::VideoNode(val) => OwnedValue::VideoNode(val.clone()),
+            Value::AudioNode(val) => OwnedValue::AudioNode(val.clone()),
+            Value::VideoFrame(val) => OwnedValue::VideoFrame(val.clone()),
+            Value::AudioFrame(val) => OwnedValue::AudioFrame(val.clone()),
+            Value::Function(val) => OwnedValue::Function(val.clone()),
+        }
+    }
+}
+
+/// A [`Value`] which owns its data, so it can outlive the map it was read from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OwnedValue {
+    Int(i64),
+    Float(f64),
+    /// Arbitrary binary data
+    Data(Vec<u8>),
+    Utf8(String),
+    VideoNode(VideoNode),
+    AudioNode(AudioNode),
+    VideoFrame(VideoFrame),
+    AudioFrame(AudioFrame),
+    Function(Function),
+}
+
+impl OwnedValue {
+    /// Borrows the value, e.g. for passing it to [`Map::set`].
+    #[must_use]
+    pub fn as_value(&self) -> Value<'_> {
+        match self {
+            OwnedValue::Int(val) => Value::Int(*val),
+            OwnedValue::Float(val) => Value::Float(*val),
+            OwnedValue::Data(val) => Value::Data(val),
+            OwnedValue::Utf8(val) => Value::Utf8(val),
+            OwnedValue::VideoNode(val) => Value::VideoNode(val.clone()),
+            OwnedValue::AudioNode(val) => Value::AudioNode(val.clone()),
+            OwnedValue::VideoFrame(val) => Value::VideoFrame(val.clone()),
+            OwnedValue::AudioFrame(val) => Value::AudioFrame(val.clone()),
+            OwnedValue::Function(val) => Value::Function(val.clone()),
+        }
+    }
+}
+
+impl From<Value<'_>> for OwnedValue {
+    fn from(value: Value<'_>) -> Self {
+        match value {
+            Value::Int(val) => OwnedValue::Int(val),
+            Value::Float(val) => OwnedValue::Float(val),
+            Value::Data(val) => OwnedValue::Data(val.to_vec()),
+            Value::Utf8(val) => OwnedValue::Utf8(val.to_owned()),
+            Value::VideoNode(val) => OwnedValue::VideoNode(val),
+            Value::AudioNode(val) => OwnedValue::AudioNode(val),
+            Value::VideoFrame(val) => OwnedValue::VideoFrame(val),
+            Value::AudioFrame(val) => OwnedValue::AudioFrame(val),
+            Value::Function(val) => OwnedValue::Function(val),
+        }
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Error)]
 pub enum MapPropertyError {
     #[error("The requested key was not found in the map")]
@@ -629,9 +1037,12 @@ pub enum MapPropertyError {
     IndexOutOfBound,
     #[error("The map has errors. Use [`Map::get_error`] to retrieve the message")]
     MapError,
+    #[error("The value does not fit in the requested type")]
+    OutOfRange,
 }
 
 pub type AppendMode = ffi::VSMapAppendMode;
+pub type PropertyType = ffi::VSPropertyType;
 
 // MARK: Tests
 
@@ -644,6 +1055,7 @@ mod tests {
     use testresult::TestResult;
 
     use super::*;
+    use crate::key;
 
     #[test]
     fn clear() -> TestResult {
@@ -783,4 +1195,149 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn iter() -> TestResult {
+        let mut map = Map::default();
+        map.set(key!(c"b"), Value::Utf8("it's"), AppendMode::Replace)?;
+        map.set_int_array(key!(c"a"), &[1, 2])?;
+        map.set(key!(c"c"), Value::Float(0.5), AppendMode::Replace)?;
+
+        let keys: Vec<_> = map.keys().map(ToString::to_string).collect();
+        assert_eq!(keys, ["a", "b", "c"]);
+
+        let (key, values) = map.iter().next().ok_or("Map is empty")?;
+        assert_eq!(key, key!(c"a"));
+        assert_eq!(values.len(), 2);
+        let ints: Vec<_> = values
+            .map(|v| match v {
+                Ok(Value::Int(i)) => i,
+                _ => panic!("Invalid type of `{key}`"),
+            })
+            .collect();
+        assert_eq!(ints, [1, 2]);
+
+        assert_eq!(
+            format!("{map:?}"),
+            r"<vapoursynth.FrameProps {'a': [1, 2], 'b': 'it\'s', 'c': 0.5}>"
+        );
+
+        let mut other = Map::default();
+        other.extend_from(&map, |key| key != key!(c"b"))?;
+        assert_eq!(other.len(), 2);
+        assert_eq!(other.get_int_array(key!(c"a"))?, &[1, 2]);
+
+        map.retain(|key| key == key!(c"b"));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get_key(0), key!(c"b"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn extend() -> TestResult {
+        let mut map = Map::default();
+        map.set_int_array(key!(c"ints"), &[1, 2])?;
+        map.set_empty(key!(c"empty"), PropertyType::Float);
+        map.set(key!(c"name"), Value::Utf8("clip"), AppendMode::Replace)?;
+        // safety: `map` is valid and the data is copied
+        let res = unsafe {
+            (map.api.mapSetData)(
+                map.as_ptr(),
+                key!(c"raw").as_ptr(),
+                c"\x01".as_ptr(),
+                1,
+                ffi::VSDataTypeHint::Unknown,
+                AppendMode::Replace,
+            )
+        };
+        assert_eq!(res, 0);
+
+        let mut other = Map::default();
+        other.set_int_array(key!(c"empty"), &[3])?;
+        other.extend_from(&map, |_| true)?;
+        assert_eq!(other.to_vec()?, map.to_vec()?);
+        assert_eq!(other.get_type(key!(c"empty")), PropertyType::Float);
+        assert_eq!(other.num_elements(key!(c"empty")), Some(0));
+        for (key, hint) in [
+            (key!(c"name"), ffi::VSDataTypeHint::Utf8),
+            (key!(c"raw"), ffi::VSDataTypeHint::Unknown),
+        ] {
+            // safety: `other` is valid
+            let copied = unsafe { other.get_internal(other.api.mapGetDataTypeHint, key, 0)? };
+            assert_eq!(copied, hint);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn typed() -> TestResult {
+        let mut map = Map::default();
+
+        map.insert(key!(c"int"), 42)?;
+        map.insert(key!(c"float"), 0.5f32)?;
+        map.insert(key!(c"str"), "good")?;
+        map.insert(key!(c"data"), vec![1u8, 2])?;
+        map.insert(key!(c"planes"), vec![0i64, 1])?;
+        map.append(key!(c"planes"), 2)?;
+        map.insert(key!(c"empty"), Vec::<f64>::new())?;
+
+        assert_eq!(map.get_as::<i64>(key!(c"int"))?, 42);
+        assert!(map.get_as::<bool>(key!(c"int"))?);
+        assert_eq!(map.get_as::<f32>(key!(c"float"))?, 0.5);
+        assert_eq!(map.get_as::<&str>(key!(c"str"))?, "good");
+        assert_eq!(map.get_as::<Vec<u8>>(key!(c"data"))?, [1, 2]);
+        assert_eq!(map.get_as::<Vec<i32>>(key!(c"planes"))?, [0, 1, 2]);
+        assert!(map.get_all::<f64>(key!(c"empty"))?.is_empty());
+        assert_eq!(map.get_type(key!(c"empty")), PropertyType::Float);
+        map.insert(key!(c"empty"), Vec::<i64>::new())?;
+        assert_eq!(map.get_type(key!(c"empty")), PropertyType::Int);
+        assert_eq!(map.get_or(key!(c"missing"), 16)?, 16);
+        assert_eq!(
+            map.get_or(key!(c"str"), 0i64),
+            Err(MapPropertyError::InvalidType)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn owned() -> TestResult {
+        let mut map = Map::default();
+        map.insert(key!(c"name"), "clip")?;
+        map.insert(key!(c"sizes"), [1, 2])?;
+
+        let snapshot = map.to_vec()?;
+        let owned = map.get_owned(key!(c"name"), 0)?;
+        drop(map);
+
+        assert_eq!(owned, OwnedValue::Utf8("clip".to_owned()));
+        assert_eq!(owned.as_value(), Value::Utf8("clip"));
+        assert_eq!(
+            snapshot,
+            [
+                (Key::new("name")?, vec![OwnedValue::Utf8("clip".to_owned())]),
+                (
+                    Key::new("sizes")?,
+                    vec![OwnedValue::Int(1), OwnedValue::Int(2)]
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn macro_() -> TestResult {
+        let core = crate::core::Core::builder().build();
+        let map = crate::map!(core; "bits" => 16, "planes" => [0, 1, 2], "name" => "clip")?;
+
+        assert_eq!(map.get_as::<i64>(key!(c"bits"))?, 16);
+        assert_eq!(map.get_as::<Vec<i64>>(key!(c"planes"))?, [0, 1, 2]);
+        assert_eq!(map.get_as::<&str>(key!(c"name"))?, "clip");
+
+        Ok(())
+    }
 }
@@ -0,0 +1,585 @@
+use crate::{
+    frame::{AudioFrame, VideoFrame},
+    function::Function,
+    node::{AudioNode, VideoNode},
+};
+
+use super::{AppendMode, KeyStr, Map, MapPropertyError, OwnedValue, PropertyType, Value};
+
+/// Types that can be read from the elements of a [`Map`].
+pub trait FromValue<'m>: Sized {
+    /// # Errors
+    ///
+    /// Return [`MapPropertyError::InvalidType`] if `value` can not be converted to `Self`
+    fn from_value(value: Value<'m>) -> Result<Self, MapPropertyError>;
+
+    /// Reads `Self` from the elements of `key`. Defaults to the first element.
+    ///
+    /// # Errors
+    ///
+    /// Return [`MapPropertyError`] if the underlying API does not success
+    fn from_map(map: &'m Map, key: &KeyStr) -> Result<Self, MapPropertyError> {
+        Self::from_value(map.get(key, 0)?)
+    }
+}
+
+/// Types that can be stored as the elements of a [`Map`].
+pub trait IntoValue {
+    /// The property type of the stored elements.
+    const TYPE: PropertyType;
+
+    /// # Errors
+    ///
+    /// Return [`MapPropertyError`] if the underlying API does not success
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError>;
+}
+
+impl Map {
+    /// Reads `key` as `T`.
+    ///
+    /// Scalars are read from the first element, [`Vec<T>`] reads all of them.
+    ///
+    /// # Errors
+    ///
+    /// Return [`MapPropertyError`] if the key is not found or has a different type
+    pub fn get_as<'m, T: FromValue<'m>>(&'m self, key: &KeyStr) -> Result<T, MapPropertyError> {
+        T::from_map(self, key)
+    }
+
+    /// Reads every element of `key` as `T`.
+    ///
+    /// # Errors
+    ///
+    /// Return [`MapPropertyError`] if the key is not found or has a different type
+    pub fn get_all<'m, T: FromValue<'m>>(
+        &'m self,
+        key: &KeyStr,
+    ) -> Result<Vec<T>, MapPropertyError> {
+        let len = self
+            .num_elements(key)
+            .ok_or(MapPropertyError::KeyNotFound)?;
+        (0..len).map(|i| T::from_value(self.get(key, i)?)).collect()
+    }
+
+    /// Reads `key` as `T`, or returns `default` if the key is not found.
+    ///
+    /// # Errors
+    ///
+    /// Return [`MapPropertyError`] if the key exists but can not be read as `T`
+    pub fn get_or<'m, T: FromValue<'m>>(
+        &'m self,
+        key: &KeyStr,
+        default: T,
+    ) -> Result<T, MapPropertyError> {
+        match T::from_map(self, key) {
+            Err(MapPropertyError::KeyNotFound) => Ok(default),
+            res => res,
+        }
+    }
+
+    /// Replaces the elements of `key` with `value`.
+    ///
+    /// # Errors
+    ///
+    /// Return [`MapPropertyError::InvalidType`] if the `key`'s type is not the same with `value`
+    pub fn insert(&mut self, key: &KeyStr, value: impl IntoValue) -> Result<(), MapPropertyError> {
+        value.set_into(self, key, AppendMode::Replace)
+    }
+
+    /// Appends `value` to the elements of `key`.
+    ///
+    /// # Errors
+    ///
+    /// Return [`MapPropertyError::InvalidType`] if the `key`'s type is not the same with `value`
+    pub fn append(&mut self, key: &KeyStr, value: impl IntoValue) -> Result<(), MapPropertyError> {
+        value.set_into(self, key, AppendMode::Append)
+    }
+}
+
+// MARK: FromValue
+
+impl<'m> FromValue<'m> for Value<'m> {
+    fn from_value(value: Value<'m>) -> Result<Self, MapPropertyError> {
+        Ok(value)
+    }
+}
+
+impl FromValue<'_> for OwnedValue {
+    fn from_value(value: Value<'_>) -> Result<Self, MapPropertyError> {
+        Ok(value.into())
+    }
+}
+
+impl FromValue<'_> for i64 {
+    fn from_value(value: Value<'_>) -> Result<Self, MapPropertyError> {
+        match value {
+            Value::Int(val) => Ok(val),
+            _ => Err(MapPropertyError::InvalidType),
+        }
+    }
+}
+
+/// Values outside of the range of `i32` are [`MapPropertyError::OutOfRange`],
+/// use [`Map::get_int_saturated`] to saturate them instead.
+impl FromValue<'_> for i32 {
+    fn from_value(value: Value<'_>) -> Result<Self, MapPropertyError> {
+        i32::try_from(i64::from_value(value)?).map_err(|_| MapPropertyError::OutOfRange)
+    }
+}
+
+impl FromValue<'_> for bool {
+    fn from_value(value: Value<'_>) -> Result<Self, MapPropertyError> {
+        i64::from_value(value).map(|val| val != 0)
+    }
+}
+
+impl FromValue<'_> for f64 {
+    fn from_value(value: Value<'_>) -> Result<Self, MapPropertyError> {
+        match value {
+            Value::Float(val) => Ok(val),
+            _ => Err(MapPropertyError::InvalidType),
+        }
+    }
+}
+
+/// Finite values outside of the range of `f32` are [`MapPropertyError::OutOfRange`],
+/// use [`Map::get_float_saturated`] to saturate them instead.
+impl FromValue<'_> for f32 {
+    fn from_value(value: Value<'_>) -> Result<Self, MapPropertyError> {
+        let val = f64::from_value(value)?;
+        if val.is_finite() && val.abs() > f64::from(f32::MAX) {
+            return Err(MapPropertyError::OutOfRange);
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        Ok(val as f32)
+    }
+}
+
+/// [`Value::Data`] is accepted as long as it is valid UTF-8.
+impl<'m> FromValue<'m> for &'m str {
+    fn from_value(value: Value<'m>) -> Result<Self, MapPropertyError> {
+        match value {
+            Value::Utf8(val) => Ok(val),
+            Value::Data(val) => str::from_utf8(val).map_err(|_| MapPropertyError::InvalidType),
+            _ => Err(MapPropertyError::InvalidType),
+        }
+    }
+}
+
+impl FromValue<'_> for String {
+    fn from_value(value: Value<'_>) -> Result<Self, MapPropertyError> {
+        <&str>::from_value(value).map(ToOwned::to_owned)
+    }
+}
+
+impl<'m> FromValue<'m> for &'m [u8] {
+    fn from_value(value: Value<'m>) -> Result<Self, MapPropertyError> {
+        match value {
+            Value::Data(val) => Ok(val),
+            Value::Utf8(val) => Ok(val.as_bytes()),
+            _ => Err(MapPropertyError::InvalidType),
+        }
+    }
+}
+
+impl FromValue<'_> for Vec<u8> {
+    fn from_value(value: Value<'_>) -> Result<Self, MapPropertyError> {
+        <&[u8]>::from_value(value).map(ToOwned::to_owned)
+    }
+}
+
+/// Reads all elements of a key when used with [`Map::get_as`].
+///
+/// A single element, e.g. in [`Map::get_all`], is read as a `Vec` of that element.
+impl<'m, T: FromValue<'m>> FromValue<'m> for Vec<T> {
+    fn from_value(value: Value<'m>) -> Result<Self, MapPropertyError> {
+        T::from_value(value).map(|val| vec![val])
+    }
+
+    fn from_map(map: &'m Map, key: &KeyStr) -> Result<Self, MapPropertyError> {
+        map.get_all(key)
+    }
+}
+
+macro_rules! from_value_variant {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl FromValue<'_> for $ty {
+                fn from_value(value: Value<'_>) -> Result<Self, MapPropertyError> {
+                    match value {
+                        Value::$variant(val) => Ok(val),
+                        _ => Err(MapPropertyError::InvalidType),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+from_value_variant!(
+    VideoNode => VideoNode,
+    AudioNode => AudioNode,
+    VideoFrame => VideoFrame,
+    AudioFrame => AudioFrame,
+    Function => Function,
+);
+
+// MARK: IntoValue
+
+impl IntoValue for i64 {
+    const TYPE: PropertyType = PropertyType::Int;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        map.set(key, Value::Int(self), append)
+    }
+}
+
+impl IntoValue for i32 {
+    const TYPE: PropertyType = PropertyType::Int;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        i64::from(self).set_into(map, key, append)
+    }
+}
+
+impl IntoValue for bool {
+    const TYPE: PropertyType = PropertyType::Int;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        i64::from(self).set_into(map, key, append)
+    }
+}
+
+impl IntoValue for f64 {
+    const TYPE: PropertyType = PropertyType::Float;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        map.set(key, Value::Float(self), append)
+    }
+}
+
+impl IntoValue for f32 {
+    const TYPE: PropertyType = PropertyType::Float;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        f64::from(self).set_into(map, key, append)
+    }
+}
+
+impl IntoValue for &str {
+    const TYPE: PropertyType = PropertyType::Data;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        map.set(key, Value::Utf8(self), append)
+    }
+}
+
+impl IntoValue for String {
+    const TYPE: PropertyType = PropertyType::Data;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        self.as_str().set_into(map, key, append)
+    }
+}
+
+impl IntoValue for &[u8] {
+    const TYPE: PropertyType = PropertyType::Data;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        map.set(key, Value::Data(self), append)
+    }
+}
+
+impl IntoValue for Vec<u8> {
+    const TYPE: PropertyType = PropertyType::Data;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        self.as_slice().set_into(map, key, append)
+    }
+}
+
+impl IntoValue for VideoNode {
+    const TYPE: PropertyType = PropertyType::VideoNode;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        map.consume_node(key, self, append)
+    }
+}
+
+impl IntoValue for &VideoNode {
+    const TYPE: PropertyType = PropertyType::VideoNode;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        self.clone().set_into(map, key, append)
+    }
+}
+
+impl IntoValue for AudioNode {
+    const TYPE: PropertyType = PropertyType::AudioNode;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        map.consume_node(key, self, append)
+    }
+}
+
+impl IntoValue for &AudioNode {
+    const TYPE: PropertyType = PropertyType::AudioNode;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        self.clone().set_into(map, key, append)
+    }
+}
+
+impl IntoValue for VideoFrame {
+    const TYPE: PropertyType = PropertyType::VideoFrame;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        map.consume_frame(key, self, append)
+    }
+}
+
+impl IntoValue for &VideoFrame {
+    const TYPE: PropertyType = PropertyType::VideoFrame;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        self.clone().set_into(map, key, append)
+    }
+}
+
+impl IntoValue for AudioFrame {
+    const TYPE: PropertyType = PropertyType::AudioFrame;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        map.consume_frame(key, self, append)
+    }
+}
+
+impl IntoValue for &AudioFrame {
+    const TYPE: PropertyType = PropertyType::AudioFrame;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        self.clone().set_into(map, key, append)
+    }
+}
+
+impl IntoValue for Function {
+    const TYPE: PropertyType = PropertyType::Function;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        map.consume_function(key, self, append)
+    }
+}
+
+impl IntoValue for &Function {
+    const TYPE: PropertyType = PropertyType::Function;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        self.clone().set_into(map, key, append)
+    }
+}
+
+/// Stores every item as an element of the key.
+/// An empty [`Vec`] leaves an empty key of type [`IntoValue::TYPE`] when replacing.
+impl<T: IntoValue> IntoValue for Vec<T> {
+    const TYPE: PropertyType = T::TYPE;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        if self.is_empty() {
+            if append == AppendMode::Replace {
+                map.delete_key(key);
+                map.set_empty(key, T::TYPE);
+            }
+            return Ok(());
+        }
+
+        let mut append = append;
+        for val in self {
+            val.set_into(map, key, append)?;
+            append = AppendMode::Append;
+        }
+        Ok(())
+    }
+}
+
+impl<T: IntoValue, const N: usize> IntoValue for [T; N] {
+    const TYPE: PropertyType = T::TYPE;
+
+    fn set_into(
+        self,
+        map: &mut Map,
+        key: &KeyStr,
+        append: AppendMode,
+    ) -> Result<(), MapPropertyError> {
+        Vec::from(self).set_into(map, key, append)
+    }
+}
+
+// MARK: Macro
+
+/// Builds a [`Map`] from `key => value` pairs.
+///
+/// Keys are string literals validated at compile time like [`key!`](crate::key!).
+/// Values are converted with [`IntoValue`], so arrays and [`Vec`]s become multiple elements.
+/// Evaluates to `Result<Map, MapPropertyError>`.
+///
+/// ```ignore
+/// let args = map!(core; "clip" => node, "bits" => 16, "planes" => [0, 1, 2])?;
+/// ```
+#[macro_export]
+macro_rules! map {
+    ($core:expr $(; $($key:literal => $val:expr),* $(,)?)?) => {
+        'map: {
+            #[allow(unused_mut)]
+            let mut map = $core.create_map();
+            $($(
+                let key = const {
+                    match ::std::ffi::CStr::from_bytes_with_nul(concat!($key, "\0").as_bytes()) {
+                        Ok(key) => $crate::map::KeyStr::from_cstr(key),
+                        Err(_) => panic!("Key must not contain NUL"),
+                    }
+                };
+                if let Err(e) = map.insert(key, $val) {
+                    break 'map Err(e);
+                }
+            )*)?
+            Ok::<_, $crate::map::MapPropertyError>(map)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn ranges() {
+        assert_eq!(i32::from_value(Value::Int(-1)), Ok(-1));
+        assert_eq!(
+            i32::from_value(Value::Int(1 << 31)),
+            Err(MapPropertyError::OutOfRange)
+        );
+        assert_eq!(
+            i32::from_value(Value::Float(0.0)),
+            Err(MapPropertyError::InvalidType)
+        );
+
+        assert_eq!(f32::from_value(Value::Float(0.5)), Ok(0.5));
+        assert_eq!(
+            f32::from_value(Value::Float(f64::INFINITY)),
+            Ok(f32::INFINITY)
+        );
+        assert!(f32::from_value(Value::Float(f64::NAN)).is_ok_and(f32::is_nan));
+        assert_eq!(
+            f32::from_value(Value::Float(-1e39)),
+            Err(MapPropertyError::OutOfRange)
+        );
+
+        assert_eq!(Vec::<i64>::from_value(Value::Int(3)), Ok(vec![3]));
+    }
+}
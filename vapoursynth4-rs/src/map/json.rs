@@ -0,0 +1,191 @@
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use serde_json::{Map as Object, Value as Json};
+use thiserror::Error;
+
+use crate::core::Core;
+
+use super::{AppendMode, InvalidKey, Key, Map, MapPropertyError, PropertyType, PyRepr, Value};
+
+/// Object key of binary data.
+const BASE64_KEY: &str = "base64";
+/// Object key of values that can not be represented in JSON.
+const PLACEHOLDER_KEY: &str = "placeholder";
+
+impl Map {
+    /// Converts the map into a JSON object.
+    ///
+    /// - Ints and floats become numbers. Non-finite floats become `null`.
+    /// - UTF-8 data becomes strings, binary data becomes `{"base64": "..."}`.
+    /// - Keys with exactly one element become a scalar, others become an array.
+    /// - Nodes, frames and functions become `{"placeholder": "<vapoursynth.VideoNode ...>"}`.
+    ///
+    /// # Errors
+    ///
+    /// Return [`MapPropertyError`] if an element can not be read.
+    pub fn to_json(&self) -> Result<Json, MapPropertyError> {
+        let obj = self
+            .iter()
+            .map(|(key, values)| {
+                let mut json = values
+                    .map(|v| v.map(|v| value_to_json(&v)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let json = if json.len() == 1 {
+                    json.remove(0)
+                } else {
+                    Json::Array(json)
+                };
+                Ok((key.to_string(), json))
+            })
+            .collect::<Result<_, MapPropertyError>>()?;
+
+        Ok(Json::Object(obj))
+    }
+
+    /// Creates a map from a JSON object produced by [`Map::to_json`].
+    ///
+    /// An empty array carries no type and is imported as an empty int array.
+    ///
+    /// # Errors
+    ///
+    /// Return [`JsonError`] if `json` is not an object, a key is invalid,
+    /// or a value has no map representation, like `null` or a placeholder.
+    pub fn from_json(core: impl AsRef<Core>, json: &Json) -> Result<Map, JsonError> {
+        let Json::Object(obj) = json else {
+            return Err(JsonError::NotAnObject);
+        };
+
+        let mut map = core.as_ref().create_map();
+        for (key, json) in obj {
+            let key = Key::new(key.as_str())?;
+            match json {
+                Json::Array(values) if values.is_empty() => {
+                    map.delete_key(&key);
+                    map.set_empty(&key, PropertyType::Int);
+                }
+                Json::Array(values) => {
+                    for (i, json) in values.iter().enumerate() {
+                        let append = if i == 0 {
+                            AppendMode::Replace
+                        } else {
+                            AppendMode::Append
+                        };
+                        set_json(&mut map, &key, json, append)?;
+                    }
+                }
+                json => set_json(&mut map, &key, json, AppendMode::Replace)?,
+            }
+        }
+
+        Ok(map)
+    }
+}
+
+fn value_to_json(value: &Value) -> Json {
+    match value {
+        Value::Int(val) => Json::from(*val),
+        Value::Float(val) => Json::from(*val),
+        Value::Utf8(val) => Json::from(*val),
+        Value::Data(val) => Json::Object(Object::from_iter([(
+            BASE64_KEY.into(),
+            BASE64.encode(val).into(),
+        )])),
+        _ => Json::Object(Object::from_iter([(
+            PLACEHOLDER_KEY.into(),
+            PyRepr(value).to_string().into(),
+        )])),
+    }
+}
+
+fn set_json(map: &mut Map, key: &Key, json: &Json, append: AppendMode) -> Result<(), JsonError> {
+    let unsupported = || JsonError::Unsupported(key.clone());
+
+    match json {
+        Json::Number(n) => match n.as_i64() {
+            Some(val) => map.set(key, Value::Int(val), append)?,
+            None => map.set(
+                key,
+                Value::Float(n.as_f64().ok_or_else(unsupported)?),
+                append,
+            )?,
+        },
+        Json::String(val) => map.set(key, Value::Utf8(val), append)?,
+        Json::Object(obj) => {
+            let Some(Json::String(data)) = obj.get(BASE64_KEY) else {
+                return Err(unsupported());
+            };
+            let data = BASE64
+                .decode(data)
+                .map_err(|_| JsonError::InvalidBase64(key.clone()))?;
+            map.set(key, Value::Data(&data), append)?;
+        }
+        Json::Null | Json::Bool(_) | Json::Array(_) => return Err(unsupported()),
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Error)]
+pub enum JsonError {
+    #[error("Expected a JSON object")]
+    NotAnObject,
+    #[error(transparent)]
+    InvalidKey(#[from] InvalidKey),
+    #[error("The value of key `{0}` can not be stored in a map")]
+    Unsupported(Key),
+    #[error("The binary data of key `{0}` is not valid base64")]
+    InvalidBase64(Key),
+    #[error(transparent)]
+    Map(#[from] MapPropertyError),
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use testresult::TestResult;
+
+    use super::*;
+    use crate::{key, mock};
+
+    #[test]
+    #[cfg(feature = "link-vs")]
+    fn round_trip() -> TestResult {
+        let core = Core::builder().build();
+        let mut map = core.create_map();
+        map.insert(key!(c"_Matrix"), 1)?;
+        map.insert(key!(c"_Gamma"), [2.2, 2.4])?;
+        map.insert(key!(c"Name"), "clip")?;
+        map.insert(key!(c"Blob"), vec![0u8, 1, 2])?;
+
+        let json = map.to_json()?;
+        assert_eq!(
+            json,
+            json!({
+                "Blob": { "base64": "AAEC" },
+                "Name": "clip",
+                "_Gamma": [2.2, 2.4],
+                "_Matrix": 1,
+            })
+        );
+
+        let map = Map::from_json(&core, &json)?;
+        assert_eq!(map.to_json()?, json);
+
+        let res = Map::from_json(&core, &json!({ "clip": { "placeholder": "VideoNode" } }));
+        assert!(matches!(res, Err(JsonError::Unsupported(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn empty_array() -> TestResult {
+        let core = Core::builder().api(mock::api()).build();
+        let json = json!({ "planes": [] });
+
+        let map = Map::from_json(&core, &json)?;
+        assert_eq!(map.get_type(key!(c"planes")), PropertyType::Int);
+        assert_eq!(map.num_elements(key!(c"planes")), Some(0));
+        assert_eq!(map.to_json()?, json);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,449 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! A `VSAPI` implemented in pure Rust, for unit testing filters without `VapourSynth`.
+//!
+//! [`api`] returns an [`Api`] backed by in-memory maps, frames and nodes, so a core
+//! created with it can run [`Filter::create`](crate::node::Filter::create) and
+//! [`Node::get_frame`](crate::node::Node::get_frame) in `cargo test`:
+//!
+//! ```ignore
+//! let core = Core::builder().api(vapoursynth4_rs::mock::api()).build();
+//! let clip = core.video_source(info, Parallel, render).unwrap();
+//! let frame = clip.get_frame(0)?;
+//! ```
+//!
+//! Frame requests are rendered synchronously on the calling thread. A filter is called
+//! with [`Initial`](crate::ffi::VSActivationReason::Initial), then with
+//! [`AllFramesReady`](crate::ffi::VSActivationReason::AllFramesReady) once the frames
+//! it requested are rendered, or with [`Error`](crate::ffi::VSActivationReason::Error)
+//! if one of them failed. Frames are not cached and the filter mode is ignored.
+//!
+//! The core has no plugins: looking one up returns `None`, and other plugin functions
+//! panic. Messages logged as [`Fatal`](crate::ffi::VSMessageType::Fatal) panic instead
+//! of aborting.
+
+mod frame;
+mod map;
+mod node;
+mod vs_core;
+
+use crate::{api::Api, ffi};
+
+static API: ffi::VSAPI = ffi::VSAPI {
+    createVideoFilter: node::create_video_filter,
+    createVideoFilter2: node::create_video_filter2,
+    createAudioFilter: node::create_audio_filter,
+    createAudioFilter2: node::create_audio_filter2,
+    setLinearFilter: node::set_linear_filter,
+    setCacheMode: node::set_cache_mode,
+    setCacheOptions: node::set_cache_options,
+    freeNode: node::free_node,
+    addNodeRef: node::add_node_ref,
+    getNodeType: node::get_node_type,
+    getVideoInfo: node::get_video_info,
+    getAudioInfo: node::get_audio_info,
+    newVideoFrame: frame::new_video_frame,
+    newVideoFrame2: frame::new_video_frame2,
+    newAudioFrame: frame::new_audio_frame,
+    newAudioFrame2: frame::new_audio_frame2,
+    freeFrame: frame::free_frame,
+    addFrameRef: frame::add_frame_ref,
+    copyFrame: frame::copy_frame,
+    getFramePropertiesRO: frame::get_frame_properties_ro,
+    getFramePropertiesRW: frame::get_frame_properties_rw,
+    getStride: frame::get_stride,
+    getReadPtr: frame::get_read_ptr,
+    getWritePtr: frame::get_write_ptr,
+    getVideoFrameFormat: frame::get_video_frame_format,
+    getAudioFrameFormat: frame::get_audio_frame_format,
+    getFrameType: frame::get_frame_type,
+    getFrameWidth: frame::get_frame_width,
+    getFrameHeight: frame::get_frame_height,
+    getFrameLength: frame::get_frame_length,
+    getVideoFormatName: frame::get_video_format_name,
+    getAudioFormatName: frame::get_audio_format_name,
+    queryVideoFormat: frame::query_video_format,
+    queryAudioFormat: frame::query_audio_format,
+    queryVideoFormatID: frame::query_video_format_id,
+    getVideoFormatByID: frame::get_video_format_by_id,
+    getFrame: node::get_frame,
+    getFrameAsync: node::get_frame_async,
+    getFrameFilter: node::get_frame_filter,
+    requestFrameFilter: node::request_frame_filter,
+    releaseFrameEarly: node::release_frame_early,
+    cacheFrame: node::cache_frame,
+    setFilterError: node::set_filter_error,
+    createFunction: vs_core::create_function,
+    freeFunction: vs_core::free_function,
+    addFunctionRef: vs_core::add_function_ref,
+    callFunction: vs_core::call_function,
+    createMap: map::create_map,
+    freeMap: map::free_map,
+    clearMap: map::clear_map,
+    copyMap: map::copy_map,
+    mapSetError: map::map_set_error,
+    mapGetError: map::map_get_error,
+    mapNumKeys: map::map_num_keys,
+    mapGetKey: map::map_get_key,
+    mapDeleteKey: map::map_delete_key,
+    mapNumElements: map::map_num_elements,
+    mapGetType: map::map_get_type,
+    mapSetEmpty: map::map_set_empty,
+    mapGetInt: map::map_get_int,
+    mapGetIntSaturated: map::map_get_int_saturated,
+    mapGetIntArray: map::map_get_int_array,
+    mapSetInt: map::map_set_int,
+    mapSetIntArray: map::map_set_int_array,
+    mapGetFloat: map::map_get_float,
+    mapGetFloatSaturated: map::map_get_float_saturated,
+    mapGetFloatArray: map::map_get_float_array,
+    mapSetFloat: map::map_set_float,
+    mapSetFloatArray: map::map_set_float_array,
+    mapGetData: map::map_get_data,
+    mapGetDataSize: map::map_get_data_size,
+    mapGetDataTypeHint: map::map_get_data_type_hint,
+    mapSetData: map::map_set_data,
+    mapGetNode: map::map_get_node,
+    mapSetNode: map::map_set_node,
+    mapConsumeNode: map::map_consume_node,
+    mapGetFrame: map::map_get_frame,
+    mapSetFrame: map::map_set_frame,
+    mapConsumeFrame: map::map_consume_frame,
+    mapGetFunction: map::map_get_function,
+    mapSetFunction: map::map_set_function,
+    mapConsumeFunction: map::map_consume_function,
+    registerFunction: vs_core::register_function,
+    getPluginByID: vs_core::get_plugin_by_id,
+    getPluginByNamespace: vs_core::get_plugin_by_namespace,
+    getNextPlugin: vs_core::get_next_plugin,
+    getPluginName: vs_core::get_plugin_string,
+    getPluginID: vs_core::get_plugin_string,
+    getPluginNamespace: vs_core::get_plugin_string,
+    getNextPluginFunction: vs_core::get_next_plugin_function,
+    getPluginFunctionByName: vs_core::get_plugin_function_by_name,
+    getPluginFunctionName: vs_core::get_plugin_function_string,
+    getPluginFunctionArguments: vs_core::get_plugin_function_string,
+    getPluginFunctionReturnType: vs_core::get_plugin_function_string,
+    getPluginPath: vs_core::get_plugin_path,
+    getPluginVersion: vs_core::get_plugin_version,
+    invoke: vs_core::invoke,
+    createCore: vs_core::create_core,
+    freeCore: vs_core::free_core,
+    setMaxCacheSize: vs_core::set_max_cache_size,
+    setThreadCount: vs_core::set_thread_count,
+    getCoreInfo: vs_core::get_core_info,
+    getAPIVersion: vs_core::get_api_version,
+    logMessage: vs_core::log_message,
+    addLogHandler: vs_core::add_log_handler,
+    removeLogHandler: vs_core::remove_log_handler,
+    #[cfg(feature = "vs-41")]
+    clearNodeCache: node::clear_node_cache,
+    #[cfg(feature = "vs-41")]
+    clearCoreCaches: vs_core::clear_core_caches,
+    #[cfg(feature = "vs-41")]
+    getNodeName: node::get_node_name,
+    #[cfg(feature = "vs-41")]
+    getNodeFilterMode: node::get_node_filter_mode,
+    #[cfg(feature = "vs-41")]
+    getNumNodeDependencies: node::get_num_node_dependencies,
+    #[cfg(feature = "vs-41")]
+    getNodeDependencies: node::get_node_dependencies,
+    getCoreNodeTiming: vs_core::get_core_node_timing,
+    setCoreNodeTiming: vs_core::set_core_node_timing,
+    getNodeProcessingTime: node::get_node_processing_time,
+    getFreedNodeProcessingTime: vs_core::get_freed_node_processing_time,
+    #[cfg(feature = "vs-graph")]
+    getNodeCreationFunctionName: node::get_node_creation_function_name,
+    #[cfg(feature = "vs-graph")]
+    getNodeCreationFunctionArguments: node::get_node_creation_function_arguments,
+};
+
+/// Returns the mock API, to be passed to [`Core::builder`](crate::core::Core::builder).
+#[must_use]
+pub fn api() -> Api {
+    // SAFETY: `API` is a complete `VSAPI` living for the whole program
+    unsafe { Api::from_ptr(&raw const API) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use testresult::TestResult;
+
+    use super::*;
+    use crate::{
+        ColorFamily, SampleType, VideoInfo,
+        core::{Core, CoreRef},
+        frame::FrameContext,
+        frame::{Frame, VideoFrame, VideoFrameMut},
+        key, map,
+        map::{MapPropertyError, MapRef},
+        node::{
+            ActivationReason, Filter, FrameStateSlot, Instance, Node, Simple, SimpleVideoFilter,
+            VideoNode, mode::Parallel,
+        },
+    };
+
+    fn core() -> Core {
+        Core::builder().api(api()).build()
+    }
+
+    #[test]
+    fn maps() -> TestResult {
+        let core = core();
+        let mut map = map!(core; "int" => 1, "floats" => [0.5, 1.5], "text" => "hello")?;
+        map.append(key!(c"int"), 2)?;
+
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get_all::<i64>(key!(c"int"))?, [1, 2]);
+        assert_eq!(map.get_float_array(key!(c"floats"))?, [0.5, 1.5]);
+        assert_eq!(map.get_as::<&str>(key!(c"text"))?, "hello");
+        assert!(matches!(
+            map.get_int(key!(c"text"), 0),
+            Err(MapPropertyError::InvalidType)
+        ));
+        assert!(matches!(
+            map.get_int(key!(c"int"), 2),
+            Err(MapPropertyError::IndexOutOfBound)
+        ));
+        assert!(matches!(
+            map.append(key!(c"int"), 0.5),
+            Err(MapPropertyError::InvalidType)
+        ));
+
+        map.delete_key(key!(c"floats"));
+        assert_eq!(map.get_key(1).to_str()?, "text");
+
+        map.set_error(c"broken");
+        assert_eq!(map.get_error(), Some(c"broken"));
+        assert!(matches!(
+            map.get_int(key!(c"int"), 0),
+            Err(MapPropertyError::MapError)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn formats() {
+        let core = core();
+        let format = core.query_video_format(ColorFamily::YUV, SampleType::Integer, 10, 1, 1);
+        assert_eq!((format.bytes_per_sample, format.num_planes), (2, 3));
+        assert_eq!(
+            core.get_video_format_name(&format).as_deref(),
+            Some("YUV420P10")
+        );
+
+        let id = core.query_video_format_id(ColorFamily::RGB, SampleType::Float, 32, 0, 0);
+        let rgbs = core.get_video_format_by_id(id);
+        assert_eq!(core.get_video_format_name(&rgbs).as_deref(), Some("RGBS"));
+
+        let invalid = core.query_video_format(ColorFamily::Gray, SampleType::Float, 8, 0, 0);
+        assert_eq!(invalid.color_family, ColorFamily::Undefined);
+
+        let audio = core.query_audio_format(SampleType::Integer, 24, 0b11);
+        assert_eq!((audio.bytes_per_sample, audio.num_channels), (4, 2));
+        assert_eq!(
+            core.get_audio_format_name(&audio).as_deref(),
+            Some("Audio24 (2 CH)")
+        );
+    }
+
+    struct Invert {
+        input: VideoNode,
+    }
+
+    impl SimpleVideoFilter for Invert {
+        type Error = &'static CStr;
+
+        const NAME: &'static CStr = c"Invert";
+        const ARGS: &'static CStr = c"clip:vnode;";
+
+        fn create(args: MapRef, _core: CoreRef) -> Result<Self, Self::Error> {
+            let input = args
+                .get_video_node(key!(c"clip"), 0)
+                .map_err(|_| c"Invert: clip is required")?;
+            Ok(Self { input })
+        }
+
+        fn input(&self) -> &VideoNode {
+            &self.input
+        }
+
+        fn process(
+            &self,
+            _n: i32,
+            src: &VideoFrame,
+            dst: &mut VideoFrameMut,
+            _core: CoreRef,
+        ) -> Result<(), Self::Error> {
+            let width = usize::try_from(src.frame_width(0)).unwrap_or(0);
+            for y in 0..isize::try_from(src.frame_height(0)).unwrap_or(0) {
+                let row = src.plane(0).wrapping_offset(y * src.stride(0));
+                let out = dst.plane_mut(0).wrapping_offset(y * dst.stride(0));
+                for x in 0..width {
+                    // SAFETY: Each row holds `width` samples
+                    unsafe { out.add(x).write(!row.add(x).read()) };
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn filters() -> TestResult {
+        let core = core();
+        let format = core.query_video_format(ColorFamily::Gray, SampleType::Integer, 8, 0, 0);
+        let info = VideoInfo {
+            format,
+            fps_num: 30,
+            fps_den: 1,
+            width: 4,
+            height: 2,
+            num_frames: 5,
+        };
+        let source = core
+            .video_source(info, Parallel, |n, frame, _| {
+                if n == 3 {
+                    return Err(c"frame 3 is broken");
+                }
+                let value = u8::try_from(n).map_err(|_| c"out of range")?;
+                unsafe { frame.plane_mut(0).add(1).write(value) };
+                Ok(())
+            })
+            .ok_or("failed to create source")?;
+
+        let args = map!(core; "clip" => source)?;
+        let mut out = core.create_map();
+        let core_ref = unsafe { CoreRef::from_ptr(core.as_ptr(), core.api()) };
+        unsafe {
+            <Simple<Invert> as Filter>::create(
+                MapRef::from_ptr(args.as_ptr(), core.api()),
+                MapRef::from_ptr(out.as_ptr(), core.api()),
+                None,
+                core_ref,
+            )
+            .map_err(CStr::to_string_lossy)?;
+        }
+        let clip = out.get_video_node(key!(c"clip"), 0)?;
+        out.clear();
+        assert_eq!(clip.info().num_frames, 5);
+
+        let get_frame = |n| {
+            clip.get_frame(n)
+                .map_err(|e| e.to_string_lossy().into_owned())
+        };
+        let frame = get_frame(2)?;
+        assert_eq!(frame.stride(0) % 64, 0);
+        assert_eq!(
+            unsafe { [*frame.plane(0), *frame.plane(0).add(1)] },
+            [255, 253]
+        );
+        let props = frame.properties().ok_or("no props")?;
+        assert_eq!(props.get_as::<i64>(key!(c"_DurationDen"))?, 30);
+
+        assert!(get_frame(3).unwrap_err().contains("frame 3 is broken"));
+        assert!(get_frame(5).is_err());
+
+        Ok(())
+    }
+
+    /// Requests `n - 1` and `n`, then requests `n` again in a second round before returning it.
+    struct TwoRounds {
+        input: VideoNode,
+    }
+
+    impl Filter for TwoRounds {
+        type Mode = Parallel;
+        type Error = &'static CStr;
+        type FrameType = VideoFrame;
+        type FilterData = ();
+        type FrameState = ();
+
+        const NAME: &'static CStr = c"TwoRounds";
+        const ARGS: &'static CStr = c"";
+        const RETURN_TYPE: &'static CStr = c"";
+
+        fn create(
+            _input: MapRef,
+            _output: MapRef,
+            _data: Option<Box<()>>,
+            _core: CoreRef,
+        ) -> Result<(), Self::Error> {
+            Err(c"TwoRounds: not a plugin function")
+        }
+
+        fn get_frame(
+            this: Instance<'_, Self>,
+            n: i32,
+            activation_reason: ActivationReason,
+            mut frame_state: FrameStateSlot<'_, ()>,
+            mut ctx: FrameContext,
+            _core: CoreRef,
+        ) -> Result<Option<VideoFrame>, Self::Error> {
+            match activation_reason {
+                ActivationReason::Initial => {
+                    ctx.request_frame_filter(n - 1, &this.input);
+                    ctx.request_frame_filter(n, &this.input);
+                    Ok(None)
+                }
+                ActivationReason::AllFramesReady if frame_state.take().is_none() => {
+                    frame_state.insert(());
+                    ctx.request_frame_filter(n, &this.input);
+                    Ok(None)
+                }
+                ActivationReason::AllFramesReady => {
+                    Ok(Some(this.input.get_frame_filter(n, &mut ctx)))
+                }
+                ActivationReason::Error => Ok(None),
+            }
+        }
+    }
+
+    #[test]
+    fn rounds() -> TestResult {
+        let core = core();
+        let format = core.query_video_format(ColorFamily::Gray, SampleType::Integer, 8, 0, 0);
+        let info = VideoInfo {
+            format,
+            fps_num: 30,
+            fps_den: 1,
+            width: 4,
+            height: 2,
+            num_frames: 3,
+        };
+        let source = core
+            .video_source(info.clone(), Parallel, |n, frame, _| {
+                let value = u8::try_from(n).map_err(|_| c"out of range")?;
+                unsafe { frame.plane_mut(0).write(value) };
+                Ok::<_, &CStr>(())
+            })
+            .ok_or("failed to create source")?;
+        let deps = [ffi::VSFilterDependency {
+            source: source.as_ptr(),
+            request_pattern: ffi::VSRequestPattern::General,
+        }];
+        let node = VideoNode::new(
+            "TwoRounds",
+            &info,
+            TwoRounds { input: source },
+            &deps,
+            &core,
+        )
+        .ok_or("failed to create TwoRounds")?;
+
+        for n in 0..3 {
+            let frame = node
+                .get_frame(n)
+                .map_err(|e| e.to_string_lossy().into_owned())?;
+            assert_eq!(i32::from(unsafe { *frame.plane(0) }), n);
+        }
+
+        Ok(())
+    }
+}
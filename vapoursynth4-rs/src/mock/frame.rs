@@ -0,0 +1,551 @@
+use std::{
+    ffi::{c_char, c_int},
+    fmt::Write,
+    ptr::{self, null},
+    slice,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::ffi::{
+    VSAudioFormat, VSColorFamily, VSCore, VSFrame, VSMap, VSMediaType, VSSampleType, VSVideoFormat,
+};
+
+use super::map::MockMap;
+
+/// Alignment of the planes and strides, the same as `VapourSynth` with AVX-512.
+const ALIGNMENT: usize = 64;
+
+#[derive(Clone, Copy)]
+#[repr(C, align(64))]
+struct Block([u8; ALIGNMENT]);
+
+/// The memory of a plane or an audio channel.
+#[derive(Clone)]
+struct Plane {
+    blocks: Vec<Block>,
+    width: c_int,
+    height: c_int,
+    stride: usize,
+}
+
+impl Plane {
+    fn new(width: c_int, height: c_int, bytes_per_sample: c_int) -> Self {
+        let [w, h, bytes] =
+            [width, height, bytes_per_sample].map(|v| usize::try_from(v).unwrap_or(0));
+        let stride = (w * bytes).next_multiple_of(ALIGNMENT);
+        Self {
+            blocks: vec![Block([0; ALIGNMENT]); stride / ALIGNMENT * h],
+            width,
+            height,
+            stride,
+        }
+    }
+}
+
+enum Format {
+    Video(VSVideoFormat),
+    Audio(VSAudioFormat, c_int),
+}
+
+/// A frame with its own plane memory and properties.
+pub(super) struct MockFrame {
+    refs: AtomicUsize,
+    format: Format,
+    planes: Vec<Plane>,
+    props: MockMap,
+}
+
+impl MockFrame {
+    unsafe fn from_ptr<'a>(f: *const VSFrame) -> &'a Self {
+        unsafe { &*f.cast() }
+    }
+
+    fn into_ptr(self) -> *mut VSFrame {
+        Box::into_raw(Box::new(self)).cast()
+    }
+
+    fn plane(&self, plane: c_int) -> &Plane {
+        usize::try_from(plane)
+            .ok()
+            .and_then(|p| self.planes.get(p))
+            .unwrap_or_else(|| panic!("mock: plane {plane} does not exist"))
+    }
+
+    fn plane_mut(&mut self, plane: c_int) -> &mut Plane {
+        usize::try_from(plane)
+            .ok()
+            .and_then(|p| self.planes.get_mut(p))
+            .unwrap_or_else(|| panic!("mock: plane {plane} does not exist"))
+    }
+
+    fn video(format: &VSVideoFormat, width: c_int, height: c_int, props: MockMap) -> Self {
+        assert!(
+            width > 0 && height > 0,
+            "mock: invalid frame size {width}x{height}"
+        );
+        let planes = (0..format.num_planes)
+            .map(|p| {
+                let (w, h) = if p == 0 {
+                    (width, height)
+                } else {
+                    (
+                        width >> format.sub_sampling_w,
+                        height >> format.sub_sampling_h,
+                    )
+                };
+                Plane::new(w, h, format.bytes_per_sample)
+            })
+            .collect();
+        Self {
+            refs: AtomicUsize::new(1),
+            format: Format::Video(format.clone()),
+            planes,
+            props,
+        }
+    }
+
+    fn audio(format: &VSAudioFormat, length: c_int, props: MockMap) -> Self {
+        assert!(
+            (1..=crate::ffi::VS_AUDIO_FRAME_SAMPLES).contains(&length),
+            "mock: invalid audio frame length {length}"
+        );
+        let planes = (0..format.num_channels)
+            .map(|_| Plane::new(length, 1, format.bytes_per_sample))
+            .collect();
+        Self {
+            refs: AtomicUsize::new(1),
+            format: Format::Audio(format.clone(), length),
+            planes,
+            props,
+        }
+    }
+}
+
+/// Clones the properties of `prop_src`, or returns an empty map.
+unsafe fn props_of(prop_src: *const VSFrame) -> MockMap {
+    if prop_src.is_null() {
+        MockMap::default()
+    } else {
+        unsafe { MockFrame::from_ptr(prop_src) }.props.clone()
+    }
+}
+
+/// Copies planes from other frames, as `newVideoFrame2` and `newAudioFrame2` do.
+unsafe fn copy_planes(frame: &mut MockFrame, src: *const *const VSFrame, planes: *const c_int) {
+    let len = frame.planes.len();
+    let (src, planes) = unsafe {
+        (
+            slice::from_raw_parts(src, len),
+            slice::from_raw_parts(planes, len),
+        )
+    };
+    for ((dst, &src), &plane) in frame.planes.iter_mut().zip(src).zip(planes) {
+        if !src.is_null() {
+            let src = unsafe { MockFrame::from_ptr(src) }.plane(plane);
+            assert!(
+                (src.width, src.height) == (dst.width, dst.height),
+                "mock: copied plane has a different size"
+            );
+            dst.clone_from(src);
+        }
+    }
+}
+
+pub(super) unsafe fn add_ref(f: *const VSFrame) -> *mut VSFrame {
+    unsafe { MockFrame::from_ptr(f) }
+        .refs
+        .fetch_add(1, Ordering::Relaxed);
+    f.cast_mut()
+}
+
+pub(super) unsafe extern "system-unwind" fn new_video_frame(
+    format: *const VSVideoFormat,
+    width: c_int,
+    height: c_int,
+    prop_src: *const VSFrame,
+    _core: *mut VSCore,
+) -> *mut VSFrame {
+    let props = unsafe { props_of(prop_src) };
+    MockFrame::video(unsafe { &*format }, width, height, props).into_ptr()
+}
+
+pub(super) unsafe extern "system-unwind" fn new_video_frame2(
+    format: *const VSVideoFormat,
+    width: c_int,
+    height: c_int,
+    plane_src: *const *const VSFrame,
+    planes: *const c_int,
+    prop_src: *const VSFrame,
+    _core: *mut VSCore,
+) -> *mut VSFrame {
+    let props = unsafe { props_of(prop_src) };
+    let mut frame = MockFrame::video(unsafe { &*format }, width, height, props);
+    unsafe { copy_planes(&mut frame, plane_src, planes) };
+    frame.into_ptr()
+}
+
+pub(super) unsafe extern "system-unwind" fn new_audio_frame(
+    format: *const VSAudioFormat,
+    num_samples: c_int,
+    prop_src: *const VSFrame,
+    _core: *mut VSCore,
+) -> *mut VSFrame {
+    let props = unsafe { props_of(prop_src) };
+    MockFrame::audio(unsafe { &*format }, num_samples, props).into_ptr()
+}
+
+pub(super) unsafe extern "system-unwind" fn new_audio_frame2(
+    format: *const VSAudioFormat,
+    num_samples: c_int,
+    channel_src: *const *const VSFrame,
+    channels: *const c_int,
+    prop_src: *const VSFrame,
+    _core: *mut VSCore,
+) -> *mut VSFrame {
+    let props = unsafe { props_of(prop_src) };
+    let mut frame = MockFrame::audio(unsafe { &*format }, num_samples, props);
+    unsafe { copy_planes(&mut frame, channel_src, channels) };
+    frame.into_ptr()
+}
+
+pub(super) unsafe extern "system-unwind" fn free_frame(f: *const VSFrame) {
+    if !f.is_null()
+        && unsafe { MockFrame::from_ptr(f) }
+            .refs
+            .fetch_sub(1, Ordering::AcqRel)
+            == 1
+    {
+        drop(unsafe { Box::from_raw(f.cast::<MockFrame>().cast_mut()) });
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn add_frame_ref(f: *const VSFrame) -> *mut VSFrame {
+    unsafe { add_ref(f) }
+}
+
+pub(super) unsafe extern "system-unwind" fn copy_frame(
+    f: *const VSFrame,
+    _core: *mut VSCore,
+) -> *mut VSFrame {
+    let f = unsafe { MockFrame::from_ptr(f) };
+    MockFrame {
+        refs: AtomicUsize::new(1),
+        format: match &f.format {
+            Format::Video(format) => Format::Video(format.clone()),
+            Format::Audio(format, length) => Format::Audio(format.clone(), *length),
+        },
+        planes: f.planes.clone(),
+        props: f.props.clone(),
+    }
+    .into_ptr()
+}
+
+pub(super) unsafe extern "system-unwind" fn get_frame_properties_ro(
+    f: *const VSFrame,
+) -> *const VSMap {
+    ptr::from_ref(&unsafe { MockFrame::from_ptr(f) }.props).cast()
+}
+
+pub(super) unsafe extern "system-unwind" fn get_frame_properties_rw(f: *mut VSFrame) -> *mut VSMap {
+    unsafe { &raw mut (*f.cast::<MockFrame>()).props }.cast()
+}
+
+pub(super) unsafe extern "system-unwind" fn get_stride(f: *const VSFrame, plane: c_int) -> isize {
+    unsafe { MockFrame::from_ptr(f) }
+        .plane(plane)
+        .stride
+        .cast_signed()
+}
+
+pub(super) unsafe extern "system-unwind" fn get_read_ptr(
+    f: *const VSFrame,
+    plane: c_int,
+) -> *const u8 {
+    unsafe { MockFrame::from_ptr(f) }
+        .plane(plane)
+        .blocks
+        .as_ptr()
+        .cast()
+}
+
+pub(super) unsafe extern "system-unwind" fn get_write_ptr(
+    f: *mut VSFrame,
+    plane: c_int,
+) -> *mut u8 {
+    unsafe { &mut *f.cast::<MockFrame>() }
+        .plane_mut(plane)
+        .blocks
+        .as_mut_ptr()
+        .cast()
+}
+
+pub(super) unsafe extern "system-unwind" fn get_video_frame_format(
+    f: *const VSFrame,
+) -> *const VSVideoFormat {
+    match &unsafe { MockFrame::from_ptr(f) }.format {
+        Format::Video(format) => format,
+        Format::Audio(..) => null(),
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn get_audio_frame_format(
+    f: *const VSFrame,
+) -> *const VSAudioFormat {
+    match &unsafe { MockFrame::from_ptr(f) }.format {
+        Format::Audio(format, _) => format,
+        Format::Video(_) => null(),
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn get_frame_type(f: *const VSFrame) -> VSMediaType {
+    match unsafe { MockFrame::from_ptr(f) }.format {
+        Format::Video(_) => VSMediaType::Video,
+        Format::Audio(..) => VSMediaType::Audio,
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn get_frame_width(
+    f: *const VSFrame,
+    plane: c_int,
+) -> c_int {
+    unsafe { MockFrame::from_ptr(f) }.plane(plane).width
+}
+
+pub(super) unsafe extern "system-unwind" fn get_frame_height(
+    f: *const VSFrame,
+    plane: c_int,
+) -> c_int {
+    unsafe { MockFrame::from_ptr(f) }.plane(plane).height
+}
+
+pub(super) unsafe extern "system-unwind" fn get_frame_length(f: *const VSFrame) -> c_int {
+    match unsafe { MockFrame::from_ptr(f) }.format {
+        Format::Audio(_, length) => length,
+        Format::Video(_) => 0,
+    }
+}
+
+// MARK: Formats
+
+const UNDEFINED_VIDEO_FORMAT: VSVideoFormat = VSVideoFormat {
+    color_family: VSColorFamily::Undefined,
+    sample_type: VSSampleType::Integer,
+    bits_per_sample: 0,
+    bytes_per_sample: 0,
+    sub_sampling_w: 0,
+    sub_sampling_h: 0,
+    num_planes: 0,
+};
+
+fn video_format(
+    color_family: VSColorFamily,
+    sample_type: VSSampleType,
+    bits_per_sample: c_int,
+    sub_sampling_w: c_int,
+    sub_sampling_h: c_int,
+) -> Option<VSVideoFormat> {
+    let valid_bits = match sample_type {
+        VSSampleType::Integer => (8..=32).contains(&bits_per_sample),
+        VSSampleType::Float => matches!(bits_per_sample, 16 | 32),
+    };
+    let valid_sub_sampling = match color_family {
+        VSColorFamily::Undefined => return None,
+        VSColorFamily::Gray | VSColorFamily::RGB => (sub_sampling_w, sub_sampling_h) == (0, 0),
+        VSColorFamily::YUV => {
+            (0..=4).contains(&sub_sampling_w) && (0..=4).contains(&sub_sampling_h)
+        }
+    };
+    (valid_bits && valid_sub_sampling).then(|| VSVideoFormat {
+        color_family,
+        sample_type,
+        bits_per_sample,
+        bytes_per_sample: bytes_per_sample(bits_per_sample),
+        sub_sampling_w,
+        sub_sampling_h,
+        num_planes: if color_family == VSColorFamily::Gray {
+            1
+        } else {
+            3
+        },
+    })
+}
+
+fn bytes_per_sample(bits_per_sample: c_int) -> c_int {
+    match bits_per_sample {
+        ..=8 => 1,
+        9..=16 => 2,
+        _ => 4,
+    }
+}
+
+/// Writes `name` to a format name buffer of 32 bytes.
+unsafe fn write_name(buffer: *mut c_char, name: &str) -> c_int {
+    let len = name.len().min(31);
+    unsafe {
+        ptr::copy_nonoverlapping(name.as_ptr().cast(), buffer, len);
+        *buffer.add(len) = 0;
+    }
+    1
+}
+
+pub(super) unsafe extern "system-unwind" fn get_video_format_name(
+    format: *const VSVideoFormat,
+    buffer: *mut c_char,
+) -> c_int {
+    let f = unsafe { &*format };
+    let suffix = |name: &mut String| match (f.sample_type, f.bits_per_sample) {
+        (VSSampleType::Float, 16) => name.push('H'),
+        (VSSampleType::Float, _) => name.push('S'),
+        (VSSampleType::Integer, bits) => write!(name, "{bits}").unwrap(),
+    };
+    let mut name = String::new();
+    match f.color_family {
+        VSColorFamily::Undefined => return 0,
+        VSColorFamily::Gray => {
+            name.push_str("Gray");
+            suffix(&mut name);
+        }
+        VSColorFamily::RGB => {
+            name.push_str("RGB");
+            if f.sample_type == VSSampleType::Integer {
+                write!(name, "{}", f.bits_per_sample * 3).unwrap();
+            } else {
+                suffix(&mut name);
+            }
+        }
+        VSColorFamily::YUV => {
+            match (f.sub_sampling_w, f.sub_sampling_h) {
+                (1, 1) => name.push_str("YUV420P"),
+                (1, 0) => name.push_str("YUV422P"),
+                (0, 0) => name.push_str("YUV444P"),
+                (2, 2) => name.push_str("YUV410P"),
+                (2, 0) => name.push_str("YUV411P"),
+                (0, 1) => name.push_str("YUV440P"),
+                (w, h) => write!(name, "YUVssw{w}ssh{h}P").unwrap(),
+            }
+            suffix(&mut name);
+        }
+    }
+    unsafe { write_name(buffer, &name) }
+}
+
+pub(super) unsafe extern "system-unwind" fn get_audio_format_name(
+    format: *const VSAudioFormat,
+    buffer: *mut c_char,
+) -> c_int {
+    let f = unsafe { &*format };
+    let float = if f.sample_type == VSSampleType::Float {
+        "F"
+    } else {
+        ""
+    };
+    let name = format!("Audio{}{float} ({} CH)", f.bits_per_sample, f.num_channels);
+    unsafe { write_name(buffer, &name) }
+}
+
+pub(super) unsafe extern "system-unwind" fn query_video_format(
+    format: *mut VSVideoFormat,
+    color_family: VSColorFamily,
+    sample_type: VSSampleType,
+    bits_per_sample: c_int,
+    sub_sampling_w: c_int,
+    sub_sampling_h: c_int,
+    _core: *mut VSCore,
+) -> c_int {
+    let result = video_format(
+        color_family,
+        sample_type,
+        bits_per_sample,
+        sub_sampling_w,
+        sub_sampling_h,
+    );
+    let valid = result.is_some();
+    unsafe { format.write(result.unwrap_or(UNDEFINED_VIDEO_FORMAT)) };
+    c_int::from(valid)
+}
+
+pub(super) unsafe extern "system-unwind" fn query_audio_format(
+    format: *mut VSAudioFormat,
+    sample_type: VSSampleType,
+    bits_per_sample: c_int,
+    channel_layout: u64,
+    _core: *mut VSCore,
+) -> c_int {
+    let valid_bits = match sample_type {
+        VSSampleType::Integer => (16..=32).contains(&bits_per_sample),
+        VSSampleType::Float => bits_per_sample == 32,
+    };
+    if !valid_bits || channel_layout == 0 {
+        return 0;
+    }
+    unsafe {
+        format.write(VSAudioFormat {
+            sample_type,
+            bits_per_sample,
+            bytes_per_sample: if bits_per_sample > 16 { 4 } else { 2 },
+            num_channels: channel_layout.count_ones().cast_signed(),
+            channel_layout,
+        });
+    }
+    1
+}
+
+pub(super) unsafe extern "system-unwind" fn query_video_format_id(
+    color_family: VSColorFamily,
+    sample_type: VSSampleType,
+    bits_per_sample: c_int,
+    sub_sampling_w: c_int,
+    sub_sampling_h: c_int,
+    _core: *mut VSCore,
+) -> u32 {
+    video_format(
+        color_family,
+        sample_type,
+        bits_per_sample,
+        sub_sampling_w,
+        sub_sampling_h,
+    )
+    .map_or(0, |f| {
+        // All fields are small and non-negative
+        #[allow(clippy::cast_sign_loss)]
+        let id = ((f.color_family as u32) << 28)
+            | ((f.sample_type as u32) << 24)
+            | ((f.bits_per_sample as u32) << 16)
+            | ((f.sub_sampling_w as u32) << 8)
+            | f.sub_sampling_h as u32;
+        id
+    })
+}
+
+pub(super) unsafe extern "system-unwind" fn get_video_format_by_id(
+    format: *mut VSVideoFormat,
+    id: u32,
+    core: *mut VSCore,
+) -> c_int {
+    let color_family = match id >> 28 {
+        1 => VSColorFamily::Gray,
+        2 => VSColorFamily::RGB,
+        3 => VSColorFamily::YUV,
+        _ => VSColorFamily::Undefined,
+    };
+    let sample_type = if (id >> 24) & 0xF == 1 {
+        VSSampleType::Float
+    } else {
+        VSSampleType::Integer
+    };
+    // Each field is masked to at most 8 bits
+    #[allow(clippy::cast_possible_wrap)]
+    let [bits, sub_sampling_w, sub_sampling_h] =
+        [(id >> 16) & 0xFF, (id >> 8) & 0xFF, id & 0xFF].map(|v| v as c_int);
+    unsafe {
+        query_video_format(
+            format,
+            color_family,
+            sample_type,
+            bits,
+            sub_sampling_w,
+            sub_sampling_h,
+            core,
+        )
+    }
+}
@@ -0,0 +1,615 @@
+use std::{
+    ffi::{CStr, CString, c_char, c_double, c_float, c_int},
+    ptr::{null, null_mut},
+    slice,
+};
+
+use crate::ffi::{
+    VSDataTypeHint, VSFrame, VSFunction, VSMap, VSMapAppendMode, VSMapPropertyError, VSMediaType,
+    VSNode, VSPropertyType,
+};
+
+use super::{frame, node, vs_core};
+
+/// The values of a key, which all have the same type.
+enum Values {
+    Int(Vec<i64>),
+    Float(Vec<f64>),
+    /// Data with a trailing nul byte, so it can be returned as a C string.
+    Data(Vec<(Vec<u8>, VSDataTypeHint)>),
+    Node(VSMediaType, Vec<*mut VSNode>),
+    Frame(VSMediaType, Vec<*const VSFrame>),
+    Function(Vec<*mut VSFunction>),
+}
+
+impl Values {
+    fn property_type(&self) -> VSPropertyType {
+        match self {
+            Self::Int(_) => VSPropertyType::Int,
+            Self::Float(_) => VSPropertyType::Float,
+            Self::Data(_) => VSPropertyType::Data,
+            Self::Function(_) => VSPropertyType::Function,
+            Self::Node(VSMediaType::Video, _) => VSPropertyType::VideoNode,
+            Self::Node(VSMediaType::Audio, _) => VSPropertyType::AudioNode,
+            Self::Frame(VSMediaType::Video, _) => VSPropertyType::VideoFrame,
+            Self::Frame(VSMediaType::Audio, _) => VSPropertyType::AudioFrame,
+        }
+    }
+
+    fn empty(ty: VSPropertyType) -> Option<Self> {
+        Some(match ty {
+            VSPropertyType::Unset => return None,
+            VSPropertyType::Int => Self::Int(Vec::new()),
+            VSPropertyType::Float => Self::Float(Vec::new()),
+            VSPropertyType::Data => Self::Data(Vec::new()),
+            VSPropertyType::Function => Self::Function(Vec::new()),
+            VSPropertyType::VideoNode => Self::Node(VSMediaType::Video, Vec::new()),
+            VSPropertyType::AudioNode => Self::Node(VSMediaType::Audio, Vec::new()),
+            VSPropertyType::VideoFrame => Self::Frame(VSMediaType::Video, Vec::new()),
+            VSPropertyType::AudioFrame => Self::Frame(VSMediaType::Audio, Vec::new()),
+        })
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Int(v) => v.len(),
+            Self::Float(v) => v.len(),
+            Self::Data(v) => v.len(),
+            Self::Node(_, v) => v.len(),
+            Self::Frame(_, v) => v.len(),
+            Self::Function(v) => v.len(),
+        }
+    }
+
+    /// Appends the values of `other` if it has the same type, taking its references.
+    fn append(&mut self, mut other: Self) -> Result<(), Self> {
+        match (self, &mut other) {
+            (Self::Int(a), Self::Int(b)) => a.append(b),
+            (Self::Float(a), Self::Float(b)) => a.append(b),
+            (Self::Data(a), Self::Data(b)) => a.append(b),
+            (Self::Node(ta, a), Self::Node(tb, b)) if ta == tb => a.append(b),
+            (Self::Frame(ta, a), Self::Frame(tb, b)) if ta == tb => a.append(b),
+            (Self::Function(a), Self::Function(b)) => a.append(b),
+            _ => return Err(other),
+        }
+        Ok(())
+    }
+}
+
+impl Clone for Values {
+    fn clone(&self) -> Self {
+        // SAFETY: The values hold references to valid objects
+        unsafe {
+            match self {
+                Self::Int(v) => Self::Int(v.clone()),
+                Self::Float(v) => Self::Float(v.clone()),
+                Self::Data(v) => Self::Data(v.clone()),
+                Self::Node(t, v) => Self::Node(*t, v.iter().map(|&n| node::add_ref(n)).collect()),
+                Self::Frame(t, v) => Self::Frame(
+                    *t,
+                    v.iter().map(|&f| frame::add_ref(f).cast_const()).collect(),
+                ),
+                Self::Function(v) => {
+                    Self::Function(v.iter().map(|&f| vs_core::add_function_ref(f)).collect())
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Values {
+    fn drop(&mut self) {
+        // SAFETY: The values own one reference of each object
+        unsafe {
+            match self {
+                Self::Node(_, v) => v.iter().for_each(|&n| node::free_node(n)),
+                Self::Frame(_, v) => v.iter().for_each(|&f| frame::free_frame(f)),
+                Self::Function(v) => v.iter().for_each(|&f| vs_core::free_function(f)),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// A `VSMap` keeping its keys in insertion order.
+#[derive(Clone, Default)]
+pub(super) struct MockMap {
+    entries: Vec<(CString, Values)>,
+    error: Option<CString>,
+}
+
+impl MockMap {
+    unsafe fn from_ptr<'a>(map: *const VSMap) -> &'a Self {
+        unsafe { &*map.cast() }
+    }
+
+    unsafe fn from_ptr_mut<'a>(map: *mut VSMap) -> &'a mut Self {
+        unsafe { &mut *map.cast() }
+    }
+
+    fn get(&self, key: &CStr) -> Option<&Values> {
+        self.entries
+            .iter()
+            .find_map(|(k, v)| (k.as_c_str() == key).then_some(v))
+    }
+
+    /// Sets or appends `values` to `key`, returning whether the types matched.
+    fn set(&mut self, key: &CStr, values: Values, append: VSMapAppendMode) -> bool {
+        let existing = self.entries.iter_mut().find(|(k, _)| k.as_c_str() == key);
+        match (existing, append) {
+            (Some((_, old)), VSMapAppendMode::Append) => old.append(values).is_ok(),
+            (Some((_, old)), VSMapAppendMode::Replace) => {
+                *old = values;
+                true
+            }
+            (None, _) => {
+                self.entries.push((key.to_owned(), values));
+                true
+            }
+        }
+    }
+}
+
+fn set_error(error: *mut VSMapPropertyError, value: Option<VSMapPropertyError>) {
+    if !error.is_null() {
+        // SAFETY: `error` points to a writable value if it is not null
+        unsafe { *error = value.unwrap_or(VSMapPropertyError::Success) };
+    }
+}
+
+/// Returns the values of `key`, reporting failures through `error`.
+unsafe fn lookup<'a>(
+    map: *const VSMap,
+    key: *const c_char,
+    error: *mut VSMapPropertyError,
+) -> Option<&'a Values> {
+    let map = unsafe { MockMap::from_ptr(map) };
+    let result = if map.error.is_some() {
+        Err(VSMapPropertyError::Error)
+    } else {
+        map.get(unsafe { CStr::from_ptr(key) })
+            .ok_or(VSMapPropertyError::Unset)
+    };
+    set_error(error, result.as_ref().err().copied());
+    result.ok()
+}
+
+/// Reads element `index` of `key` with `f`, which returns `None` for the wrong type.
+unsafe fn get_with<'a, T>(
+    map: *const VSMap,
+    key: *const c_char,
+    index: c_int,
+    error: *mut VSMapPropertyError,
+    f: impl FnOnce((&'a Values, usize)) -> Option<T>,
+) -> Option<T> {
+    let values = unsafe { lookup(map, key, error) }?;
+    let Some(i) = usize::try_from(index).ok().filter(|&i| i < values.len()) else {
+        set_error(error, Some(VSMapPropertyError::Index));
+        return None;
+    };
+    let value = f((values, i));
+    if value.is_none() {
+        set_error(error, Some(VSMapPropertyError::Type));
+    }
+    value
+}
+
+/// Reads all elements of `key` with `f`, which returns `None` for the wrong type.
+unsafe fn get_array_with<'a, T>(
+    map: *const VSMap,
+    key: *const c_char,
+    error: *mut VSMapPropertyError,
+    f: impl FnOnce(&'a Values) -> Option<T>,
+) -> Option<T> {
+    let values = unsafe { lookup(map, key, error) }?;
+    let value = f(values);
+    if value.is_none() {
+        set_error(error, Some(VSMapPropertyError::Type));
+    }
+    value
+}
+
+pub(super) unsafe extern "system-unwind" fn create_map() -> *mut VSMap {
+    Box::into_raw(Box::<MockMap>::default()).cast()
+}
+
+pub(super) unsafe extern "system-unwind" fn free_map(map: *mut VSMap) {
+    if !map.is_null() {
+        drop(unsafe { Box::from_raw(map.cast::<MockMap>()) });
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn clear_map(map: *mut VSMap) {
+    let map = unsafe { MockMap::from_ptr_mut(map) };
+    map.entries.clear();
+    map.error = None;
+}
+
+pub(super) unsafe extern "system-unwind" fn copy_map(src: *const VSMap, dst: *mut VSMap) {
+    let src = unsafe { MockMap::from_ptr(src) }.clone();
+    let dst = unsafe { MockMap::from_ptr_mut(dst) };
+    for (key, values) in src.entries {
+        dst.set(&key, values, VSMapAppendMode::Replace);
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn map_set_error(map: *mut VSMap, msg: *const c_char) {
+    let map = unsafe { MockMap::from_ptr_mut(map) };
+    map.entries.clear();
+    map.error = Some(unsafe { CStr::from_ptr(msg) }.to_owned());
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_error(map: *const VSMap) -> *const c_char {
+    let map = unsafe { MockMap::from_ptr(map) };
+    map.error.as_ref().map_or(null(), |e| e.as_ptr())
+}
+
+pub(super) unsafe extern "system-unwind" fn map_num_keys(map: *const VSMap) -> c_int {
+    let map = unsafe { MockMap::from_ptr(map) };
+    map.entries.len().try_into().unwrap_or(c_int::MAX)
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_key(
+    map: *const VSMap,
+    index: c_int,
+) -> *const c_char {
+    let map = unsafe { MockMap::from_ptr(map) };
+    usize::try_from(index)
+        .ok()
+        .and_then(|i| map.entries.get(i))
+        .map_or(null(), |(key, _)| key.as_ptr())
+}
+
+pub(super) unsafe extern "system-unwind" fn map_delete_key(
+    map: *mut VSMap,
+    key: *const c_char,
+) -> c_int {
+    let map = unsafe { MockMap::from_ptr_mut(map) };
+    let key = unsafe { CStr::from_ptr(key) };
+    let len = map.entries.len();
+    map.entries.retain(|(k, _)| k.as_c_str() != key);
+    c_int::from(map.entries.len() != len)
+}
+
+pub(super) unsafe extern "system-unwind" fn map_num_elements(
+    map: *const VSMap,
+    key: *const c_char,
+) -> c_int {
+    let map = unsafe { MockMap::from_ptr(map) };
+    map.get(unsafe { CStr::from_ptr(key) })
+        .map_or(-1, |v| v.len().try_into().unwrap_or(c_int::MAX))
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_type(
+    map: *const VSMap,
+    key: *const c_char,
+) -> VSPropertyType {
+    let map = unsafe { MockMap::from_ptr(map) };
+    map.get(unsafe { CStr::from_ptr(key) })
+        .map_or(VSPropertyType::Unset, Values::property_type)
+}
+
+pub(super) unsafe extern "system-unwind" fn map_set_empty(
+    map: *mut VSMap,
+    key: *const c_char,
+    ty: VSPropertyType,
+) -> c_int {
+    let map = unsafe { MockMap::from_ptr_mut(map) };
+    let key = unsafe { CStr::from_ptr(key) };
+    match Values::empty(ty) {
+        Some(values) if map.get(key).is_none() => {
+            map.set(key, values, VSMapAppendMode::Replace);
+            0
+        }
+        _ => 1,
+    }
+}
+
+/// Stores `values` at `key`, returning 0 on success like the `mapSet*` functions.
+unsafe fn set_values(
+    map: *mut VSMap,
+    key: *const c_char,
+    values: Values,
+    append: VSMapAppendMode,
+) -> c_int {
+    let map = unsafe { MockMap::from_ptr_mut(map) };
+    c_int::from(!map.set(unsafe { CStr::from_ptr(key) }, values, append))
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_int(
+    map: *const VSMap,
+    key: *const c_char,
+    index: c_int,
+    error: *mut VSMapPropertyError,
+) -> i64 {
+    let value = unsafe {
+        get_with(map, key, index, error, |(v, i)| match v {
+            Values::Int(v) => Some(v[i]),
+            _ => None,
+        })
+    };
+    value.unwrap_or(0)
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_int_saturated(
+    map: *const VSMap,
+    key: *const c_char,
+    index: c_int,
+    error: *mut VSMapPropertyError,
+) -> c_int {
+    let value = unsafe { map_get_int(map, key, index, error) };
+    // Saturated into the range of `c_int`
+    #[allow(clippy::cast_possible_truncation)]
+    let value = value.clamp(c_int::MIN.into(), c_int::MAX.into()) as c_int;
+    value
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_int_array(
+    map: *const VSMap,
+    key: *const c_char,
+    error: *mut VSMapPropertyError,
+) -> *const i64 {
+    let value = unsafe {
+        get_array_with(map, key, error, |v| match v {
+            Values::Int(v) => Some(v.as_ptr()),
+            _ => None,
+        })
+    };
+    value.unwrap_or(null())
+}
+
+pub(super) unsafe extern "system-unwind" fn map_set_int(
+    map: *mut VSMap,
+    key: *const c_char,
+    i: i64,
+    append: VSMapAppendMode,
+) -> c_int {
+    unsafe { set_values(map, key, Values::Int(vec![i]), append) }
+}
+
+pub(super) unsafe extern "system-unwind" fn map_set_int_array(
+    map: *mut VSMap,
+    key: *const c_char,
+    i: *const i64,
+    size: c_int,
+) -> c_int {
+    let Ok(size) = usize::try_from(size) else {
+        return 1;
+    };
+    let values = if size == 0 {
+        Vec::new()
+    } else {
+        unsafe { slice::from_raw_parts(i, size) }.to_vec()
+    };
+    unsafe { set_values(map, key, Values::Int(values), VSMapAppendMode::Replace) }
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_float(
+    map: *const VSMap,
+    key: *const c_char,
+    index: c_int,
+    error: *mut VSMapPropertyError,
+) -> c_double {
+    let value = unsafe {
+        get_with(map, key, index, error, |(v, i)| match v {
+            Values::Float(v) => Some(v[i]),
+            _ => None,
+        })
+    };
+    value.unwrap_or(0.0)
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_float_saturated(
+    map: *const VSMap,
+    key: *const c_char,
+    index: c_int,
+    error: *mut VSMapPropertyError,
+) -> c_float {
+    // Saturated into the range of `c_float`
+    #[allow(clippy::cast_possible_truncation)]
+    let value = unsafe { map_get_float(map, key, index, error) }
+        .clamp(f64::from(c_float::MIN), f64::from(c_float::MAX)) as c_float;
+    value
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_float_array(
+    map: *const VSMap,
+    key: *const c_char,
+    error: *mut VSMapPropertyError,
+) -> *const c_double {
+    let value = unsafe {
+        get_array_with(map, key, error, |v| match v {
+            Values::Float(v) => Some(v.as_ptr()),
+            _ => None,
+        })
+    };
+    value.unwrap_or(null())
+}
+
+pub(super) unsafe extern "system-unwind" fn map_set_float(
+    map: *mut VSMap,
+    key: *const c_char,
+    d: c_double,
+    append: VSMapAppendMode,
+) -> c_int {
+    unsafe { set_values(map, key, Values::Float(vec![d]), append) }
+}
+
+pub(super) unsafe extern "system-unwind" fn map_set_float_array(
+    map: *mut VSMap,
+    key: *const c_char,
+    d: *const c_double,
+    size: c_int,
+) -> c_int {
+    let Ok(size) = usize::try_from(size) else {
+        return 1;
+    };
+    let values = if size == 0 {
+        Vec::new()
+    } else {
+        unsafe { slice::from_raw_parts(d, size) }.to_vec()
+    };
+    unsafe { set_values(map, key, Values::Float(values), VSMapAppendMode::Replace) }
+}
+
+unsafe fn get_data<'a>(
+    map: *const VSMap,
+    key: *const c_char,
+    index: c_int,
+    error: *mut VSMapPropertyError,
+) -> Option<&'a (Vec<u8>, VSDataTypeHint)> {
+    unsafe {
+        get_with(map, key, index, error, |(v, i)| match v {
+            Values::Data(v) => Some(&v[i]),
+            _ => None,
+        })
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_data(
+    map: *const VSMap,
+    key: *const c_char,
+    index: c_int,
+    error: *mut VSMapPropertyError,
+) -> *const c_char {
+    unsafe { get_data(map, key, index, error) }.map_or(null(), |(data, _)| data.as_ptr().cast())
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_data_size(
+    map: *const VSMap,
+    key: *const c_char,
+    index: c_int,
+    error: *mut VSMapPropertyError,
+) -> c_int {
+    unsafe { get_data(map, key, index, error) }.map_or(-1, |(data, _)| {
+        (data.len() - 1).try_into().unwrap_or(c_int::MAX)
+    })
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_data_type_hint(
+    map: *const VSMap,
+    key: *const c_char,
+    index: c_int,
+    error: *mut VSMapPropertyError,
+) -> VSDataTypeHint {
+    unsafe { get_data(map, key, index, error) }.map_or(VSDataTypeHint::Unknown, |&(_, hint)| hint)
+}
+
+pub(super) unsafe extern "system-unwind" fn map_set_data(
+    map: *mut VSMap,
+    key: *const c_char,
+    data: *const c_char,
+    size: c_int,
+    hint: VSDataTypeHint,
+    append: VSMapAppendMode,
+) -> c_int {
+    let mut bytes = match usize::try_from(size) {
+        Ok(0) => Vec::new(),
+        Ok(size) => unsafe { slice::from_raw_parts(data.cast::<u8>(), size) }.to_vec(),
+        // A negative size means a nul terminated string
+        Err(_) => unsafe { CStr::from_ptr(data) }.to_bytes().to_vec(),
+    };
+    bytes.push(0);
+    unsafe { set_values(map, key, Values::Data(vec![(bytes, hint)]), append) }
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_node(
+    map: *const VSMap,
+    key: *const c_char,
+    index: c_int,
+    error: *mut VSMapPropertyError,
+) -> *mut VSNode {
+    let value = unsafe {
+        get_with(map, key, index, error, |(v, i)| match v {
+            Values::Node(_, v) => Some(v[i]),
+            _ => None,
+        })
+    };
+    value.map_or(null_mut(), |n| unsafe { node::add_ref(n) })
+}
+
+pub(super) unsafe extern "system-unwind" fn map_set_node(
+    map: *mut VSMap,
+    key: *const c_char,
+    node: *mut VSNode,
+    append: VSMapAppendMode,
+) -> c_int {
+    unsafe { map_consume_node(map, key, node::add_ref(node), append) }
+}
+
+pub(super) unsafe extern "system-unwind" fn map_consume_node(
+    map: *mut VSMap,
+    key: *const c_char,
+    node: *mut VSNode,
+    append: VSMapAppendMode,
+) -> c_int {
+    let ty = unsafe { node::get_node_type(node) };
+    unsafe { set_values(map, key, Values::Node(ty, vec![node]), append) }
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_frame(
+    map: *const VSMap,
+    key: *const c_char,
+    index: c_int,
+    error: *mut VSMapPropertyError,
+) -> *const VSFrame {
+    let value = unsafe {
+        get_with(map, key, index, error, |(v, i)| match v {
+            Values::Frame(_, v) => Some(v[i]),
+            _ => None,
+        })
+    };
+    value.map_or(null(), |f| unsafe { frame::add_ref(f) }.cast_const())
+}
+
+pub(super) unsafe extern "system-unwind" fn map_set_frame(
+    map: *mut VSMap,
+    key: *const c_char,
+    f: *const VSFrame,
+    append: VSMapAppendMode,
+) -> c_int {
+    unsafe { map_consume_frame(map, key, frame::add_ref(f), append) }
+}
+
+pub(super) unsafe extern "system-unwind" fn map_consume_frame(
+    map: *mut VSMap,
+    key: *const c_char,
+    f: *const VSFrame,
+    append: VSMapAppendMode,
+) -> c_int {
+    let ty = unsafe { frame::get_frame_type(f) };
+    unsafe { set_values(map, key, Values::Frame(ty, vec![f]), append) }
+}
+
+pub(super) unsafe extern "system-unwind" fn map_get_function(
+    map: *const VSMap,
+    key: *const c_char,
+    index: c_int,
+    error: *mut VSMapPropertyError,
+) -> *mut VSFunction {
+    let value = unsafe {
+        get_with(map, key, index, error, |(v, i)| match v {
+            Values::Function(v) => Some(v[i]),
+            _ => None,
+        })
+    };
+    value.map_or(null_mut(), |f| unsafe { vs_core::add_function_ref(f) })
+}
+
+pub(super) unsafe extern "system-unwind" fn map_set_function(
+    map: *mut VSMap,
+    key: *const c_char,
+    func: *mut VSFunction,
+    append: VSMapAppendMode,
+) -> c_int {
+    unsafe { map_consume_function(map, key, vs_core::add_function_ref(func), append) }
+}
+
+pub(super) unsafe extern "system-unwind" fn map_consume_function(
+    map: *mut VSMap,
+    key: *const c_char,
+    func: *mut VSFunction,
+    append: VSMapAppendMode,
+) -> c_int {
+    unsafe { set_values(map, key, Values::Function(vec![func]), append) }
+}
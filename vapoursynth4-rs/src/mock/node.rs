@@ -0,0 +1,544 @@
+use std::{
+    ffi::{CStr, CString, c_char, c_int, c_void},
+    ptr::{self, null, null_mut},
+    slice,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::ffi::{
+    VS_AUDIO_FRAME_SAMPLES, VSActivationReason, VSAudioInfo, VSCacheMode, VSCore,
+    VSFilterDependency, VSFilterFree, VSFilterGetFrame, VSFilterMode, VSFrame, VSFrameContext,
+    VSFrameDoneCallback, VSMap, VSMapAppendMode, VSMediaType, VSNode, VSVideoInfo,
+};
+
+use super::{API, frame, map};
+
+enum Info {
+    Video(VSVideoInfo),
+    Audio(VSAudioInfo),
+}
+
+/// A filter instance created by `createVideoFilter` or `createAudioFilter`.
+struct MockNode {
+    refs: AtomicUsize,
+    name: CString,
+    info: Info,
+    get_frame: VSFilterGetFrame,
+    free: VSFilterFree,
+    mode: VSFilterMode,
+    dependencies: Vec<VSFilterDependency>,
+    instance_data: *mut c_void,
+    core: *mut VSCore,
+}
+
+impl MockNode {
+    unsafe fn from_ptr<'a>(node: *mut VSNode) -> &'a Self {
+        unsafe { &*node.cast() }
+    }
+
+    fn num_frames(&self) -> c_int {
+        match &self.info {
+            Info::Video(vi) => vi.num_frames,
+            Info::Audio(ai) => ai.num_frames,
+        }
+    }
+}
+
+/// The frames requested and received by a filter while it produces one frame.
+#[derive(Default)]
+struct MockFrameContext {
+    requests: Vec<(*mut VSNode, c_int)>,
+    frames: Vec<(*mut VSNode, c_int, *const VSFrame)>,
+    error: Option<CString>,
+}
+
+impl Drop for MockFrameContext {
+    fn drop(&mut self) {
+        for &(_, _, f) in &self.frames {
+            // SAFETY: The context owns a reference to each received frame
+            unsafe { frame::free_frame(f) };
+        }
+    }
+}
+
+/// Produces frame `n` of `node`, rendering the requested frames of its dependencies first.
+///
+/// Every request is served synchronously in the order of the activation reasons
+/// `VapourSynth` uses, without caching.
+unsafe fn render(node: *mut VSNode, n: c_int) -> Result<*const VSFrame, CString> {
+    let this = unsafe { MockNode::from_ptr(node) };
+    let mut frame_data = [null_mut::<c_void>(); 4];
+    let mut ctx = MockFrameContext::default();
+    let mut reason = VSActivationReason::Initial;
+
+    loop {
+        let f = unsafe { this.activate(n, reason, &mut frame_data, &mut ctx) };
+        if let Some(error) = ctx.error.take() {
+            unsafe { frame::free_frame(f) };
+            return Err(error);
+        }
+        if !f.is_null() {
+            return Ok(f);
+        }
+        if ctx.requests.is_empty() {
+            let name = this.name.to_string_lossy();
+            return Err(CString::new(format!(
+                "{name}: no frame returned at the end of processing"
+            ))
+            .unwrap_or_default());
+        }
+
+        for (dep, k) in std::mem::take(&mut ctx.requests) {
+            if ctx.frames.iter().any(|&(d, i, _)| d == dep && i == k) {
+                continue;
+            }
+            match unsafe { render(dep, k) } {
+                Ok(f) => ctx.frames.push((dep, k, f)),
+                Err(error) => {
+                    let f = unsafe {
+                        this.activate(n, VSActivationReason::Error, &mut frame_data, &mut ctx)
+                    };
+                    unsafe { frame::free_frame(f) };
+                    return Err(error);
+                }
+            }
+        }
+        reason = VSActivationReason::AllFramesReady;
+    }
+}
+
+impl MockNode {
+    unsafe fn activate(
+        &self,
+        n: c_int,
+        reason: VSActivationReason,
+        frame_data: &mut [*mut c_void; 4],
+        ctx: &mut MockFrameContext,
+    ) -> *const VSFrame {
+        unsafe {
+            (self.get_frame)(
+                n,
+                reason,
+                self.instance_data,
+                frame_data.as_mut_ptr(),
+                ptr::from_mut(ctx).cast(),
+                self.core,
+                &raw const API,
+            )
+        }
+    }
+}
+
+/// Clamps `n` to the frames of `node`, as `VapourSynth` does for filter requests.
+unsafe fn clamp_frame(node: *mut VSNode, n: c_int) -> c_int {
+    n.clamp(0, unsafe { MockNode::from_ptr(node) }.num_frames() - 1)
+}
+
+pub(super) unsafe fn add_ref(node: *mut VSNode) -> *mut VSNode {
+    unsafe { MockNode::from_ptr(node) }
+        .refs
+        .fetch_add(1, Ordering::Relaxed);
+    node
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn create_node(
+    name: *const c_char,
+    info: Info,
+    get_frame: VSFilterGetFrame,
+    free: VSFilterFree,
+    mode: VSFilterMode,
+    dependencies: *const VSFilterDependency,
+    num_deps: c_int,
+    instance_data: *mut c_void,
+    core: *mut VSCore,
+) -> Result<*mut VSNode, CString> {
+    let name = unsafe { CStr::from_ptr(name) }.to_owned();
+    let node = MockNode {
+        refs: AtomicUsize::new(1),
+        info,
+        get_frame,
+        free,
+        mode,
+        dependencies: match usize::try_from(num_deps) {
+            Ok(len) if len > 0 => unsafe { slice::from_raw_parts(dependencies, len) }
+                .iter()
+                .map(|dep| VSFilterDependency {
+                    source: dep.source,
+                    request_pattern: dep.request_pattern,
+                })
+                .collect(),
+            _ => Vec::new(),
+        },
+        instance_data,
+        core,
+        name,
+    };
+    if node.num_frames() <= 0 {
+        let msg = format!(
+            "{}: the clip must have at least one frame",
+            node.name.to_string_lossy()
+        );
+        drop(node);
+        return Err(CString::new(msg).unwrap_or_default());
+    }
+
+    Ok(Box::into_raw(Box::new(node)).cast())
+}
+
+impl Drop for MockNode {
+    fn drop(&mut self) {
+        if let Some(free) = self.free {
+            // SAFETY: The instance data is no longer used after the last reference
+            unsafe { free(self.instance_data, self.core, &raw const API) };
+        }
+    }
+}
+
+/// Appends the result of a `create*Filter2` function to the `clip` key of `out`.
+unsafe fn append_clip(out: *mut VSMap, node: Result<*mut VSNode, CString>) {
+    match node {
+        Ok(node) => unsafe {
+            map::map_consume_node(out, c"clip".as_ptr(), node, VSMapAppendMode::Append);
+        },
+        Err(msg) => unsafe { map::map_set_error(out, msg.as_ptr()) },
+    }
+}
+
+fn audio_info(ai: &VSAudioInfo) -> Info {
+    let samples = i64::from(VS_AUDIO_FRAME_SAMPLES);
+    Info::Audio(VSAudioInfo {
+        num_frames: c_int::try_from((ai.num_samples + samples - 1) / samples).unwrap_or(c_int::MAX),
+        ..ai.clone()
+    })
+}
+
+pub(super) unsafe extern "system-unwind" fn create_video_filter(
+    out: *mut VSMap,
+    name: *const c_char,
+    vi: *const VSVideoInfo,
+    get_frame: VSFilterGetFrame,
+    free: VSFilterFree,
+    mode: VSFilterMode,
+    dependencies: *const VSFilterDependency,
+    num_deps: c_int,
+    instance_data: *mut c_void,
+    core: *mut VSCore,
+) {
+    unsafe {
+        let info = Info::Video((*vi).clone());
+        let node = create_node(
+            name,
+            info,
+            get_frame,
+            free,
+            mode,
+            dependencies,
+            num_deps,
+            instance_data,
+            core,
+        );
+        append_clip(out, node);
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn create_video_filter2(
+    name: *const c_char,
+    vi: *const VSVideoInfo,
+    get_frame: VSFilterGetFrame,
+    free: VSFilterFree,
+    mode: VSFilterMode,
+    dependencies: *const VSFilterDependency,
+    num_deps: c_int,
+    instance_data: *mut c_void,
+    core: *mut VSCore,
+) -> *mut VSNode {
+    unsafe {
+        let info = Info::Video((*vi).clone());
+        create_node(
+            name,
+            info,
+            get_frame,
+            free,
+            mode,
+            dependencies,
+            num_deps,
+            instance_data,
+            core,
+        )
+        .unwrap_or(null_mut())
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn create_audio_filter(
+    out: *mut VSMap,
+    name: *const c_char,
+    ai: *const VSAudioInfo,
+    get_frame: VSFilterGetFrame,
+    free: VSFilterFree,
+    mode: VSFilterMode,
+    dependencies: *const VSFilterDependency,
+    num_deps: c_int,
+    instance_data: *mut c_void,
+    core: *mut VSCore,
+) {
+    unsafe {
+        let info = audio_info(&*ai);
+        let node = create_node(
+            name,
+            info,
+            get_frame,
+            free,
+            mode,
+            dependencies,
+            num_deps,
+            instance_data,
+            core,
+        );
+        append_clip(out, node);
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn create_audio_filter2(
+    name: *const c_char,
+    ai: *const VSAudioInfo,
+    get_frame: VSFilterGetFrame,
+    free: VSFilterFree,
+    mode: VSFilterMode,
+    dependencies: *const VSFilterDependency,
+    num_deps: c_int,
+    instance_data: *mut c_void,
+    core: *mut VSCore,
+) -> *mut VSNode {
+    unsafe {
+        let info = audio_info(&*ai);
+        create_node(
+            name,
+            info,
+            get_frame,
+            free,
+            mode,
+            dependencies,
+            num_deps,
+            instance_data,
+            core,
+        )
+        .unwrap_or(null_mut())
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn set_linear_filter(_node: *mut VSNode) -> c_int {
+    0
+}
+
+pub(super) unsafe extern "system-unwind" fn set_cache_mode(_node: *mut VSNode, _mode: VSCacheMode) {
+}
+
+pub(super) unsafe extern "system-unwind" fn set_cache_options(
+    _node: *mut VSNode,
+    _fixed_size: c_int,
+    _max_size: c_int,
+    _max_history_size: c_int,
+) {
+}
+
+pub(super) unsafe extern "system-unwind" fn free_node(node: *mut VSNode) {
+    if !node.is_null()
+        && unsafe { MockNode::from_ptr(node) }
+            .refs
+            .fetch_sub(1, Ordering::AcqRel)
+            == 1
+    {
+        drop(unsafe { Box::from_raw(node.cast::<MockNode>()) });
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn add_node_ref(node: *mut VSNode) -> *mut VSNode {
+    unsafe { add_ref(node) }
+}
+
+pub(super) unsafe extern "system-unwind" fn get_node_type(node: *mut VSNode) -> VSMediaType {
+    match unsafe { MockNode::from_ptr(node) }.info {
+        Info::Video(_) => VSMediaType::Video,
+        Info::Audio(_) => VSMediaType::Audio,
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn get_video_info(
+    node: *mut VSNode,
+) -> *const VSVideoInfo {
+    match &unsafe { MockNode::from_ptr(node) }.info {
+        Info::Video(vi) => vi,
+        Info::Audio(_) => null(),
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn get_audio_info(
+    node: *mut VSNode,
+) -> *const VSAudioInfo {
+    match &unsafe { MockNode::from_ptr(node) }.info {
+        Info::Audio(ai) => ai,
+        Info::Video(_) => null(),
+    }
+}
+
+// MARK: Frame requests
+
+/// Copies `msg` into a buffer of `size` bytes, truncating it if needed.
+unsafe fn copy_error(msg: &CStr, buf: *mut c_char, size: c_int) {
+    let Some(size) = usize::try_from(size)
+        .ok()
+        .filter(|&s| s > 0 && !buf.is_null())
+    else {
+        return;
+    };
+    let bytes = msg.to_bytes();
+    let len = bytes.len().min(size - 1);
+    unsafe {
+        ptr::copy_nonoverlapping(bytes.as_ptr().cast(), buf, len);
+        *buf.add(len) = 0;
+    }
+}
+
+/// Renders frame `n` for a caller outside of a filter.
+unsafe fn get_frame_checked(n: c_int, node: *mut VSNode) -> Result<*const VSFrame, CString> {
+    let num_frames = unsafe { MockNode::from_ptr(node) }.num_frames();
+    if (0..num_frames).contains(&n) {
+        unsafe { render(node, n) }
+    } else {
+        Err(CString::new(format!(
+            "Invalid frame number {n} requested, clip only has {num_frames} frames"
+        ))
+        .unwrap_or_default())
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn get_frame(
+    n: c_int,
+    node: *mut VSNode,
+    error_msg: *mut c_char,
+    buf_size: c_int,
+) -> *const VSFrame {
+    match unsafe { get_frame_checked(n, node) } {
+        Ok(f) => f,
+        Err(msg) => {
+            unsafe { copy_error(&msg, error_msg, buf_size) };
+            null()
+        }
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn get_frame_async(
+    n: c_int,
+    node: *mut VSNode,
+    callback: VSFrameDoneCallback,
+    user_data: *mut c_void,
+) {
+    match unsafe { get_frame_checked(n, node) } {
+        Ok(f) => unsafe { callback(user_data, f, n, node, null()) },
+        Err(msg) => unsafe { callback(user_data, null(), n, node, msg.as_ptr()) },
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn get_frame_filter(
+    n: c_int,
+    node: *mut VSNode,
+    frame_ctx: *mut VSFrameContext,
+) -> *const VSFrame {
+    let n = unsafe { clamp_frame(node, n) };
+    let ctx = unsafe { &*frame_ctx.cast::<MockFrameContext>() };
+    ctx.frames
+        .iter()
+        .find(|&&(dep, k, _)| dep == node && k == n)
+        .map_or(null(), |&(_, _, f)| {
+            unsafe { frame::add_ref(f) }.cast_const()
+        })
+}
+
+pub(super) unsafe extern "system-unwind" fn request_frame_filter(
+    n: c_int,
+    node: *mut VSNode,
+    frame_ctx: *mut VSFrameContext,
+) {
+    let n = unsafe { clamp_frame(node, n) };
+    let ctx = unsafe { &mut *frame_ctx.cast::<MockFrameContext>() };
+    // A frame received in an earlier round is still a request, but is not rendered again
+    if !ctx.requests.contains(&(node, n)) {
+        ctx.requests.push((node, n));
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn release_frame_early(
+    _node: *mut VSNode,
+    _n: c_int,
+    _frame_ctx: *mut VSFrameContext,
+) {
+}
+
+pub(super) unsafe extern "system-unwind" fn cache_frame(
+    _frame: *const VSFrame,
+    _n: c_int,
+    _frame_ctx: *mut VSFrameContext,
+) {
+}
+
+pub(super) unsafe extern "system-unwind" fn set_filter_error(
+    error_message: *const c_char,
+    frame_ctx: *mut VSFrameContext,
+) {
+    let ctx = unsafe { &mut *frame_ctx.cast::<MockFrameContext>() };
+    ctx.error = Some(unsafe { CStr::from_ptr(error_message) }.to_owned());
+}
+
+// MARK: Node information
+
+#[cfg(feature = "vs-41")]
+pub(super) unsafe extern "system-unwind" fn clear_node_cache(_node: *mut VSNode) {}
+
+#[cfg(feature = "vs-41")]
+pub(super) unsafe extern "system-unwind" fn get_node_name(node: *mut VSNode) -> *const c_char {
+    unsafe { MockNode::from_ptr(node) }.name.as_ptr()
+}
+
+#[cfg(feature = "vs-41")]
+pub(super) unsafe extern "system-unwind" fn get_node_filter_mode(
+    node: *mut VSNode,
+) -> VSFilterMode {
+    unsafe { MockNode::from_ptr(node) }.mode
+}
+
+#[cfg(feature = "vs-41")]
+pub(super) unsafe extern "system-unwind" fn get_num_node_dependencies(node: *mut VSNode) -> c_int {
+    let deps = &unsafe { MockNode::from_ptr(node) }.dependencies;
+    deps.len().try_into().unwrap_or(c_int::MAX)
+}
+
+#[cfg(feature = "vs-41")]
+pub(super) unsafe extern "system-unwind" fn get_node_dependencies(
+    node: *mut VSNode,
+) -> *const VSFilterDependency {
+    unsafe { MockNode::from_ptr(node) }.dependencies.as_ptr()
+}
+
+pub(super) unsafe extern "system-unwind" fn get_node_processing_time(
+    _node: *mut VSNode,
+    _reset: c_int,
+) -> i64 {
+    0
+}
+
+#[cfg(feature = "vs-graph")]
+pub(super) unsafe extern "system-unwind" fn get_node_creation_function_name(
+    _node: *mut VSNode,
+    _level: c_int,
+) -> *const c_char {
+    null()
+}
+
+#[cfg(feature = "vs-graph")]
+pub(super) unsafe extern "system-unwind" fn get_node_creation_function_arguments(
+    _node: *mut VSNode,
+    _level: c_int,
+) -> *const VSMap {
+    null()
+}
@@ -0,0 +1,327 @@
+use std::{
+    ffi::{CStr, c_char, c_int, c_void},
+    ptr::null_mut,
+    sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use crate::ffi::{
+    VAPOURSYNTH_API_VERSION, VSCore, VSCoreInfo, VSFreeFunctionData, VSFunction, VSLogHandle,
+    VSLogHandler, VSLogHandlerFree, VSMap, VSMessageType, VSPlugin, VSPluginFunction,
+    VSPublicFunction,
+};
+
+use super::{API, map};
+
+struct LogHandler {
+    handler: VSLogHandler,
+    free: VSLogHandlerFree,
+    user_data: *mut c_void,
+}
+
+impl Drop for LogHandler {
+    fn drop(&mut self) {
+        if let Some(free) = self.free {
+            // SAFETY: The user data is no longer used after the handler is removed
+            unsafe { free(self.user_data) };
+        }
+    }
+}
+
+/// A core with settings and log handlers, but no plugins.
+struct MockCore {
+    max_cache_size: i64,
+    threads: c_int,
+    // The handles returned by `addLogHandler` point into the boxes
+    #[allow(clippy::vec_box)]
+    handlers: Mutex<Vec<Box<LogHandler>>>,
+}
+
+impl MockCore {
+    unsafe fn from_ptr<'a>(core: *mut VSCore) -> &'a mut Self {
+        unsafe { &mut *core.cast() }
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn create_core(_flags: c_int) -> *mut VSCore {
+    Box::into_raw(Box::new(MockCore {
+        max_cache_size: 1 << 30,
+        threads: 1,
+        handlers: Mutex::default(),
+    }))
+    .cast()
+}
+
+pub(super) unsafe extern "system-unwind" fn free_core(core: *mut VSCore) {
+    if !core.is_null() {
+        drop(unsafe { Box::from_raw(core.cast::<MockCore>()) });
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn set_max_cache_size(
+    bytes: i64,
+    core: *mut VSCore,
+) -> i64 {
+    let core = unsafe { MockCore::from_ptr(core) };
+    if bytes > 0 {
+        core.max_cache_size = bytes;
+    }
+    core.max_cache_size
+}
+
+pub(super) unsafe extern "system-unwind" fn set_thread_count(
+    threads: c_int,
+    core: *mut VSCore,
+) -> c_int {
+    let core = unsafe { MockCore::from_ptr(core) };
+    core.threads = threads.max(1);
+    core.threads
+}
+
+pub(super) unsafe extern "system-unwind" fn get_core_info(
+    core: *mut VSCore,
+    info: *mut VSCoreInfo,
+) {
+    let core = unsafe { MockCore::from_ptr(core) };
+    unsafe {
+        info.write(VSCoreInfo {
+            version_string: c"VapourSynth mock core".as_ptr(),
+            core: 0,
+            api: VAPOURSYNTH_API_VERSION,
+            num_threads: core.threads,
+            max_framebuffer_size: core.max_cache_size,
+            used_framebuffer_size: 0,
+        });
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn get_api_version() -> c_int {
+    VAPOURSYNTH_API_VERSION
+}
+
+// MARK: Logging
+
+/// Passes the message to the log handlers. A fatal message panics instead of aborting.
+pub(super) unsafe extern "system-unwind" fn log_message(
+    msg_type: VSMessageType,
+    msg: *const c_char,
+    core: *mut VSCore,
+) {
+    let core = unsafe { MockCore::from_ptr(core) };
+    for h in core
+        .handlers
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+    {
+        if let Some(handler) = h.handler {
+            unsafe { handler(msg_type as c_int, msg, h.user_data) };
+        }
+    }
+    assert!(
+        msg_type != VSMessageType::Fatal,
+        "{}",
+        unsafe { CStr::from_ptr(msg) }.to_string_lossy()
+    );
+}
+
+pub(super) unsafe extern "system-unwind" fn add_log_handler(
+    handler: VSLogHandler,
+    free: VSLogHandlerFree,
+    user_data: *mut c_void,
+    core: *mut VSCore,
+) -> *mut VSLogHandle {
+    let core = unsafe { MockCore::from_ptr(core) };
+    let mut handler = Box::new(LogHandler {
+        handler,
+        free,
+        user_data,
+    });
+    let handle = (&raw mut *handler).cast();
+    core.handlers
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(handler);
+    handle
+}
+
+pub(super) unsafe extern "system-unwind" fn remove_log_handler(
+    handle: *mut VSLogHandle,
+    core: *mut VSCore,
+) -> c_int {
+    let core = unsafe { MockCore::from_ptr(core) };
+    let mut handlers = core.handlers.lock().unwrap_or_else(PoisonError::into_inner);
+    let len = handlers.len();
+    handlers.retain(|h| !std::ptr::eq(&raw const **h, handle.cast()));
+    c_int::from(handlers.len() != len)
+}
+
+// MARK: Functions
+
+struct MockFunction {
+    refs: AtomicUsize,
+    func: VSPublicFunction,
+    user_data: *mut c_void,
+    free: VSFreeFunctionData,
+    core: *mut VSCore,
+}
+
+impl Drop for MockFunction {
+    fn drop(&mut self) {
+        if let Some(free) = self.free {
+            // SAFETY: The user data is no longer used after the last reference
+            unsafe { free(self.user_data) };
+        }
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn create_function(
+    func: VSPublicFunction,
+    user_data: *mut c_void,
+    free: VSFreeFunctionData,
+    core: *mut VSCore,
+) -> *mut VSFunction {
+    Box::into_raw(Box::new(MockFunction {
+        refs: AtomicUsize::new(1),
+        func,
+        user_data,
+        free,
+        core,
+    }))
+    .cast()
+}
+
+pub(super) unsafe extern "system-unwind" fn free_function(f: *mut VSFunction) {
+    if !f.is_null()
+        && unsafe { &*f.cast::<MockFunction>() }
+            .refs
+            .fetch_sub(1, Ordering::AcqRel)
+            == 1
+    {
+        drop(unsafe { Box::from_raw(f.cast::<MockFunction>()) });
+    }
+}
+
+pub(super) unsafe extern "system-unwind" fn add_function_ref(
+    f: *mut VSFunction,
+) -> *mut VSFunction {
+    unsafe { &*f.cast::<MockFunction>() }
+        .refs
+        .fetch_add(1, Ordering::Relaxed);
+    f
+}
+
+pub(super) unsafe extern "system-unwind" fn call_function(
+    func: *mut VSFunction,
+    in_: *const VSMap,
+    out: *mut VSMap,
+) {
+    let func = unsafe { &*func.cast::<MockFunction>() };
+    unsafe { (func.func)(in_, out, func.user_data, func.core, &raw const API) };
+}
+
+// MARK: Plugins
+
+const NO_PLUGINS: &str = "mock: the core has no plugins";
+
+pub(super) unsafe extern "system-unwind" fn register_function(
+    _name: *const c_char,
+    _args: *const c_char,
+    _return_type: *const c_char,
+    _args_func: VSPublicFunction,
+    _function_data: *mut c_void,
+    _plugin: *mut VSPlugin,
+) -> c_int {
+    0
+}
+
+pub(super) unsafe extern "system-unwind" fn get_plugin_by_id(
+    _identifier: *const c_char,
+    _core: *mut VSCore,
+) -> *mut VSPlugin {
+    null_mut()
+}
+
+pub(super) unsafe extern "system-unwind" fn get_plugin_by_namespace(
+    _ns: *const c_char,
+    _core: *mut VSCore,
+) -> *mut VSPlugin {
+    null_mut()
+}
+
+pub(super) unsafe extern "system-unwind" fn get_next_plugin(
+    _plugin: *mut VSPlugin,
+    _core: *mut VSCore,
+) -> *mut VSPlugin {
+    null_mut()
+}
+
+pub(super) unsafe extern "system-unwind" fn get_plugin_string(
+    _plugin: *mut VSPlugin,
+) -> *const c_char {
+    panic!("{NO_PLUGINS}")
+}
+
+pub(super) unsafe extern "system-unwind" fn get_next_plugin_function(
+    _func: *mut VSPluginFunction,
+    _plugin: *mut VSPlugin,
+) -> *mut VSPluginFunction {
+    panic!("{NO_PLUGINS}")
+}
+
+pub(super) unsafe extern "system-unwind" fn get_plugin_function_by_name(
+    _name: *const c_char,
+    _plugin: *mut VSPlugin,
+) -> *mut VSPluginFunction {
+    panic!("{NO_PLUGINS}")
+}
+
+pub(super) unsafe extern "system-unwind" fn get_plugin_function_string(
+    _func: *mut VSPluginFunction,
+) -> *const c_char {
+    panic!("{NO_PLUGINS}")
+}
+
+pub(super) unsafe extern "system-unwind" fn get_plugin_path(
+    _plugin: *const VSPlugin,
+) -> *const c_char {
+    panic!("{NO_PLUGINS}")
+}
+
+pub(super) unsafe extern "system-unwind" fn get_plugin_version(_plugin: *const VSPlugin) -> c_int {
+    panic!("{NO_PLUGINS}")
+}
+
+pub(super) unsafe extern "system-unwind" fn invoke(
+    _plugin: *mut VSPlugin,
+    _name: *const c_char,
+    _args: *const VSMap,
+) -> *mut VSMap {
+    let out = unsafe { map::create_map() };
+    unsafe { map::map_set_error(out, c"mock: the core has no plugins".as_ptr()) };
+    out
+}
+
+// MARK: Caches and timing
+
+#[cfg(feature = "vs-41")]
+pub(super) unsafe extern "system-unwind" fn clear_core_caches(_core: *mut VSCore) {}
+
+pub(super) unsafe extern "system-unwind" fn get_core_node_timing(_core: *mut VSCore) -> c_int {
+    0
+}
+
+pub(super) unsafe extern "system-unwind" fn set_core_node_timing(
+    _core: *mut VSCore,
+    _enable: c_int,
+) {
+}
+
+pub(super) unsafe extern "system-unwind" fn get_freed_node_processing_time(
+    _core: *mut VSCore,
+    _reset: c_int,
+) -> i64 {
+    0
+}
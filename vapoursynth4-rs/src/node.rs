@@ -4,11 +4,23 @@
  file, You can obtain one at http://mozilla.org/MPL/2.0/.
 */
 
+mod async_filter;
 mod dependency;
+mod eval;
 mod filter;
 pub(crate) mod internal;
-
-use std::ffi::{CStr, CString, c_void};
+pub mod mode;
+mod modify;
+mod panic;
+mod simple;
+mod source;
+mod state;
+mod temporal;
+
+use std::{
+    ffi::{CStr, CString, c_void},
+    ptr::NonNull,
+};
 
 use crate::{
     AudioInfo, VideoInfo,
@@ -19,8 +31,15 @@ use crate::{
     node::internal::FilterExtern,
 };
 
+pub use async_filter::*;
 pub use dependency::*;
 pub use filter::*;
+pub use mode::Instance;
+pub use panic::PanicPolicy;
+pub use simple::*;
+pub use source::{AudioSource, VideoSource};
+pub use state::*;
+pub use temporal::*;
 use vapoursynth4_sys::VSFrameDoneCallback;
 
 pub trait Node: Sized + Send + Sync + crate::_private::Sealed {
@@ -153,8 +172,8 @@ impl VideoNode {
                 core.as_ptr(),
             )
         };
-        ptr.is_null()
-            .then_some(unsafe { Self::from_ptr(ptr, core.api()) })
+        // The core returns null if the filter could not be created
+        NonNull::new(ptr).map(|ptr| unsafe { Self::from_ptr(ptr.as_ptr(), core.api()) })
     }
 }
 
@@ -244,8 +263,8 @@ impl AudioNode {
                 core.as_ptr(),
             )
         };
-        ptr.is_null()
-            .then_some(unsafe { Self::from_ptr(ptr, core.api()) })
+        // The core returns null if the filter could not be created
+        NonNull::new(ptr).map(|ptr| unsafe { Self::from_ptr(ptr.as_ptr(), core.api()) })
     }
 }
 
@@ -263,3 +282,78 @@ impl Drop for AudioNode {
 
 pub type FilterMode = ffi::VSFilterMode;
 pub type CacheMode = ffi::VSCacheMode;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ColorFamily, SampleType,
+        core::CoreRef,
+        map::MapRef,
+        mock,
+        node::{ActivationReason, FrameStateSlot, Instance, mode::Parallel},
+    };
+
+    /// A filter that never produces a frame, only used to create nodes.
+    struct Blank;
+
+    impl Filter for Blank {
+        type Mode = Parallel;
+        type Error = &'static CStr;
+        type FrameType = VideoFrame;
+        type FilterData = ();
+        type FrameState = ();
+
+        const NAME: &'static CStr = c"Blank";
+        const ARGS: &'static CStr = c"";
+        const RETURN_TYPE: &'static CStr = c"";
+
+        fn create(_: MapRef, _: MapRef, _: Option<Box<()>>, _: CoreRef) -> Result<(), Self::Error> {
+            Err(c"Blank: not a plugin function")
+        }
+
+        fn get_frame(
+            _this: Instance<'_, Self>,
+            _n: i32,
+            _activation_reason: ActivationReason,
+            _frame_state: FrameStateSlot<'_, ()>,
+            _frame_ctx: FrameContext,
+            _core: CoreRef,
+        ) -> Result<Option<VideoFrame>, Self::Error> {
+            Err(c"Blank: no frames")
+        }
+    }
+
+    #[test]
+    fn new_nodes() {
+        let core = Core::builder().api(mock::api()).build();
+
+        let format = core.query_video_format(ColorFamily::Gray, SampleType::Integer, 8, 0, 0);
+        let info = |num_frames| VideoInfo {
+            format: format.clone(),
+            fps_num: 30,
+            fps_den: 1,
+            width: 4,
+            height: 2,
+            num_frames,
+        };
+        let node = VideoNode::new("Blank", &info(1), Blank, &[], &core);
+        assert!(node.is_some_and(|node| node.info().num_frames == 1));
+        // The core fails to create a clip without frames
+        assert!(VideoNode::new("Blank", &info(0), Blank, &[], &core).is_none());
+
+        let info = |num_samples| AudioInfo {
+            format: core.query_audio_format(
+                SampleType::Float,
+                32,
+                1 << ffi::VSAudioChannels::FrontLeft as u64,
+            ),
+            sample_rate: 48000,
+            num_samples,
+            num_frames: 0,
+        };
+        let node = AudioNode::new("Blank", &info(100), Blank, &[], &core);
+        assert!(node.is_some_and(|node| node.info().num_frames == 1));
+        assert!(AudioNode::new("Blank", &info(0), Blank, &[], &core).is_none());
+    }
+}
@@ -0,0 +1,450 @@
+use std::{
+    ffi::CStr,
+    future::Future,
+    panic::RefUnwindSafe,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+};
+
+use crate::{
+    VideoInfo,
+    api::Api,
+    core::CoreRef,
+    ffi,
+    frame::{FrameContext, VideoFrame},
+    map::MapRef,
+};
+
+use super::{
+    ActivationReason, Dependencies, Filter, FilterDependency, FrameStateSlot, Instance, Node,
+    RequestPattern, VideoNode, mode::Parallel,
+};
+
+/// A filter written as an `async fn`, which awaits the frames it needs with
+/// [`AsyncContext::request`].
+///
+/// Wrap it in [`Async`] to get a [`Filter`], which drives the future across activations.
+/// Each time the future waits for frames, they are requested from `VapourSynth`,
+/// and the future is resumed once all of them are ready. No thread is ever blocked.
+///
+/// The future is only polled again when the frames it requested are ready, its waker is
+/// never woken. A future that waits for anything else, such as a channel, a timer or a
+/// `FuturesUnordered` (which only polls the futures that woke it, even frame requests),
+/// is never resumed, and the frame request fails once the future waits without any frame
+/// requested.
+///
+/// ```ignore
+/// async fn get_frame(&self, n: i32, ctx: AsyncContext) -> Result<VideoFrame, Self::Error> {
+///     let src = ctx.request(&self.clip, n).await;
+///     let k = src.properties().and_then(|p| p.get_as::<i32>(key!(c"Ref")).ok()).unwrap_or(n);
+///     Ok(ctx.request(&self.clip, k).await)
+/// }
+/// ```
+pub trait AsyncFilter: Sized + Send + Sync + RefUnwindSafe + 'static {
+    /// Filter error that can turned into a [`&CStr`](std::ffi::CStr)
+    type Error: AsRef<CStr> + From<&'static CStr> + Send;
+
+    const NAME: &'static CStr;
+    const ARGS: &'static CStr;
+
+    /// Creates the filter from its arguments.
+    ///
+    /// # Errors
+    ///
+    /// Return [`Self::Error`] if the arguments are invalid.
+    /// The error message will be passed to `VapourSynth`.
+    fn create(args: MapRef, core: CoreRef) -> Result<Self, Self::Error>;
+
+    /// The clips whose frames may be requested.
+    fn inputs(&self) -> &[VideoNode];
+
+    /// The output clip info.
+    fn info(&self) -> VideoInfo;
+
+    /// Produces frame `n`.
+    fn get_frame(
+        &self,
+        n: i32,
+        ctx: AsyncContext,
+    ) -> impl Future<Output = Result<VideoFrame, Self::Error>> + Send;
+}
+
+#[derive(Default)]
+struct Requests {
+    /// Requested by the future, but not from `VapourSynth` yet.
+    pending: Vec<(VideoNode, i32)>,
+    /// Requested from `VapourSynth`, ready on the next activation.
+    requested: Vec<(VideoNode, i32)>,
+    ready: Vec<(VideoNode, i32, VideoFrame)>,
+}
+
+/// Requests frames for an [`AsyncFilter`].
+#[derive(Clone)]
+pub struct AsyncContext {
+    requests: Arc<Mutex<Requests>>,
+    core: *const ffi::VSCore,
+    api: Api,
+}
+
+// SAFETY: The core is valid during the whole lifetime of the filter,
+// and the API is thread-safe
+unsafe impl Send for AsyncContext {}
+unsafe impl Sync for AsyncContext {}
+
+impl AsyncContext {
+    fn lock(&self) -> MutexGuard<'_, Requests> {
+        self.requests
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    #[must_use]
+    pub fn core(&self) -> CoreRef<'_> {
+        unsafe { CoreRef::from_ptr(self.core, self.api) }
+    }
+
+    /// Waits for frame `n` of `node`.
+    #[must_use]
+    pub fn request(&self, node: &VideoNode, n: i32) -> FrameRequest {
+        FrameRequest {
+            ctx: self.clone(),
+            node: node.clone(),
+            n,
+        }
+    }
+}
+
+/// A frame requested by [`AsyncContext::request`].
+pub struct FrameRequest {
+    ctx: AsyncContext,
+    node: VideoNode,
+    n: i32,
+}
+
+impl Future for FrameRequest {
+    type Output = VideoFrame;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let key = |node: &VideoNode, n: i32| node.as_ptr() == self.node.as_ptr() && n == self.n;
+
+        let mut requests = self.ctx.lock();
+        if let Some((_, _, frame)) = requests.ready.iter().find(|(node, n, _)| key(node, *n)) {
+            return Poll::Ready(frame.clone());
+        }
+        if !requests
+            .pending
+            .iter()
+            .chain(&requests.requested)
+            .any(|(node, n)| key(node, *n))
+        {
+            requests.pending.push((self.node.clone(), self.n));
+        }
+
+        Poll::Pending
+    }
+}
+
+/// The in-flight [`AsyncFilter::get_frame`] of a frame request.
+///
+/// The future borrows the filter instance as `&'static T`, which is sound because the
+/// state never outlives the instance:
+///
+/// - The frame state is dropped by the `getFrame` trampoline as soon as the request
+///   returns a frame, returns an error, panics or is activated with
+///   [`ActivationReason::Error`], so it only lives while the request is in flight.
+/// - `VapourSynth` calls the `free` function of a filter, which drops the instance,
+///   only after every frame request of the node has finished.
+pub struct AsyncFrameState<T: AsyncFilter> {
+    future: Pin<Box<dyn Future<Output = Result<VideoFrame, T::Error>> + Send>>,
+    ctx: AsyncContext,
+}
+
+impl<T: AsyncFilter> AsyncFrameState<T> {
+    /// Polls the future once and requests the frames it waits for.
+    fn drive(&mut self, frame_ctx: &mut FrameContext) -> Result<Option<VideoFrame>, T::Error> {
+        let mut cx = Context::from_waker(Waker::noop());
+        if let Poll::Ready(frame) = self.future.as_mut().poll(&mut cx) {
+            return frame.map(Some);
+        }
+
+        let mut requests = self.ctx.lock();
+        if requests.pending.is_empty() {
+            return Err(c"Filter is waiting without requesting any frame".into());
+        }
+        let pending = std::mem::take(&mut requests.pending);
+        for (node, n) in &pending {
+            frame_ctx.request_frame_filter(*n, node);
+        }
+        requests.requested = pending;
+
+        Ok(None)
+    }
+}
+
+/// Adapter from [`AsyncFilter`] to [`Filter`].
+pub struct Async<T> {
+    inner: T,
+}
+
+impl<T> Async<T> {
+    #[must_use]
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: AsyncFilter> Filter for Async<T> {
+    type Mode = Parallel;
+    type Error = T::Error;
+    type FrameType = VideoFrame;
+    type FilterData = ();
+    type FrameState = AsyncFrameState<T>;
+
+    const NAME: &'static CStr = T::NAME;
+    const ARGS: &'static CStr = T::ARGS;
+    const RETURN_TYPE: &'static CStr = c"clip:vnode;";
+
+    fn create(
+        input: MapRef,
+        output: MapRef,
+        _data: Option<Box<Self::FilterData>>,
+        mut core: CoreRef,
+    ) -> Result<(), Self::Error> {
+        let inner = T::create(input, core)?;
+        let deps: Vec<_> = inner
+            .inputs()
+            .iter()
+            .map(|node| FilterDependency {
+                source: node.as_ptr(),
+                request_pattern: RequestPattern::General,
+            })
+            .collect();
+
+        core.create_video_filter(
+            output,
+            T::NAME,
+            &inner.info(),
+            Box::new(Async { inner }),
+            Dependencies::new(&deps).unwrap(),
+        );
+
+        Ok(())
+    }
+
+    fn get_frame(
+        this: Instance<'_, Self>,
+        n: i32,
+        activation_reason: ActivationReason,
+        mut frame_state: FrameStateSlot<'_, Self::FrameState>,
+        mut ctx: FrameContext,
+        core: CoreRef,
+    ) -> Result<Option<VideoFrame>, Self::Error> {
+        match activation_reason {
+            ActivationReason::Initial => {
+                let async_ctx = AsyncContext {
+                    requests: Arc::default(),
+                    core: core.as_ptr(),
+                    api: core.api(),
+                };
+                // SAFETY: The future lives in the frame state, which is dropped before the
+                // request finishes, and `filter_free` only runs after all requests finished.
+                // See `AsyncFrameState`.
+                let inner: &'static T = unsafe { &*std::ptr::from_ref(&this.inner) };
+                let state = frame_state.insert(AsyncFrameState {
+                    future: Box::pin(inner.get_frame(n, async_ctx.clone())),
+                    ctx: async_ctx,
+                });
+                state.drive(&mut ctx)
+            }
+            ActivationReason::AllFramesReady => {
+                let Some(state) = frame_state.get_mut() else {
+                    return Err(c"Frame state is missing".into());
+                };
+                {
+                    let mut requests = state.ctx.lock();
+                    let requested = std::mem::take(&mut requests.requested);
+                    for (node, n) in requested {
+                        let frame = node.get_frame_filter(n, &mut ctx);
+                        requests.ready.push((node, n, frame));
+                    }
+                }
+                state.drive(&mut ctx)
+            }
+            ActivationReason::Error => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use testresult::TestResult;
+
+    use super::*;
+    use crate::{ColorFamily, SampleType, core::Core, mock, node::Node};
+
+    /// Counts the dropped futures of [`Chain`].
+    struct Guard(Arc<AtomicUsize>);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Returns frame 0 directly, refuses frame 3 after waiting for it, waits forever
+    /// for frame 5, and otherwise returns the frame after the value of frame `n`.
+    struct Chain {
+        clip: [VideoNode; 1],
+        drops: Arc<AtomicUsize>,
+    }
+
+    fn value(frame: &VideoFrame) -> u8 {
+        // SAFETY: The frame has at least one sample
+        unsafe { *frame.plane(0) }
+    }
+
+    impl AsyncFilter for Chain {
+        type Error = &'static CStr;
+
+        const NAME: &'static CStr = c"Chain";
+        const ARGS: &'static CStr = c"";
+
+        fn create(_args: MapRef, _core: CoreRef) -> Result<Self, Self::Error> {
+            Err(c"Chain: not a plugin function")
+        }
+
+        fn inputs(&self) -> &[VideoNode] {
+            &self.clip
+        }
+
+        fn info(&self) -> VideoInfo {
+            self.clip[0].info().clone()
+        }
+
+        async fn get_frame(&self, n: i32, ctx: AsyncContext) -> Result<VideoFrame, Self::Error> {
+            let _guard = Guard(self.drops.clone());
+            let clip = &self.clip[0];
+            match n {
+                0 => Ok(ctx.request(clip, 0).await),
+                3 => {
+                    ctx.request(clip, 3).await;
+                    Err(c"Chain: frame 3 is refused")
+                }
+                5 => {
+                    std::future::pending::<()>().await;
+                    Err(c"Chain: unreachable")
+                }
+                _ => {
+                    let first = ctx.request(clip, n).await;
+                    Ok(ctx.request(clip, i32::from(value(&first)) + 1).await)
+                }
+            }
+        }
+    }
+
+    /// A source with the frame number as the first sample, failing at frame 5,
+    /// and a [`Chain`] over it.
+    fn chain(core: &Core) -> TestResult<(VideoNode, Arc<AtomicUsize>)> {
+        let format = core.query_video_format(ColorFamily::Gray, SampleType::Integer, 8, 0, 0);
+        let info = VideoInfo {
+            format,
+            fps_num: 30,
+            fps_den: 1,
+            width: 4,
+            height: 2,
+            num_frames: 6,
+        };
+        let clip = core
+            .video_source(info.clone(), Parallel, |n, frame, _| {
+                if n == 5 {
+                    return Err(c"frame 5 is broken");
+                }
+                let value = u8::try_from(n).map_err(|_| c"out of range")?;
+                // SAFETY: The frame has at least one sample
+                unsafe { frame.plane_mut(0).write(value) };
+                Ok(())
+            })
+            .ok_or("failed to create source")?;
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let deps = [FilterDependency {
+            source: clip.as_ptr(),
+            request_pattern: RequestPattern::General,
+        }];
+        let chain = Async {
+            inner: Chain {
+                clip: [clip],
+                drops: drops.clone(),
+            },
+        };
+        let node = VideoNode::new("Chain", &info, chain, &deps, core).ok_or("failed to create")?;
+        Ok((node, drops))
+    }
+
+    fn get_frame(node: &VideoNode, n: i32) -> Result<u8, String> {
+        node.get_frame(n)
+            .map(|frame| value(&frame))
+            .map_err(|e| e.to_string_lossy().into_owned())
+    }
+
+    #[test]
+    fn single_request() -> TestResult {
+        let core = Core::builder().api(mock::api()).build();
+        let (node, drops) = chain(&core)?;
+
+        assert_eq!(get_frame(&node, 0)?, 0);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn multiple_rounds() -> TestResult {
+        let core = Core::builder().api(mock::api()).build();
+        let (node, drops) = chain(&core)?;
+
+        assert_eq!(get_frame(&node, 1)?, 2);
+        assert_eq!(get_frame(&node, 2)?, 3);
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn error_while_pending() -> TestResult {
+        let core = Core::builder().api(mock::api()).build();
+        let (node, drops) = chain(&core)?;
+
+        // Frame 4 waits for frame 5 of the source, which fails
+        let err = get_frame(&node, 4).expect_err("frame 5 of the source should fail");
+        assert!(err.contains("frame 5 is broken"), "{err}");
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        // The instance is still usable after the state of the failed request is dropped
+        assert_eq!(get_frame(&node, 0)?, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn early_drop() -> TestResult {
+        let core = Core::builder().api(mock::api()).build();
+        let (node, drops) = chain(&core)?;
+
+        let err = get_frame(&node, 3).expect_err("frame 3 should be refused");
+        assert!(err.contains("frame 3 is refused"), "{err}");
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        let err = get_frame(&node, 5).expect_err("frame 5 should never be ready");
+        assert!(err.contains("without requesting any frame"), "{err}");
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+
+        drop(node);
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+}
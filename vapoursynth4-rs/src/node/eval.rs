@@ -0,0 +1,281 @@
+use std::{ffi::CStr, panic::RefUnwindSafe};
+
+use crate::{
+    ColorFamily, SampleType,
+    core::{Core, CoreRef},
+    frame::{Frame, FrameContext, VideoFormat, VideoFrame},
+    map::MapRef,
+};
+
+use super::{
+    ActivationReason, Filter, FilterDependency, FrameStateSlot, Instance, Node, RequestPattern,
+    VideoNode, mode::Parallel,
+};
+
+impl VideoNode {
+    /// Chooses one of `clips` per frame, like `std.FrameEval`.
+    ///
+    /// `eval` is called with the frame number and the properties of frame `n` of each of
+    /// `prop_src`, and returns the index of the clip to take frame `n` from.
+    /// The output has the length of the first clip, and a variable format or size
+    /// if the clips differ in them.
+    ///
+    /// Returns `None` if `clips` is empty or the filter could not be created.
+    pub fn frame_eval<F>(
+        core: impl AsRef<Core>,
+        clips: &[VideoNode],
+        prop_src: &[VideoNode],
+        eval: F,
+    ) -> Option<VideoNode>
+    where
+        F: Fn(i32, &[MapRef]) -> usize + Send + Sync + RefUnwindSafe + 'static,
+    {
+        let (first, rest) = clips.split_first()?;
+        let mut info = first.info().clone();
+        for clip in rest {
+            let other = clip.info();
+            if other.format != info.format {
+                info.format = VideoFormat {
+                    color_family: ColorFamily::Undefined,
+                    sample_type: SampleType::Integer,
+                    bits_per_sample: 0,
+                    bytes_per_sample: 0,
+                    sub_sampling_w: 0,
+                    sub_sampling_h: 0,
+                    num_planes: 0,
+                };
+            }
+            if (other.width, other.height) != (info.width, info.height) {
+                info.width = 0;
+                info.height = 0;
+            }
+        }
+
+        let deps: Vec<_> = clips
+            .iter()
+            .chain(prop_src)
+            .map(|node| FilterDependency {
+                source: node.as_ptr(),
+                request_pattern: RequestPattern::General,
+            })
+            .collect();
+        let filter = FrameEval {
+            clips: clips.to_vec(),
+            prop_src: prop_src.to_vec(),
+            eval,
+        };
+
+        VideoNode::new("FrameEval", &info, filter, &deps, core)
+    }
+}
+
+/// The filter behind [`VideoNode::frame_eval`].
+pub(crate) struct FrameEval<F> {
+    clips: Vec<VideoNode>,
+    prop_src: Vec<VideoNode>,
+    eval: F,
+}
+
+impl<F> FrameEval<F>
+where
+    F: Fn(i32, &[MapRef]) -> usize + Send + Sync + RefUnwindSafe + 'static,
+{
+    fn eval(&self, n: i32, ctx: &mut FrameContext) -> Result<usize, &'static CStr> {
+        let frames: Vec<_> = self
+            .prop_src
+            .iter()
+            .map(|node| node.get_frame_filter(n, ctx))
+            .collect();
+        let props = frames
+            .iter()
+            .map(Frame::properties)
+            .collect::<Option<Vec<_>>>()
+            .ok_or(c"FrameEval: failed to get frame properties")?;
+
+        let index = (self.eval)(n, &props);
+        if index < self.clips.len() {
+            Ok(index)
+        } else {
+            Err(c"FrameEval: returned clip index is out of range")
+        }
+    }
+}
+
+impl<F> Filter for FrameEval<F>
+where
+    F: Fn(i32, &[MapRef]) -> usize + Send + Sync + RefUnwindSafe + 'static,
+{
+    type Mode = Parallel;
+    type Error = &'static CStr;
+    type FrameType = VideoFrame;
+    type FilterData = ();
+    /// The chosen clip, once `eval` is called.
+    type FrameState = usize;
+
+    const NAME: &'static CStr = c"FrameEval";
+    const ARGS: &'static CStr = c"";
+    const RETURN_TYPE: &'static CStr = c"clip:vnode;";
+
+    fn create(
+        _input: MapRef,
+        _output: MapRef,
+        _data: Option<Box<Self::FilterData>>,
+        _core: CoreRef,
+    ) -> Result<(), Self::Error> {
+        Err(c"FrameEval can only be created with VideoNode::frame_eval")
+    }
+
+    fn get_frame(
+        this: Instance<'_, Self>,
+        n: i32,
+        activation_reason: ActivationReason,
+        mut frame_state: FrameStateSlot<'_, usize>,
+        mut ctx: FrameContext,
+        _core: CoreRef,
+    ) -> Result<Option<VideoFrame>, Self::Error> {
+        match (activation_reason, frame_state.get().copied()) {
+            (ActivationReason::Initial, _) if this.prop_src.is_empty() => {
+                let index = this.eval(n, &mut ctx)?;
+                ctx.request_frame_filter(n, &this.clips[index]);
+                frame_state.insert(index);
+            }
+            (ActivationReason::Initial, _) => {
+                for node in &this.prop_src {
+                    ctx.request_frame_filter(n, node);
+                }
+            }
+            (ActivationReason::AllFramesReady, None) => {
+                let index = this.eval(n, &mut ctx)?;
+                ctx.request_frame_filter(n, &this.clips[index]);
+                frame_state.insert(index);
+            }
+            (ActivationReason::AllFramesReady, Some(index)) => {
+                return Ok(Some(this.clips[index].get_frame_filter(n, &mut ctx)));
+            }
+            (ActivationReason::Error, _) => {}
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use testresult::TestResult;
+
+    use super::*;
+    #[cfg(feature = "link-vs")]
+    use crate::map;
+    use crate::{VideoInfo, key, mock};
+
+    /// A 4x2 clip with `value` as the first sample and `_Pick` set to `n % 3`,
+    /// failing at frame 4.
+    fn source(core: &Core, bits: i32, value: u8) -> TestResult<VideoNode> {
+        let format = core.query_video_format(ColorFamily::Gray, SampleType::Integer, bits, 0, 0);
+        let info = VideoInfo {
+            format,
+            fps_num: 30,
+            fps_den: 1,
+            width: 4,
+            height: 2,
+            num_frames: 5,
+        };
+        let node = core.video_source(info, Parallel, move |n, frame, _| {
+            if n == 4 {
+                return Err(c"frame 4 is broken");
+            }
+            // SAFETY: The frame has at least one sample
+            unsafe { frame.plane_mut(0).write(value) };
+            frame
+                .properties_mut()
+                .ok_or(c"no props")?
+                .insert(key!(c"_Pick"), i64::from(n % 3))
+                .map_err(|_| c"failed to set prop")?;
+            Ok::<_, &CStr>(())
+        });
+        Ok(node.ok_or("failed to create source")?)
+    }
+
+    #[test]
+    fn frame_eval_mock() -> TestResult {
+        let core = Core::builder().api(mock::api()).build();
+        let clips = [source(&core, 8, 0)?, source(&core, 8, 255)?];
+        let get_frame = |node: &VideoNode, n| {
+            node.get_frame(n)
+                // SAFETY: The frame has at least one sample
+                .map(|frame| unsafe { *frame.plane(0) })
+                .map_err(|e| e.to_string_lossy().into_owned())
+        };
+
+        // Without property sources, the clip is chosen in the initial activation
+        let node = VideoNode::frame_eval(&core, &clips, &[], |n, props| {
+            assert!(props.is_empty());
+            usize::try_from(n % 2).unwrap_or(0)
+        })
+        .ok_or("failed to create FrameEval")?;
+        assert_eq!(node.info(), clips[0].info());
+        assert_eq!(get_frame(&node, 0)?, 0);
+        assert_eq!(get_frame(&node, 1)?, 255);
+
+        // With property sources, the clip is chosen once their frames are ready
+        let node = VideoNode::frame_eval(&core, &clips, &clips[..1], |_, props| {
+            props[0]
+                .get_int(key!(c"_Pick"), 0)
+                .map_or(0, |pick| usize::try_from(pick).unwrap_or(0))
+        })
+        .ok_or("failed to create FrameEval")?;
+        assert_eq!(get_frame(&node, 0)?, 0);
+        assert_eq!(get_frame(&node, 1)?, 255);
+        let err = get_frame(&node, 2).expect_err("index 2 is out of range");
+        assert!(err.contains("returned clip index is out of range"), "{err}");
+        let err = get_frame(&node, 4).expect_err("the property source should fail");
+        assert!(err.contains("frame 4 is broken"), "{err}");
+
+        // Clips of different formats give a variable format
+        let mixed = [source(&core, 8, 0)?, source(&core, 16, 0)?];
+        let node = VideoNode::frame_eval(&core, &mixed, &[], |_, _| 1)
+            .ok_or("failed to create FrameEval")?;
+        assert_eq!(node.info().format.color_family, ColorFamily::Undefined);
+        assert_eq!((node.info().width, node.info().height), (4, 2));
+
+        assert!(VideoNode::frame_eval(&core, &[], &[], |_, _| 0).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "link-vs")]
+    fn frame_eval() -> TestResult {
+        let core = Core::builder().build();
+        let std = core
+            .get_plugin_by_namespace(c"std")
+            .ok_or("std not found")?;
+        let blank = |color: i64| -> Result<VideoNode, Box<dyn std::error::Error>> {
+            let args = map!(core; "length" => 4, "color" => [color, color, color])?;
+            Ok(std
+                .invoke(c"BlankClip", args)
+                .get_as::<VideoNode>(crate::key!(c"clip"))?)
+        };
+        let clips = [blank(0)?, blank(255)?];
+
+        let node = VideoNode::frame_eval(&core, &clips, &clips[..1], |n, props| {
+            assert_eq!(props.len(), 1);
+            usize::try_from(n % 2).unwrap()
+        })
+        .ok_or("failed to create FrameEval")?;
+
+        assert_eq!(node.info().num_frames, 4);
+        for n in 0..4 {
+            let frame = node
+                .get_frame(n)
+                .map_err(|e| e.to_string_lossy().into_owned())?;
+            let pixel = unsafe { *frame.plane(0) };
+            assert_eq!(pixel, if n % 2 == 0 { 0 } else { 255 });
+        }
+
+        let bad = VideoNode::frame_eval(&core, &clips, &[], |_, _| 2).ok_or("failed")?;
+        assert!(bad.get_frame(0).is_err());
+
+        Ok(())
+    }
+}
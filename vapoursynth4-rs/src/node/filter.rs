@@ -1,25 +1,30 @@
-use std::{
-    ffi::{CStr, c_void},
-    ptr::null_mut,
-};
+use std::{ffi::CStr, ptr::null_mut};
 
 use crate::{
     core::CoreRef,
     ffi,
     frame::{Frame, FrameContext},
     map::MapRef,
-    node::FilterMode,
+    node::{FrameStateSlot, Instance, PanicPolicy, mode},
 };
 
 pub trait Filter
 where
-    Self: Sized + std::panic::RefUnwindSafe,
+    Self: Sized + Send + std::panic::RefUnwindSafe,
 {
-    const FILTER_MODE: FilterMode = FilterMode::Parallel;
+    /// How `VapourSynth` calls [`get_frame`](Self::get_frame), one of the markers in [`mode`].
+    type Mode: mode::Mode<Self>;
+    /// How to handle a panic in [`create`](Self::create), [`get_frame`](Self::get_frame)
+    /// or [`free`](Self::free).
+    const PANIC_POLICY: PanicPolicy = PanicPolicy::Report;
+    /// Whether to append a backtrace to the panic message.
+    const PANIC_BACKTRACE: bool = false;
     /// Filter error that can turned into a [`&CStr`](std::ffi::CStr)
     type Error: AsRef<CStr>;
     type FrameType: Frame;
-    type FilterData; // TODO: Ensure Send + Sync when FILTER_MODE is Parallel
+    type FilterData;
+    /// State kept across the activations of a frame request, see [`FrameStateSlot`].
+    type FrameState: Send;
 
     const NAME: &'static CStr;
     const ARGS: &'static CStr;
@@ -41,10 +46,10 @@ where
     /// Return [`Self::Error`] if anything happens during the filter creation.
     /// The error message will be passed to `VapourSynth`.
     fn get_frame(
-        &self,
+        this: Instance<'_, Self>,
         n: i32,
         activation_reason: ffi::VSActivationReason,
-        frame_data: *mut *mut c_void,
+        frame_state: FrameStateSlot<'_, Self::FrameState>,
         frame_ctx: FrameContext,
         core: CoreRef,
     ) -> Result<Option<Self::FrameType>, Self::Error>;
@@ -1,7 +1,6 @@
 use std::{
     ffi::{c_int, c_void},
     mem::ManuallyDrop,
-    panic::AssertUnwindSafe,
     ptr::null,
 };
 
@@ -10,12 +9,17 @@ use crate::{
     core::CoreRef,
     frame::{Frame, FrameContext},
     map::MapRef,
-    utils::ToCString,
 };
 
-use super::{Filter, ffi};
+use super::{
+    ActivationReason, Filter, FilterMode, FrameStateSlot, Instance, ffi,
+    mode::Mode,
+    panic::{apply_policy, catch},
+};
 
 pub trait FilterExtern: Filter {
+    const FILTER_MODE: FilterMode = <Self::Mode as Mode<Self>>::MODE;
+
     unsafe extern "system-unwind" fn filter_create(
         in_: *const ffi::VSMap,
         out: *mut ffi::VSMap,
@@ -35,17 +39,19 @@ pub trait FilterExtern: Filter {
                 Some(Box::from_raw(user_data.cast()))
             };
 
-            match std::panic::catch_unwind(AssertUnwindSafe(|| {
+            match catch(Self::PANIC_BACKTRACE, || {
                 Self::create(input, output, data, core)
-            })) {
+            }) {
                 Ok(Err(e)) => {
                     output.set_error(e.as_ref());
                 }
                 Err(p) => {
-                    let e = p.downcast::<&str>().unwrap_unchecked();
-                    output.set_error(&e.into_cstring_lossy());
+                    let msg = p.to_message(Self::NAME.to_string_lossy());
+                    if let Some(msg) = apply_policy(Self::PANIC_POLICY, &msg, core) {
+                        output.set_error(msg);
+                    }
                 }
-                _ => {}
+                Ok(Ok(())) => {}
             }
         }
     }
@@ -61,13 +67,31 @@ pub trait FilterExtern: Filter {
     ) -> *const ffi::VSFrame {
         unsafe {
             let api = Api::from_ptr(vsapi);
-            let filter = instance_data.cast::<Self>().as_mut().unwrap_unchecked();
-            let mut ctx = AssertUnwindSafe(FrameContext::from_ptr(frame_ctx, api));
+            let filter = instance_data.cast::<Self>();
+            let mut ctx = FrameContext::from_ptr(frame_ctx, api);
             let core = CoreRef::from_ptr(core, api);
 
-            let frame = std::panic::catch_unwind(|| {
-                filter.get_frame(n, activation_reason, frame_data, *ctx, core)
+            let frame = catch(Self::PANIC_BACKTRACE, || {
+                let frame = Self::get_frame(
+                    Instance::from_ptr(filter),
+                    n,
+                    activation_reason,
+                    FrameStateSlot::from_ptr(frame_data),
+                    ctx,
+                    core,
+                );
+                // The request is finished unless more frames are pending
+                if activation_reason == ActivationReason::Error || !matches!(frame, Ok(None)) {
+                    drop(FrameStateSlot::<Self::FrameState>::from_ptr(frame_data).take());
+                }
+                frame
             });
+            if frame.is_err() {
+                // Drop the state of the panicked request, a second panic is ignored
+                let _ = catch(false, || {
+                    drop(FrameStateSlot::<Self::FrameState>::from_ptr(frame_data).take());
+                });
+            }
             match frame {
                 Ok(Ok(Some(frame))) => {
                     // Transfer the ownership to VapourSynth
@@ -78,8 +102,11 @@ pub trait FilterExtern: Filter {
                     ctx.set_filter_error(e.as_ref());
                 }
                 Err(p) => {
-                    let e = p.downcast::<&str>().unwrap_unchecked();
-                    ctx.set_filter_error(&e.into_cstring_lossy());
+                    let msg =
+                        p.to_message(format_args!("{}: frame {n}", Self::NAME.to_string_lossy()));
+                    if let Some(msg) = apply_policy(Self::PANIC_POLICY, &msg, core) {
+                        ctx.set_filter_error(msg);
+                    }
                 }
                 _ => {}
             }
@@ -98,7 +125,14 @@ pub trait FilterExtern: Filter {
             let filter = Box::from_raw(instance_data.cast::<Self>());
             let core = CoreRef::from_ptr(core, api);
 
-            filter.free(core);
+            if let Err(p) = catch(Self::PANIC_BACKTRACE, || filter.free(core)) {
+                let msg = p.to_message(Self::NAME.to_string_lossy());
+                if let Some(msg) = apply_policy(Self::PANIC_POLICY, &msg, core) {
+                    // There is no filter error to set while freeing
+                    let mut core = core;
+                    core.log(ffi::VSMessageType::Critical, msg);
+                }
+            }
         }
     }
 }
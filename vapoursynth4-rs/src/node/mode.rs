@@ -0,0 +1,221 @@
+//! Filter modes lifted into the type system.
+//!
+//! See [`VSFilterMode`](ffi::VSFilterMode) for how `VapourSynth` calls `get_frame` in each mode.
+//! [`Parallel`] and [`ParallelRequests`] filters may be shared between threads, so they must be
+//! [`Sync`]. [`Unordered`] and [`FrameStateMode`] filters are never called concurrently and get
+//! exclusive access through [`Instance`].
+
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use crate::ffi;
+
+use super::FilterMode;
+
+/// Completely parallel execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Parallel;
+
+/// For filters that are serial in nature but can request in advance one or more frames they need.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ParallelRequests;
+
+/// Only one thread can be inside the filter at a time, frames are requested in any order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Unordered;
+
+/// For compatibility with other filtering architectures, serial and in order.
+///
+/// Named after [`VSFilterMode::FrameState`](ffi::VSFilterMode::FrameState), not to be confused
+/// with [`Filter::FrameState`](super::Filter::FrameState).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FrameStateMode;
+
+/// A filter mode that `F` can run in.
+///
+/// A filter that is not [`Sync`] can only use an [`Exclusive`] mode:
+///
+/// ```
+/// use std::{cell::Cell, ffi::CStr, panic::AssertUnwindSafe};
+///
+/// use vapoursynth4_rs::{
+///     core::CoreRef,
+///     frame::{FrameContext, VideoFrame},
+///     map::MapRef,
+///     node::{ActivationReason, Filter, FrameStateSlot, Instance, mode},
+/// };
+///
+/// struct Counter {
+///     count: AssertUnwindSafe<Cell<u32>>,
+/// }
+///
+/// impl Filter for Counter {
+///     type Mode = mode::Unordered;
+///     type Error = &'static CStr;
+///     type FrameType = VideoFrame;
+///     type FilterData = ();
+///     type FrameState = ();
+///
+///     const NAME: &'static CStr = c"Counter";
+///     const ARGS: &'static CStr = c"";
+///     const RETURN_TYPE: &'static CStr = c"";
+///
+///     fn create(_: MapRef, _: MapRef, _: Option<Box<()>>, _: CoreRef) -> Result<(), Self::Error> {
+///         Ok(())
+///     }
+///
+///     fn get_frame(
+///         this: Instance<'_, Self>,
+///         _n: i32,
+///         _activation_reason: ActivationReason,
+///         _frame_state: FrameStateSlot<'_, ()>,
+///         _frame_ctx: FrameContext,
+///         _core: CoreRef,
+///     ) -> Result<Option<VideoFrame>, Self::Error> {
+///         this.count.set(this.count.get() + 1);
+///         Ok(None)
+///     }
+/// }
+/// ```
+///
+/// With [`Parallel`], the same filter is rejected because `Cell` is not [`Sync`]:
+///
+/// ```compile_fail,E0277
+/// # use std::{cell::Cell, ffi::CStr, panic::AssertUnwindSafe};
+/// #
+/// # use vapoursynth4_rs::{
+/// #     core::CoreRef,
+/// #     frame::{FrameContext, VideoFrame},
+/// #     map::MapRef,
+/// #     node::{ActivationReason, Filter, FrameStateSlot, Instance, mode},
+/// # };
+/// #
+/// # struct Counter {
+/// #     count: AssertUnwindSafe<Cell<u32>>,
+/// # }
+/// #
+/// impl Filter for Counter {
+///     type Mode = mode::Parallel;
+///     // ...
+/// #     type Error = &'static CStr;
+/// #     type FrameType = VideoFrame;
+/// #     type FilterData = ();
+/// #     type FrameState = ();
+/// #
+/// #     const NAME: &'static CStr = c"Counter";
+/// #     const ARGS: &'static CStr = c"";
+/// #     const RETURN_TYPE: &'static CStr = c"";
+/// #
+/// #     fn create(_: MapRef, _: MapRef, _: Option<Box<()>>, _: CoreRef) -> Result<(), Self::Error> {
+/// #         Ok(())
+/// #     }
+/// #
+/// #     fn get_frame(
+/// #         this: Instance<'_, Self>,
+/// #         _n: i32,
+/// #         _activation_reason: ActivationReason,
+/// #         _frame_state: FrameStateSlot<'_, ()>,
+/// #         _frame_ctx: FrameContext,
+/// #         _core: CoreRef,
+/// #     ) -> Result<Option<VideoFrame>, Self::Error> {
+/// #         this.count.set(this.count.get() + 1);
+/// #         Ok(None)
+/// #     }
+/// }
+/// ```
+pub trait Mode<F>: crate::_private::Sealed {
+    const MODE: FilterMode;
+}
+
+/// A filter mode that never calls into the filter concurrently.
+pub trait Exclusive: crate::_private::Sealed {}
+
+impl crate::_private::Sealed for Parallel {}
+impl crate::_private::Sealed for ParallelRequests {}
+impl crate::_private::Sealed for Unordered {}
+impl crate::_private::Sealed for FrameStateMode {}
+
+impl<F: Sync> Mode<F> for Parallel {
+    const MODE: FilterMode = ffi::VSFilterMode::Parallel;
+}
+
+impl<F: Sync> Mode<F> for ParallelRequests {
+    const MODE: FilterMode = ffi::VSFilterMode::ParallelRequests;
+}
+
+impl<F> Mode<F> for Unordered {
+    const MODE: FilterMode = ffi::VSFilterMode::Unordered;
+}
+
+impl<F> Mode<F> for FrameStateMode {
+    const MODE: FilterMode = ffi::VSFilterMode::FrameState;
+}
+
+impl Exclusive for Unordered {}
+impl Exclusive for FrameStateMode {}
+
+/// The filter instance handed to [`Filter::get_frame`](super::Filter::get_frame).
+///
+/// It always derefs to `&F`, and to `&mut F` if the [filter mode](super::Filter::Mode) is
+/// [`Exclusive`].
+pub struct Instance<'a, F: super::Filter> {
+    ptr: *mut F,
+    _marker: PhantomData<&'a mut F>,
+}
+
+impl<F: super::Filter> Instance<'_, F> {
+    /// # Safety
+    ///
+    /// The caller must ensure that `ptr` is a valid instance and is not accessed mutably elsewhere,
+    /// and not shared at all if the filter mode is [`Exclusive`].
+    pub(crate) unsafe fn from_ptr(ptr: *mut F) -> Self {
+        Self {
+            ptr,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: super::Filter> Deref for Instance<'_, F> {
+    type Target = F;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.ptr }
+    }
+}
+
+impl<F> DerefMut for Instance<'_, F>
+where
+    F: super::Filter,
+    F::Mode: Exclusive,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.ptr }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, marker::PhantomData};
+
+    use super::*;
+
+    fn mode<M: Mode<F>, F>(_: M, _: PhantomData<F>) -> FilterMode {
+        M::MODE
+    }
+
+    #[test]
+    fn modes() {
+        // `Cell` is `Send` but not `Sync`
+        let not_sync = PhantomData::<Cell<u32>>;
+        assert_eq!(mode(Parallel, PhantomData::<u32>), FilterMode::Parallel);
+        assert_eq!(
+            mode(ParallelRequests, PhantomData::<u32>),
+            FilterMode::ParallelRequests
+        );
+        assert_eq!(mode(Unordered, not_sync), FilterMode::Unordered);
+        assert_eq!(mode(FrameStateMode, not_sync), FilterMode::FrameState);
+    }
+}
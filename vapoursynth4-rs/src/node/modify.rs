@@ -0,0 +1,297 @@
+use std::{
+    ffi::{CStr, CString},
+    marker::PhantomData,
+    panic::RefUnwindSafe,
+};
+
+use crate::{
+    core::{Core, CoreRef},
+    frame::{Frame, FrameContext},
+    map::{Map, MapRef, Value},
+    utils::ToCString,
+};
+
+use super::{
+    ActivationReason, AudioNode, Filter, FilterDependency, FrameStateSlot, Instance, Node,
+    RequestPattern, VideoNode, mode::Parallel,
+};
+
+impl VideoNode {
+    /// Edits the frame properties with `modify`, like `std.ModifyFrame` only touching props.
+    ///
+    /// `modify` is called with the frame number and a copy of the frame properties.
+    /// The frame is only copied, sharing the frame data, if `modify` changes the properties,
+    /// otherwise the source frame is returned.
+    /// An error returned by `modify` becomes the error of the frame request.
+    ///
+    /// Returns `None` if the filter could not be created.
+    pub fn modify_props<F, E>(&self, core: impl AsRef<Core>, modify: F) -> Option<VideoNode>
+    where
+        F: Fn(i32, &mut Map) -> Result<(), E> + Send + Sync + RefUnwindSafe + 'static,
+        E: AsRef<CStr>,
+    {
+        let deps = [dependency(self)];
+        VideoNode::new(
+            "ModifyProps",
+            &self.info().clone(),
+            ModifyProps::new(self.clone(), modify),
+            &deps,
+            core,
+        )
+    }
+}
+
+impl AudioNode {
+    /// Edits the frame properties with `modify`, see [`VideoNode::modify_props`].
+    ///
+    /// Returns `None` if the filter could not be created.
+    pub fn modify_props<F, E>(&self, core: impl AsRef<Core>, modify: F) -> Option<AudioNode>
+    where
+        F: Fn(i32, &mut Map) -> Result<(), E> + Send + Sync + RefUnwindSafe + 'static,
+        E: AsRef<CStr>,
+    {
+        let deps = [dependency(self)];
+        AudioNode::new(
+            "ModifyProps",
+            &self.info().clone(),
+            ModifyProps::new(self.clone(), modify),
+            &deps,
+            core,
+        )
+    }
+}
+
+fn dependency(node: &impl Node) -> FilterDependency {
+    FilterDependency {
+        source: node.as_ptr(),
+        request_pattern: RequestPattern::StrictSpatial,
+    }
+}
+
+/// The filter behind [`VideoNode::modify_props`] and [`AudioNode::modify_props`].
+pub(crate) struct ModifyProps<N, F, E> {
+    node: N,
+    modify: F,
+    _error: PhantomData<fn() -> E>,
+}
+
+impl<N, F, E> ModifyProps<N, F, E> {
+    fn new(node: N, modify: F) -> Self {
+        Self {
+            node,
+            modify,
+            _error: PhantomData,
+        }
+    }
+}
+
+impl<N, F, E> Filter for ModifyProps<N, F, E>
+where
+    N: Node + RefUnwindSafe,
+    F: Fn(i32, &mut Map) -> Result<(), E> + Send + Sync + RefUnwindSafe + 'static,
+    E: AsRef<CStr>,
+{
+    type Mode = Parallel;
+    type Error = CString;
+    type FrameType = N::FrameType;
+    type FilterData = ();
+    type FrameState = ();
+
+    const NAME: &'static CStr = c"ModifyProps";
+    const ARGS: &'static CStr = c"";
+    const RETURN_TYPE: &'static CStr = c"";
+
+    fn create(
+        _input: MapRef,
+        _output: MapRef,
+        _data: Option<Box<Self::FilterData>>,
+        _core: CoreRef,
+    ) -> Result<(), Self::Error> {
+        Err(c"ModifyProps can only be created with modify_props".into())
+    }
+
+    fn get_frame(
+        this: Instance<'_, Self>,
+        n: i32,
+        activation_reason: ActivationReason,
+        _frame_state: FrameStateSlot<'_, ()>,
+        mut ctx: FrameContext,
+        core: CoreRef,
+    ) -> Result<Option<Self::FrameType>, Self::Error> {
+        match activation_reason {
+            ActivationReason::Initial => {
+                ctx.request_frame_filter(n, &this.node);
+                Ok(None)
+            }
+            ActivationReason::AllFramesReady => {
+                let src = this.node.get_frame_filter(n, &mut ctx);
+                let Some(orig) = src.properties() else {
+                    return Err(c"ModifyProps: failed to get frame properties".into());
+                };
+                let mut props = (*orig).clone();
+                (this.modify)(n, &mut props).map_err(|e| e.as_ref().to_owned())?;
+                if props_eq(&props, &orig) {
+                    return Ok(Some(src));
+                }
+
+                // The copy shares the frame data, only the properties are replaced
+                let mut dst = core.copy_frame(&src);
+                let Some(mut dst_props) = dst.properties_mut() else {
+                    return Err(c"ModifyProps: failed to get frame properties".into());
+                };
+                dst_props.clear();
+                dst_props
+                    .extend_from(&props, |_| true)
+                    .map_err(|e| e.to_string().into_cstring_lossy())?;
+
+                Ok(Some(dst))
+            }
+            ActivationReason::Error => Ok(None),
+        }
+    }
+}
+
+/// Compares two maps in place, with floats compared by bits so that NaN equals itself.
+fn props_eq(a: &Map, b: &Map) -> bool {
+    a.len() == b.len()
+        && a.keys().all(|key| {
+            let len = a.num_elements(key);
+            a.get_type(key) == b.get_type(key)
+                && len == b.num_elements(key)
+                && (0..len.unwrap_or(0)).all(|i| match (a.get(key, i), b.get(key, i)) {
+                    (Ok(Value::Float(a)), Ok(Value::Float(b))) => a.to_bits() == b.to_bits(),
+                    (Ok(a), Ok(b)) => a == b,
+                    _ => false,
+                })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use testresult::TestResult;
+
+    use super::*;
+    #[cfg(feature = "link-vs")]
+    use crate::map;
+    use crate::{
+        ColorFamily, SampleType, VideoInfo, key, map::PropertyType, mock, node::mode::Parallel,
+    };
+
+    fn mock_core() -> Core {
+        Core::builder().api(mock::api()).build()
+    }
+
+    #[test]
+    fn props() -> TestResult {
+        let core = mock_core();
+        let mut a = core.create_map();
+        a.insert(key!(c"nan"), f64::NAN)?;
+        a.set_empty(key!(c"empty"), PropertyType::Int);
+        let mut b = a.clone();
+        assert!(props_eq(&a, &b));
+
+        b.insert(key!(c"empty"), [1])?;
+        assert!(!props_eq(&a, &b));
+        a.insert(key!(c"empty"), [1])?;
+        a.insert(key!(c"extra"), 0)?;
+        assert!(!props_eq(&a, &b));
+        assert!(!props_eq(&b, &a));
+
+        Ok(())
+    }
+
+    #[test]
+    fn modify_props_mock() -> TestResult {
+        let core = mock_core();
+        let format = core.query_video_format(ColorFamily::Gray, SampleType::Integer, 8, 0, 0);
+        let info = VideoInfo {
+            format,
+            fps_num: 30,
+            fps_den: 1,
+            width: 4,
+            height: 2,
+            num_frames: 4,
+        };
+        let clip = core
+            .video_source(info, Parallel, |_, frame, _| {
+                let mut props = frame.properties_mut().ok_or(c"no props")?;
+                props
+                    .insert(key!(c"Nan"), f64::NAN)
+                    .map_err(|_| c"failed to set")?;
+                props.set_empty(key!(c"Empty"), PropertyType::Float);
+                Ok::<_, &CStr>(())
+            })
+            .ok_or("failed to create source")?;
+
+        let node = clip
+            .modify_props(&core, |n, props| {
+                if n == 3 {
+                    return Err(c"frame 3 is broken");
+                }
+                if n == 1 {
+                    props
+                        .insert(key!(c"_Matrix"), 1)
+                        .map_err(|_| c"failed to set")?;
+                }
+                Ok(())
+            })
+            .ok_or("failed to create ModifyProps")?;
+
+        for (n, matrix) in [(0, None), (1, Some(1))] {
+            let frame = node
+                .get_frame(n)
+                .map_err(|e| e.to_string_lossy().into_owned())?;
+            let props = frame.properties().ok_or("no props")?;
+            assert!(props.get_as::<f64>(key!(c"Nan"))?.is_nan());
+            assert_eq!(props.get_type(key!(c"Empty")), PropertyType::Float);
+            assert_eq!(props.num_elements(key!(c"Empty")), Some(0));
+            assert_eq!(props.get_as::<i64>(key!(c"_Matrix")).ok(), matrix);
+        }
+        let err = node.get_frame(3).expect_err("frame 3 should fail");
+        assert!(err.to_string_lossy().contains("frame 3 is broken"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "link-vs")]
+    fn modify_props() -> TestResult {
+        let core = Core::builder().build();
+        let std = core
+            .get_plugin_by_namespace(c"std")
+            .ok_or("std not found")?;
+        let clip: VideoNode = std
+            .invoke(c"BlankClip", map!(core; "length" => 4)?)
+            .get_as(key!(c"clip"))?;
+
+        let node = clip
+            .modify_props(&core, |n, props| {
+                if n == 3 {
+                    return Err(c"frame 3 is broken");
+                }
+                if n % 2 == 0 {
+                    props
+                        .insert(key!(c"_Matrix"), 1)
+                        .map_err(|_| c"failed to set")?;
+                }
+                Ok(())
+            })
+            .ok_or("failed to create ModifyProps")?;
+
+        let get_frame = |n| {
+            node.get_frame(n)
+                .map_err(|e| e.to_string_lossy().into_owned())
+        };
+        let matrix = |frame: &crate::frame::VideoFrame| {
+            frame
+                .properties()
+                .and_then(|p| p.get_as::<i64>(key!(c"_Matrix")).ok())
+        };
+
+        assert_eq!(matrix(&get_frame(0)?), Some(1));
+        assert_eq!(matrix(&get_frame(1)?), None);
+        assert!(get_frame(3).unwrap_err().contains("frame 3 is broken"));
+
+        Ok(())
+    }
+}
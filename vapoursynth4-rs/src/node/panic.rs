@@ -0,0 +1,138 @@
+use std::{
+    backtrace::Backtrace,
+    cell::{Cell, RefCell},
+    ffi::{CStr, CString},
+    fmt::Display,
+    panic::{AssertUnwindSafe, PanicHookInfo},
+    sync::Once,
+};
+
+use crate::{core::CoreRef, ffi, utils::ToCString, utils::panic_message};
+
+/// What to do when a filter panics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PanicPolicy {
+    /// Report the panic as the filter error, or log it as critical when there is
+    /// nowhere to report it, e.g. in [`Filter::free`](super::Filter::free).
+    #[default]
+    Report,
+    /// Print the panic to stderr and abort the process.
+    Abort,
+    /// Log the panic as [`Fatal`](ffi::VSMessageType::Fatal) through `VapourSynth`,
+    /// which terminates the process.
+    LogFatal,
+}
+
+thread_local! {
+    static CAPTURE_BACKTRACE: Cell<bool> = const { Cell::new(false) };
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// Chains a panic hook that captures a backtrace while [`catch`] asks for one.
+fn install_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let prev = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info: &PanicHookInfo| {
+            if CAPTURE_BACKTRACE.get() {
+                BACKTRACE.set(Some(Backtrace::force_capture()));
+            }
+            prev(info);
+        }));
+    });
+}
+
+/// A caught panic.
+pub(crate) struct Panic {
+    message: CString,
+    backtrace: Option<Backtrace>,
+}
+
+impl Panic {
+    /// Formats the panic as `{prefix}: {message}`, followed by the backtrace if captured.
+    pub(crate) fn to_message(&self, prefix: impl Display) -> CString {
+        let mut msg = format!("{prefix}: {}", self.message.to_string_lossy());
+        if let Some(bt) = &self.backtrace {
+            msg = format!("{msg}\n{bt}");
+        }
+        msg.into_cstring_lossy()
+    }
+}
+
+/// Runs `f`, catching any panic and optionally its backtrace.
+pub(crate) fn catch<R>(backtrace: bool, f: impl FnOnce() -> R) -> Result<R, Panic> {
+    if backtrace {
+        install_hook();
+    }
+    // A panic caught inside `f` may leave its backtrace behind, which must not be
+    // attached to a later panic
+    BACKTRACE.take();
+    let prev = CAPTURE_BACKTRACE.replace(backtrace);
+    let res = std::panic::catch_unwind(AssertUnwindSafe(f));
+    CAPTURE_BACKTRACE.set(prev);
+    let backtrace = BACKTRACE.take();
+
+    res.map_err(|p| Panic {
+        message: panic_message(&*p),
+        backtrace,
+    })
+}
+
+/// Handles `msg` according to `policy`. Returns the message if it should be reported.
+pub(crate) fn apply_policy<'m>(
+    policy: PanicPolicy,
+    msg: &'m CStr,
+    mut core: CoreRef,
+) -> Option<&'m CStr> {
+    match policy {
+        PanicPolicy::Report => Some(msg),
+        PanicPolicy::Abort => {
+            eprintln!("{}", msg.to_string_lossy());
+            std::process::abort()
+        }
+        PanicPolicy::LogFatal => {
+            core.log(ffi::VSMessageType::Fatal, msg);
+            std::process::abort()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payloads() {
+        let msg = |f: fn()| catch(false, f).err().unwrap().to_message("Filter: frame 3");
+
+        assert_eq!(
+            msg(|| panic!("static")).as_ref(),
+            c"Filter: frame 3: static"
+        );
+        assert_eq!(
+            msg(|| panic!("formatted {}", 42)).as_ref(),
+            c"Filter: frame 3: formatted 42"
+        );
+        assert_eq!(
+            msg(|| std::panic::panic_any(42)).as_ref(),
+            c"Filter: frame 3: Unknown panic"
+        );
+
+        let p = catch(true, || panic!("oops")).err().unwrap();
+        assert!(p.backtrace.is_some());
+        assert!(catch(false, || 1).is_ok_and(|v| v == 1));
+    }
+
+    #[test]
+    fn stale_backtrace() {
+        // A panic caught by the filter itself captures a backtrace
+        let res = catch(true, || {
+            std::panic::catch_unwind(|| panic!("inner")).is_err()
+        });
+        assert!(res.is_ok_and(|caught| caught));
+
+        let p = catch(false, || panic!("outer")).err().unwrap();
+        assert!(p.backtrace.is_none());
+    }
+}
@@ -0,0 +1,258 @@
+use std::{ffi::CStr, panic::RefUnwindSafe};
+
+use crate::{
+    VideoInfo,
+    core::CoreRef,
+    frame::{FrameContext, VideoFrame, VideoFrameMut},
+    map::MapRef,
+    utils::is_constant_video_format,
+};
+
+use super::{
+    ActivationReason, Dependencies, Filter, FilterDependency, FrameStateSlot, Instance, Node,
+    RequestPattern, VideoNode, mode::Parallel,
+};
+
+/// A filter that produces frame `n` from frame `n` of a single input clip.
+///
+/// Wrap it in [`Simple`] to get a [`Filter`], which requests the input frame,
+/// declares a [`StrictSpatial`](RequestPattern::StrictSpatial) dependency and allocates
+/// the output frame with the properties copied from the input frame.
+///
+/// ```ignore
+/// declare_plugin!(c"com.example.invert", c"invert", c"Invert", (1, 0),
+///     VAPOURSYNTH_API_VERSION, 0, (Simple<Invert>, None));
+/// ```
+pub trait SimpleVideoFilter: Sized + Send + Sync + RefUnwindSafe {
+    /// Filter error that can turned into a [`&CStr`](std::ffi::CStr)
+    type Error: AsRef<CStr>;
+
+    const NAME: &'static CStr;
+    /// Name of the created node, [`Self::NAME`] by default.
+    const NODE_NAME: &'static CStr = Self::NAME;
+    /// Arguments of the filter, which should contain the input clip.
+    const ARGS: &'static CStr;
+
+    /// Creates the filter from its arguments.
+    ///
+    /// # Errors
+    ///
+    /// Return [`Self::Error`] if the arguments are invalid.
+    /// The error message will be passed to `VapourSynth`.
+    fn create(args: MapRef, core: CoreRef) -> Result<Self, Self::Error>;
+
+    /// The input clip.
+    fn input(&self) -> &VideoNode;
+
+    /// The output clip info. It must have the same number of frames as `input`.
+    ///
+    /// # Errors
+    ///
+    /// Return [`Self::Error`] if the input clip is not supported.
+    fn output_info(&self, input: &VideoInfo) -> Result<VideoInfo, Self::Error> {
+        Ok(input.clone())
+    }
+
+    /// Renders `dst` from `src`, frame `n` of the input clip.
+    ///
+    /// # Errors
+    ///
+    /// Return [`Self::Error`] if anything happens during the processing.
+    fn process(
+        &self,
+        n: i32,
+        src: &VideoFrame,
+        dst: &mut VideoFrameMut,
+        core: CoreRef,
+    ) -> Result<(), Self::Error>;
+}
+
+/// Adapter from [`SimpleVideoFilter`] to [`Filter`].
+pub struct Simple<T> {
+    inner: T,
+    info: VideoInfo,
+}
+
+impl<T> Simple<T> {
+    #[must_use]
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: SimpleVideoFilter> Filter for Simple<T> {
+    type Mode = Parallel;
+    type Error = T::Error;
+    type FrameType = VideoFrame;
+    type FilterData = ();
+    type FrameState = ();
+
+    const NAME: &'static CStr = T::NAME;
+    const ARGS: &'static CStr = T::ARGS;
+    const RETURN_TYPE: &'static CStr = c"clip:vnode;";
+
+    fn create(
+        input: MapRef,
+        output: MapRef,
+        _data: Option<Box<Self::FilterData>>,
+        mut core: CoreRef,
+    ) -> Result<(), Self::Error> {
+        let inner = T::create(input, core)?;
+        let info = inner.output_info(inner.input().info())?;
+        let deps = [FilterDependency {
+            source: inner.input().as_ptr(),
+            request_pattern: RequestPattern::StrictSpatial,
+        }];
+
+        core.create_video_filter(
+            output,
+            T::NODE_NAME,
+            &info.clone(),
+            Box::new(Simple { inner, info }),
+            Dependencies::new(&deps).unwrap(),
+        );
+
+        Ok(())
+    }
+
+    fn get_frame(
+        this: Instance<'_, Self>,
+        n: i32,
+        activation_reason: ActivationReason,
+        _frame_state: FrameStateSlot<'_, ()>,
+        mut ctx: FrameContext,
+        core: CoreRef,
+    ) -> Result<Option<VideoFrame>, Self::Error> {
+        match activation_reason {
+            ActivationReason::Initial => {
+                ctx.request_frame_filter(n, this.inner.input());
+                Ok(None)
+            }
+            ActivationReason::AllFramesReady => {
+                let src = this.inner.input().get_frame_filter(n, &mut ctx);
+                // Variable output follows the input frame
+                let (format, width, height) = if is_constant_video_format(&this.info) {
+                    (&this.info.format, this.info.width, this.info.height)
+                } else {
+                    (
+                        src.get_video_format(),
+                        src.frame_width(0),
+                        src.frame_height(0),
+                    )
+                };
+                let mut dst =
+                    VideoFrameMut::new(core.new_video_frame(format, width, height, Some(&src)));
+                this.inner.process(n, &src, &mut dst, core)?;
+
+                Ok(Some(dst.into_frame()))
+            }
+            ActivationReason::Error => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use testresult::TestResult;
+
+    use super::*;
+    use crate::{ColorFamily, SampleType, core::Core, key, map, mock};
+
+    /// Adds one to the first sample, and fails in `process` for frame 2.
+    struct AddOne {
+        input: VideoNode,
+    }
+
+    impl SimpleVideoFilter for AddOne {
+        type Error = &'static CStr;
+
+        const NAME: &'static CStr = c"AddOne";
+        const NODE_NAME: &'static CStr = c"Add";
+        const ARGS: &'static CStr = c"clip:vnode;";
+
+        fn create(args: MapRef, _core: CoreRef) -> Result<Self, Self::Error> {
+            let input = args
+                .get_video_node(key!(c"clip"), 0)
+                .map_err(|_| c"AddOne: clip is required")?;
+            Ok(Self { input })
+        }
+
+        fn input(&self) -> &VideoNode {
+            &self.input
+        }
+
+        fn process(
+            &self,
+            n: i32,
+            src: &VideoFrame,
+            dst: &mut VideoFrameMut,
+            _core: CoreRef,
+        ) -> Result<(), Self::Error> {
+            if n == 2 {
+                return Err(c"AddOne: frame 2 is refused");
+            }
+            // SAFETY: The frames have at least one sample
+            unsafe { dst.plane_mut(0).write(src.plane(0).read() + 1) };
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn simple() -> TestResult {
+        let core = Core::builder().api(mock::api()).build();
+        let format = core.query_video_format(ColorFamily::Gray, SampleType::Integer, 8, 0, 0);
+        let info = VideoInfo {
+            format,
+            fps_num: 30,
+            fps_den: 1,
+            width: 4,
+            height: 2,
+            num_frames: 4,
+        };
+        let source = core
+            .video_source(info, Parallel, |n, frame, _| {
+                if n == 3 {
+                    return Err(c"frame 3 is broken");
+                }
+                let value = u8::try_from(n).map_err(|_| c"out of range")?;
+                // SAFETY: The frame has at least one sample
+                unsafe { frame.plane_mut(0).write(value) };
+                Ok(())
+            })
+            .ok_or("failed to create source")?;
+
+        let args = map!(core; "clip" => source)?;
+        let mut out = core.create_map();
+        unsafe {
+            <Simple<AddOne> as Filter>::create(
+                MapRef::from_ptr(args.as_ptr(), core.api()),
+                MapRef::from_ptr(out.as_ptr(), core.api()),
+                None,
+                CoreRef::from_ptr(core.as_ptr(), core.api()),
+            )
+            .map_err(CStr::to_string_lossy)?;
+        }
+        let clip = out.get_video_node(key!(c"clip"), 0)?;
+        out.clear();
+
+        // SAFETY: The node is valid
+        let name = unsafe { CStr::from_ptr((core.api().getNodeName)(clip.as_ptr())) };
+        assert_eq!(name, c"Add");
+
+        let get_frame = |n| {
+            clip.get_frame(n)
+                // SAFETY: The frame has at least one sample
+                .map(|frame| unsafe { *frame.plane(0) })
+                .map_err(|e| e.to_string_lossy().into_owned())
+        };
+        assert_eq!(get_frame(0)?, 1);
+        assert_eq!(get_frame(1)?, 2);
+
+        let err = get_frame(2).expect_err("process should fail");
+        assert!(err.contains("AddOne: frame 2 is refused"), "{err}");
+        let err = get_frame(3).expect_err("the source should fail");
+        assert!(err.contains("frame 3 is broken"), "{err}");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    ffi::{CStr, CString},
+    marker::PhantomData,
+    panic::RefUnwindSafe,
+};
+
+use crate::{
+    AudioInfo, VideoInfo,
+    core::{Core, CoreRef},
+    ffi,
+    frame::{AudioFrame, AudioFrameMut, Frame, FrameContext, VideoFrame, VideoFrameMut},
+    key,
+    map::MapRef,
+    utils::{ToCString, is_constant_video_format},
+};
+
+use super::{ActivationReason, AudioNode, Filter, FrameStateSlot, Instance, VideoNode, mode::Mode};
+
+impl Core {
+    /// Creates a video node without dependencies whose frames are rendered by `render`.
+    ///
+    /// `_mode` is one of the markers in [`mode`](super::mode). Only `Parallel` and
+    /// `ParallelRequests` call `render` concurrently, which requires it to be [`Sync`].
+    /// Frames are allocated with the format and size of `info`, and `_DurationNum`
+    /// and `_DurationDen` set from its frame rate if it is constant.
+    ///
+    /// Returns `None` if `info` does not have a constant format and size,
+    /// or the filter could not be created.
+    pub fn video_source<M, F, E>(&self, info: VideoInfo, _mode: M, render: F) -> Option<VideoNode>
+    where
+        M: Mode<VideoSource<M, F, E>> + Send + RefUnwindSafe + 'static,
+        F: Fn(i32, &mut VideoFrameMut, CoreRef) -> Result<(), E> + Send + RefUnwindSafe + 'static,
+        E: AsRef<CStr>,
+    {
+        if !is_constant_video_format(&info) {
+            return None;
+        }
+
+        let filter = VideoSource::<M, F, E> {
+            info,
+            render,
+            _marker: PhantomData,
+        };
+        VideoNode::new("VideoSource", &filter.info.clone(), filter, &[], self)
+    }
+
+    /// Creates an audio node without dependencies whose frames are rendered by `render`.
+    ///
+    /// See [`Core::video_source`] for `_mode`. Every frame has
+    /// [`VS_AUDIO_FRAME_SAMPLES`](ffi::VS_AUDIO_FRAME_SAMPLES) samples except the last one,
+    /// which has the rest of `info.num_samples`.
+    ///
+    /// Returns `None` if the filter could not be created.
+    pub fn audio_source<M, F, E>(&self, info: AudioInfo, _mode: M, render: F) -> Option<AudioNode>
+    where
+        M: Mode<AudioSource<M, F, E>> + Send + RefUnwindSafe + 'static,
+        F: Fn(i32, &mut AudioFrameMut, CoreRef) -> Result<(), E> + Send + RefUnwindSafe + 'static,
+        E: AsRef<CStr>,
+    {
+        let filter = AudioSource::<M, F, E> {
+            info,
+            render,
+            _marker: PhantomData,
+        };
+        AudioNode::new("AudioSource", &filter.info.clone(), filter, &[], self)
+    }
+}
+
+/// The filter behind [`Core::video_source`].
+pub struct VideoSource<M, F, E> {
+    info: VideoInfo,
+    render: F,
+    _marker: PhantomData<fn() -> (M, E)>,
+}
+
+impl<M, F, E> Filter for VideoSource<M, F, E>
+where
+    M: Mode<Self> + Send + RefUnwindSafe + 'static,
+    F: Fn(i32, &mut VideoFrameMut, CoreRef) -> Result<(), E> + Send + RefUnwindSafe + 'static,
+    E: AsRef<CStr>,
+{
+    type Mode = M;
+    type Error = CString;
+    type FrameType = VideoFrame;
+    type FilterData = ();
+    type FrameState = ();
+
+    const NAME: &'static CStr = c"VideoSource";
+    const ARGS: &'static CStr = c"";
+    const RETURN_TYPE: &'static CStr = c"";
+
+    fn create(
+        _input: MapRef,
+        _output: MapRef,
+        _data: Option<Box<Self::FilterData>>,
+        _core: CoreRef,
+    ) -> Result<(), Self::Error> {
+        Err(c"VideoSource can only be created with Core::video_source".into())
+    }
+
+    fn get_frame(
+        this: Instance<'_, Self>,
+        n: i32,
+        activation_reason: ActivationReason,
+        _frame_state: FrameStateSlot<'_, ()>,
+        _ctx: FrameContext,
+        core: CoreRef,
+    ) -> Result<Option<VideoFrame>, Self::Error> {
+        if activation_reason != ActivationReason::Initial {
+            return Ok(None);
+        }
+
+        let info = &this.info;
+        let mut frame =
+            VideoFrameMut::new(core.new_video_frame(&info.format, info.width, info.height, None));
+        if info.fps_num > 0 && info.fps_den > 0 {
+            let Some(mut props) = frame.properties_mut() else {
+                return Err(c"VideoSource: failed to get frame properties".into());
+            };
+            props
+                .insert(key!(c"_DurationNum"), info.fps_den)
+                .and_then(|()| props.insert(key!(c"_DurationDen"), info.fps_num))
+                .map_err(|e| e.to_string().into_cstring_lossy())?;
+        }
+        (this.render)(n, &mut frame, core).map_err(|e| e.as_ref().to_owned())?;
+
+        Ok(Some(frame.into_frame()))
+    }
+}
+
+/// The filter behind [`Core::audio_source`].
+pub struct AudioSource<M, F, E> {
+    info: AudioInfo,
+    render: F,
+    _marker: PhantomData<fn() -> (M, E)>,
+}
+
+impl<M, F, E> Filter for AudioSource<M, F, E>
+where
+    M: Mode<Self> + Send + RefUnwindSafe + 'static,
+    F: Fn(i32, &mut AudioFrameMut, CoreRef) -> Result<(), E> + Send + RefUnwindSafe + 'static,
+    E: AsRef<CStr>,
+{
+    type Mode = M;
+    type Error = CString;
+    type FrameType = AudioFrame;
+    type FilterData = ();
+    type FrameState = ();
+
+    const NAME: &'static CStr = c"AudioSource";
+    const ARGS: &'static CStr = c"";
+    const RETURN_TYPE: &'static CStr = c"";
+
+    fn create(
+        _input: MapRef,
+        _output: MapRef,
+        _data: Option<Box<Self::FilterData>>,
+        _core: CoreRef,
+    ) -> Result<(), Self::Error> {
+        Err(c"AudioSource can only be created with Core::audio_source".into())
+    }
+
+    fn get_frame(
+        this: Instance<'_, Self>,
+        n: i32,
+        activation_reason: ActivationReason,
+        _frame_state: FrameStateSlot<'_, ()>,
+        _ctx: FrameContext,
+        core: CoreRef,
+    ) -> Result<Option<AudioFrame>, Self::Error> {
+        if activation_reason != ActivationReason::Initial {
+            return Ok(None);
+        }
+
+        let frame_samples = i64::from(ffi::VS_AUDIO_FRAME_SAMPLES);
+        let remaining = this.info.num_samples - i64::from(n) * frame_samples;
+        // Bounded by `VS_AUDIO_FRAME_SAMPLES`
+        #[allow(clippy::cast_possible_truncation)]
+        let num_samples = remaining.clamp(0, frame_samples) as i32;
+
+        let mut frame =
+            AudioFrameMut::new(core.new_audio_frame(&this.info.format, num_samples, None));
+        (this.render)(n, &mut frame, core).map_err(|e| e.as_ref().to_owned())?;
+
+        Ok(Some(frame.into_frame()))
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "link-vs")]
+mod tests {
+    use testresult::TestResult;
+
+    use super::*;
+    use crate::{
+        ColorFamily, SampleType,
+        node::{Node, mode::Parallel},
+    };
+
+    #[test]
+    fn sources() -> TestResult {
+        let core = Core::builder().build();
+
+        let format = core.query_video_format(ColorFamily::Gray, SampleType::Integer, 8, 0, 0);
+        let info = VideoInfo {
+            format,
+            fps_num: 24,
+            fps_den: 1,
+            width: 16,
+            height: 16,
+            num_frames: 10,
+        };
+        let node = core
+            .video_source(info, Parallel, |n, frame, _| {
+                let value = u8::try_from(n).map_err(|_| c"out of range")?;
+                unsafe { frame.plane_mut(0).write(value) };
+                Ok::<_, &CStr>(())
+            })
+            .ok_or("failed to create video source")?;
+        let frame = node
+            .get_frame(7)
+            .map_err(|e| e.to_string_lossy().into_owned())?;
+        assert_eq!(unsafe { *frame.plane(0) }, 7);
+        let props = frame.properties().ok_or("no props")?;
+        assert_eq!(props.get_as::<i64>(key!(c"_DurationDen"))?, 24);
+
+        let info = AudioInfo {
+            format: core.query_audio_format(
+                SampleType::Float,
+                32,
+                1 << ffi::VSAudioChannels::FrontLeft as u64,
+            ),
+            sample_rate: 48000,
+            num_samples: i64::from(ffi::VS_AUDIO_FRAME_SAMPLES) + 100,
+            num_frames: 0,
+        };
+        let node = core
+            .audio_source(info, Parallel, |_, _, _| Ok::<_, &CStr>(()))
+            .ok_or("failed to create audio source")?;
+        assert_eq!(node.info().num_frames, 2);
+        let frame = node
+            .get_frame(1)
+            .map_err(|e| e.to_string_lossy().into_owned())?;
+        assert_eq!(frame.frame_length(), 100);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use std::{ffi::c_void, marker::PhantomData};
+
+/// Per-frame state of a filter, kept across the activations of a single frame request.
+///
+/// The state is stored boxed in the `frameData` pointer of `VapourSynth` and dropped
+/// automatically once the request returns a frame, returns an error or panics,
+/// or is activated with [`ActivationReason::Error`](super::ActivationReason::Error).
+pub struct FrameStateSlot<'a, T> {
+    ptr: *mut *mut c_void,
+    _marker: PhantomData<&'a mut Option<Box<T>>>,
+}
+
+impl<T> FrameStateSlot<'_, T> {
+    /// # Safety
+    ///
+    /// The caller must ensure that `ptr` is the `frameData` of a frame request,
+    /// which is either null or set by a slot of the same `T`.
+    pub(crate) unsafe fn from_ptr(ptr: *mut *mut c_void) -> Self {
+        Self {
+            ptr,
+            _marker: PhantomData,
+        }
+    }
+
+    fn raw(&self) -> *mut T {
+        unsafe { (*self.ptr).cast() }
+    }
+
+    #[must_use]
+    pub fn is_some(&self) -> bool {
+        !self.raw().is_null()
+    }
+
+    #[must_use]
+    pub fn get(&self) -> Option<&T> {
+        unsafe { self.raw().as_ref() }
+    }
+
+    #[must_use]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.raw().as_mut() }
+    }
+
+    /// Stores `val`, dropping the previous state if any.
+    pub fn insert(&mut self, val: T) -> &mut T {
+        drop(self.take());
+        let raw = Box::into_raw(Box::new(val));
+        unsafe {
+            *self.ptr = raw.cast();
+            &mut *raw
+        }
+    }
+
+    /// Removes the state, leaving the slot empty.
+    pub fn take(&mut self) -> Option<T> {
+        let raw = self.raw();
+        if raw.is_null() {
+            None
+        } else {
+            unsafe {
+                *self.ptr = std::ptr::null_mut();
+                Some(*Box::from_raw(raw))
+            }
+        }
+    }
+
+    pub fn get_or_insert_with(&mut self, f: impl FnOnce() -> T) -> &mut T {
+        if self.is_some() {
+            unsafe { &mut *self.raw() }
+        } else {
+            self.insert(f())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::null_mut;
+
+    use super::*;
+
+    #[test]
+    fn slot() {
+        let mut data = null_mut();
+        let mut slot = unsafe { FrameStateSlot::<Vec<i32>>::from_ptr(&raw mut data) };
+        assert!(slot.get().is_none());
+
+        slot.get_or_insert_with(Vec::new).push(1);
+        slot.get_or_insert_with(Vec::new).push(2);
+        assert_eq!(slot.get(), Some(&vec![1, 2]));
+
+        slot.insert(vec![3]);
+        slot.get_mut().unwrap().push(4);
+        assert_eq!(slot.take(), Some(vec![3, 4]));
+        assert!(!slot.is_some());
+        assert!(data.is_null());
+    }
+}
@@ -0,0 +1,405 @@
+use std::{ffi::CStr, ops::Deref, panic::RefUnwindSafe};
+
+use crate::{
+    VideoInfo,
+    core::CoreRef,
+    frame::{FrameContext, VideoFrame, VideoFrameMut},
+    map::MapRef,
+    utils::is_constant_video_format,
+};
+
+use super::{
+    ActivationReason, Dependencies, Filter, FilterDependency, FrameStateSlot, Instance, Node,
+    RequestPattern, VideoNode, mode::Parallel,
+};
+
+/// How frame indices outside of a clip are resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EdgeMode {
+    /// Repeat the first or the last frame, e.g. `-2, -1` becomes `0, 0`.
+    #[default]
+    Clamp,
+    /// Reflect at the first or the last frame without repeating it, e.g. `-2, -1` becomes `2, 1`.
+    Mirror,
+}
+
+impl EdgeMode {
+    /// Resolves frame `n` into `0..num_frames`.
+    #[must_use]
+    pub fn resolve(self, n: i32, num_frames: i32) -> i32 {
+        let last = num_frames - 1;
+        if last <= 0 {
+            return 0;
+        }
+
+        match self {
+            EdgeMode::Clamp => n.clamp(0, last),
+            EdgeMode::Mirror => {
+                let n = n.rem_euclid(2 * last);
+                if n > last { 2 * last - n } else { n }
+            }
+        }
+    }
+}
+
+/// The frames `n - radius..=n + radius` of an input clip, with edges resolved by [`EdgeMode`].
+#[derive(Debug)]
+pub struct Window {
+    frames: Vec<VideoFrame>,
+    radius: u32,
+}
+
+impl Window {
+    #[must_use]
+    pub fn radius(&self) -> u32 {
+        self.radius
+    }
+
+    /// The frame at `n`.
+    #[must_use]
+    pub fn center(&self) -> &VideoFrame {
+        &self.frames[self.frames.len() / 2]
+    }
+
+    /// The frame at `n + offset`, or `None` if `offset` is outside of the radius.
+    #[must_use]
+    pub fn offset(&self, offset: i32) -> Option<&VideoFrame> {
+        usize::try_from(i64::from(self.radius) + i64::from(offset))
+            .ok()
+            .and_then(|i| self.frames.get(i))
+    }
+}
+
+impl Deref for Window {
+    type Target = [VideoFrame];
+
+    fn deref(&self) -> &Self::Target {
+        &self.frames
+    }
+}
+
+/// A filter that produces frame `n` from the frames `n - radius..=n + radius`
+/// of one or more input clips.
+///
+/// Wrap it in [`Temporal`] to get a [`Filter`], which requests the windows,
+/// declares [`General`](RequestPattern::General) dependencies and allocates the output frame
+/// with the properties copied from the center frame of the first input.
+/// Inputs shorter than the first one are resolved with their own length.
+pub trait TemporalFilter: Sized + Send + Sync + RefUnwindSafe {
+    /// Filter error that can turned into a [`&CStr`](std::ffi::CStr)
+    type Error: AsRef<CStr>;
+
+    const NAME: &'static CStr;
+    /// Arguments of the filter, which should contain the input clips.
+    const ARGS: &'static CStr;
+    const EDGE_MODE: EdgeMode = EdgeMode::Clamp;
+
+    /// Creates the filter from its arguments.
+    ///
+    /// # Errors
+    ///
+    /// Return [`Self::Error`] if the arguments are invalid.
+    /// The error message will be passed to `VapourSynth`.
+    fn create(args: MapRef, core: CoreRef) -> Result<Self, Self::Error>;
+
+    /// The input clips. The first one determines the output clip. There must be at least one.
+    fn inputs(&self) -> &[VideoNode];
+
+    /// The number of frames before and after frame `n` in the windows.
+    fn radius(&self) -> u32;
+
+    /// The output clip info. It must have the same number of frames as the first input.
+    ///
+    /// # Errors
+    ///
+    /// Return [`Self::Error`] if the input clips are not supported.
+    fn output_info(&self, input: &VideoInfo) -> Result<VideoInfo, Self::Error> {
+        Ok(input.clone())
+    }
+
+    /// Renders `dst` from `windows`, one for each of the [`inputs`](Self::inputs).
+    ///
+    /// # Errors
+    ///
+    /// Return [`Self::Error`] if anything happens during the processing.
+    fn process(
+        &self,
+        n: i32,
+        windows: &[Window],
+        dst: &mut VideoFrameMut,
+        core: CoreRef,
+    ) -> Result<(), Self::Error>;
+}
+
+/// Adapter from [`TemporalFilter`] to [`Filter`].
+pub struct Temporal<T> {
+    inner: T,
+    info: VideoInfo,
+}
+
+impl<T> Temporal<T> {
+    #[must_use]
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: TemporalFilter> Temporal<T> {
+    fn window(&self, n: i32, node: &VideoNode) -> impl Iterator<Item = i32> {
+        let radius = i32::try_from(self.inner.radius()).unwrap_or(i32::MAX);
+        let num_frames = node.info().num_frames;
+        (n.saturating_sub(radius)..=n.saturating_add(radius))
+            .map(move |i| T::EDGE_MODE.resolve(i, num_frames))
+    }
+}
+
+impl<T: TemporalFilter> Filter for Temporal<T> {
+    type Mode = Parallel;
+    type Error = T::Error;
+    type FrameType = VideoFrame;
+    type FilterData = ();
+    type FrameState = ();
+
+    const NAME: &'static CStr = T::NAME;
+    const ARGS: &'static CStr = T::ARGS;
+    const RETURN_TYPE: &'static CStr = c"clip:vnode;";
+
+    fn create(
+        input: MapRef,
+        output: MapRef,
+        _data: Option<Box<Self::FilterData>>,
+        mut core: CoreRef,
+    ) -> Result<(), Self::Error> {
+        let inner = T::create(input, core)?;
+        let info = inner.output_info(inner.inputs()[0].info())?;
+        let deps: Vec<_> = inner
+            .inputs()
+            .iter()
+            .map(|node| FilterDependency {
+                source: node.as_ptr(),
+                request_pattern: RequestPattern::General,
+            })
+            .collect();
+
+        core.create_video_filter(
+            output,
+            T::NAME,
+            &info.clone(),
+            Box::new(Temporal { inner, info }),
+            Dependencies::new(&deps).unwrap(),
+        );
+
+        Ok(())
+    }
+
+    fn get_frame(
+        this: Instance<'_, Self>,
+        n: i32,
+        activation_reason: ActivationReason,
+        _frame_state: FrameStateSlot<'_, ()>,
+        mut ctx: FrameContext,
+        core: CoreRef,
+    ) -> Result<Option<VideoFrame>, Self::Error> {
+        match activation_reason {
+            ActivationReason::Initial => {
+                for node in this.inner.inputs() {
+                    let mut requested = Vec::new();
+                    for i in this.window(n, node) {
+                        if !requested.contains(&i) {
+                            ctx.request_frame_filter(i, node);
+                            requested.push(i);
+                        }
+                    }
+                }
+                Ok(None)
+            }
+            ActivationReason::AllFramesReady => {
+                let windows: Vec<_> = this
+                    .inner
+                    .inputs()
+                    .iter()
+                    .map(|node| Window {
+                        frames: this
+                            .window(n, node)
+                            .map(|i| node.get_frame_filter(i, &mut ctx))
+                            .collect(),
+                        radius: this.inner.radius(),
+                    })
+                    .collect();
+
+                let src = windows[0].center();
+                // Variable output follows the input frame
+                let (format, width, height) = if is_constant_video_format(&this.info) {
+                    (&this.info.format, this.info.width, this.info.height)
+                } else {
+                    (
+                        src.get_video_format(),
+                        src.frame_width(0),
+                        src.frame_height(0),
+                    )
+                };
+                let mut dst =
+                    VideoFrameMut::new(core.new_video_frame(format, width, height, Some(src)));
+                this.inner.process(n, &windows, &mut dst, core)?;
+
+                Ok(Some(dst.into_frame()))
+            }
+            ActivationReason::Error => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use testresult::TestResult;
+
+    use super::*;
+    use crate::{ColorFamily, SampleType, core::Core, key, map, mock};
+
+    #[test]
+    fn edges() {
+        let resolve = |mode: EdgeMode, len| (-3..len + 3).map(move |n| mode.resolve(n, len));
+
+        assert!(resolve(EdgeMode::Clamp, 4).eq([0, 0, 0, 0, 1, 2, 3, 3, 3, 3]));
+        assert!(resolve(EdgeMode::Mirror, 4).eq([3, 2, 1, 0, 1, 2, 3, 2, 1, 0]));
+        assert!(resolve(EdgeMode::Mirror, 2).eq([1, 0, 1, 0, 1, 0, 1, 0]));
+        assert!(resolve(EdgeMode::Mirror, 1).eq([0; 7]));
+    }
+
+    /// Writes the first sample of every frame in the windows to a row of the output,
+    /// one row for each input.
+    struct Indices<const MIRROR: bool> {
+        inputs: [VideoNode; 2],
+    }
+
+    impl<const MIRROR: bool> TemporalFilter for Indices<MIRROR> {
+        type Error = &'static CStr;
+
+        const NAME: &'static CStr = c"Indices";
+        const ARGS: &'static CStr = c"clip:vnode;other:vnode;";
+        const EDGE_MODE: EdgeMode = if MIRROR {
+            EdgeMode::Mirror
+        } else {
+            EdgeMode::Clamp
+        };
+
+        fn create(args: MapRef, _core: CoreRef) -> Result<Self, Self::Error> {
+            let get = |key| {
+                args.get_video_node(key, 0)
+                    .map_err(|_| c"Indices: missing clip")
+            };
+            Ok(Self {
+                inputs: [get(key!(c"clip"))?, get(key!(c"other"))?],
+            })
+        }
+
+        fn inputs(&self) -> &[VideoNode] {
+            &self.inputs
+        }
+
+        fn radius(&self) -> u32 {
+            2
+        }
+
+        fn process(
+            &self,
+            _n: i32,
+            windows: &[Window],
+            dst: &mut VideoFrameMut,
+            _core: CoreRef,
+        ) -> Result<(), Self::Error> {
+            for (y, window) in (0..).zip(windows) {
+                let radius = i32::try_from(window.radius()).map_err(|_| c"Indices: radius")?;
+                if window.offset(-radius) != window.first()
+                    || window.offset(0) != Some(window.center())
+                    || window.offset(radius + 1).is_some()
+                {
+                    return Err(c"Indices: wrong offsets");
+                }
+                let row = dst.plane_mut(0).wrapping_offset(y * dst.stride(0));
+                for (x, frame) in window.iter().enumerate() {
+                    // SAFETY: The frames have at least one sample, and the row holds 8 samples
+                    unsafe { row.add(x).write(*frame.plane(0)) };
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// A clip of `num_frames` with `base + n` as the first sample of frame `n`.
+    fn source(core: &Core, num_frames: i32, base: u8) -> TestResult<VideoNode> {
+        let format = core.query_video_format(ColorFamily::Gray, SampleType::Integer, 8, 0, 0);
+        let info = VideoInfo {
+            format,
+            fps_num: 30,
+            fps_den: 1,
+            width: 8,
+            height: 2,
+            num_frames,
+        };
+        let node = core.video_source(info, Parallel, move |n, frame, _| {
+            let value = u8::try_from(n).map_err(|_| c"out of range")? + base;
+            // SAFETY: The frame has at least one sample
+            unsafe { frame.plane_mut(0).write(value) };
+            Ok::<_, &CStr>(())
+        });
+        Ok(node.ok_or("failed to create source")?)
+    }
+
+    /// Renders frame `n` of `Temporal<Indices<MIRROR>>` over a clip of 6 frames
+    /// and one of 3 frames.
+    fn windows<const MIRROR: bool>(core: &Core, n: i32) -> TestResult<[[u8; 5]; 2]> {
+        let args = map!(core; "clip" => source(core, 6, 0)?, "other" => source(core, 3, 10)?)?;
+        let mut out = core.create_map();
+        unsafe {
+            <Temporal<Indices<MIRROR>> as Filter>::create(
+                MapRef::from_ptr(args.as_ptr(), core.api()),
+                MapRef::from_ptr(out.as_ptr(), core.api()),
+                None,
+                CoreRef::from_ptr(core.as_ptr(), core.api()),
+            )
+            .map_err(CStr::to_string_lossy)?;
+        }
+        let clip = out.get_video_node(key!(c"clip"), 0)?;
+        out.clear();
+
+        let frame = clip
+            .get_frame(n)
+            .map_err(|e| e.to_string_lossy().into_owned())?;
+        let row = |y: isize| {
+            let ptr = frame.plane(0).wrapping_offset(y * frame.stride(0));
+            // SAFETY: Each row holds 8 samples
+            std::array::from_fn(|x| unsafe { *ptr.add(x) })
+        };
+        Ok([row(0), row(1)])
+    }
+
+    #[test]
+    fn temporal() -> TestResult {
+        let core = Core::builder().api(mock::api()).build();
+
+        assert_eq!(
+            windows::<false>(&core, 2)?,
+            [[0, 1, 2, 3, 4], [10, 11, 12, 12, 12]]
+        );
+        assert_eq!(
+            windows::<false>(&core, 0)?,
+            [[0, 0, 0, 1, 2], [10, 10, 10, 11, 12]]
+        );
+        assert_eq!(
+            windows::<false>(&core, 5)?,
+            [[3, 4, 5, 5, 5], [12, 12, 12, 12, 12]]
+        );
+
+        assert_eq!(
+            windows::<true>(&core, 0)?,
+            [[2, 1, 0, 1, 2], [12, 11, 10, 11, 12]]
+        );
+        assert_eq!(
+            windows::<true>(&core, 5)?,
+            [[3, 4, 5, 4, 3], [11, 10, 11, 12, 11]]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Writers dumping the frames of nodes to files in pure Rust.
+
+use thiserror::Error;
+
+mod images;
+
+pub use images::ImageFormat;
+
+#[derive(Debug, Error)]
+pub enum OutputError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Invalid file name pattern: {0}")]
+    InvalidPattern(String),
+    #[error("Unsupported format: {0}")]
+    Unsupported(String),
+    #[error("Failed to get frame {n}: {message}")]
+    Frame { n: i32, message: String },
+    #[cfg(feature = "png")]
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+}
@@ -0,0 +1,380 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    ops::{Bound, RangeBounds},
+    path::PathBuf,
+};
+
+use crate::{
+    ffi,
+    frame::VideoFrame,
+    node::{Node, VideoNode},
+};
+
+use super::OutputError;
+
+/// File format of the images written by [`VideoNode::write_images`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Binary PGM for gray clips and PPM for `RGB` clips.
+    #[default]
+    Pnm,
+    /// PNG, with samples of more than 8 bits scaled to 16 bits.
+    #[cfg(feature = "png")]
+    Png,
+}
+
+impl VideoNode {
+    /// Writes the frames in `range` to numbered image files.
+    ///
+    /// `pattern` is a path with one printf-style integer conversion such as `%d` or `%05d`,
+    /// which is replaced by the frame number. `%%` is a literal `%`. The range is clamped
+    /// to the length of the clip.
+    ///
+    /// `GRAY` clips are written as single-channel images and planar `RGB` clips as
+    /// interleaved pixels. Integer formats of 8 to 16 bits are supported.
+    ///
+    /// # Errors
+    ///
+    /// Return [`OutputError`] if `pattern` is invalid, a frame has an unsupported format
+    /// or can not be rendered, or a file can not be written.
+    pub fn write_images(
+        &self,
+        pattern: &str,
+        range: impl RangeBounds<i32>,
+        format: ImageFormat,
+    ) -> Result<(), OutputError> {
+        let pattern = Pattern::parse(pattern)?;
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let num_frames = self.info().num_frames;
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n.saturating_add(1),
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => num_frames,
+        };
+
+        for n in start.max(0)..end.min(num_frames) {
+            let frame = self.get_frame(n).map_err(|e| OutputError::Frame {
+                n,
+                message: e.to_string_lossy().into_owned(),
+            })?;
+            let raster = Raster::from_frame(&frame)?;
+            let path = pattern.format(n);
+            match format {
+                ImageFormat::Pnm => {
+                    let mut file = BufWriter::new(File::create(path)?);
+                    raster.write_pnm(&mut file)?;
+                    file.flush()?;
+                }
+                #[cfg(feature = "png")]
+                ImageFormat::Png => raster.write_png(path)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A file name pattern with one integer conversion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern {
+    prefix: String,
+    suffix: String,
+    width: usize,
+    zero_pad: bool,
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Result<Self, OutputError> {
+        let invalid = |msg: &str| OutputError::InvalidPattern(format!("{msg} in `{pattern}`"));
+
+        let mut parts = [String::new(), String::new()];
+        let mut conversion = None;
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                parts[usize::from(conversion.is_some())].push(c);
+                continue;
+            }
+            if chars.next_if_eq(&'%').is_some() {
+                parts[usize::from(conversion.is_some())].push('%');
+                continue;
+            }
+            if conversion.is_some() {
+                return Err(invalid("more than one conversion"));
+            }
+
+            let zero_pad = chars.next_if_eq(&'0').is_some();
+            let mut width = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                width.push(digit);
+            }
+            if chars.next_if(|&c| c == 'd' || c == 'i').is_none() {
+                return Err(invalid("unsupported conversion"));
+            }
+            let width = if width.is_empty() {
+                0
+            } else {
+                width.parse().map_err(|_| invalid("invalid width"))?
+            };
+            conversion = Some((width, zero_pad));
+        }
+
+        let (width, zero_pad) = conversion.ok_or_else(|| invalid("missing conversion"))?;
+        let [prefix, suffix] = parts;
+        Ok(Self {
+            prefix,
+            suffix,
+            width,
+            zero_pad,
+        })
+    }
+
+    fn format(&self, n: i32) -> PathBuf {
+        let Self {
+            prefix,
+            suffix,
+            width,
+            ..
+        } = self;
+        if self.zero_pad {
+            format!("{prefix}{n:0width$}{suffix}").into()
+        } else {
+            format!("{prefix}{n:width$}{suffix}").into()
+        }
+    }
+}
+
+/// Interleaved samples of a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Raster {
+    width: usize,
+    height: usize,
+    channels: usize,
+    bits: u32,
+    samples: Vec<u16>,
+}
+
+impl Raster {
+    fn from_frame(frame: &VideoFrame) -> Result<Self, OutputError> {
+        let format = frame.get_video_format();
+        let unsupported = || {
+            OutputError::Unsupported(format!(
+                "{:?} {:?} {}-bit",
+                format.color_family, format.sample_type, format.bits_per_sample
+            ))
+        };
+        let channels = match format.color_family {
+            ffi::VSColorFamily::Gray => 1,
+            ffi::VSColorFamily::RGB => 3,
+            _ => return Err(unsupported()),
+        };
+        let bits = u32::try_from(format.bits_per_sample)
+            .ok()
+            .filter(|bits| {
+                (8..=16).contains(bits) && format.sample_type == ffi::VSSampleType::Integer
+            })
+            .ok_or_else(unsupported)?;
+
+        let width = usize::try_from(frame.frame_width(0)).map_err(|_| unsupported())?;
+        let height = usize::try_from(frame.frame_height(0)).map_err(|_| unsupported())?;
+        let mut samples = Vec::with_capacity(width * height * channels);
+        for y in 0..height {
+            let rows: Vec<_> = (0..)
+                .take(channels)
+                .map(|plane| {
+                    let offset = frame.stride(plane) * y.cast_signed();
+                    frame.plane(plane).wrapping_offset(offset)
+                })
+                .collect();
+            for x in 0..width {
+                // SAFETY: Each row of a plane holds `width` samples of the format
+                samples.extend(rows.iter().map(|row| unsafe {
+                    if bits > 8 {
+                        row.cast::<u16>().add(x).read()
+                    } else {
+                        u16::from(row.add(x).read())
+                    }
+                }));
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            channels,
+            bits,
+            samples,
+        })
+    }
+
+    fn write_pnm(&self, w: &mut impl Write) -> std::io::Result<()> {
+        let magic = if self.channels == 1 { "P5" } else { "P6" };
+        let max = (1u32 << self.bits) - 1;
+        write!(w, "{magic}\n{} {}\n{max}\n", self.width, self.height)?;
+        if self.bits > 8 {
+            let data: Vec<_> = self.samples.iter().flat_map(|s| s.to_be_bytes()).collect();
+            w.write_all(&data)
+        } else {
+            // Samples of 8 bits fit in `u8`
+            #[allow(clippy::cast_possible_truncation)]
+            let data: Vec<_> = self.samples.iter().map(|&s| s as u8).collect();
+            w.write_all(&data)
+        }
+    }
+
+    #[cfg(feature = "png")]
+    fn write_png(&self, path: PathBuf) -> Result<(), image::ImageError> {
+        use image::{DynamicImage, ImageBuffer};
+
+        let (width, height) = (
+            u32::try_from(self.width).unwrap_or(u32::MAX),
+            u32::try_from(self.height).unwrap_or(u32::MAX),
+        );
+        let buffer_error = || {
+            image::ImageError::Parameter(image::error::ParameterError::from_kind(
+                image::error::ParameterErrorKind::DimensionMismatch,
+            ))
+        };
+        let image = if self.bits > 8 {
+            // Scale to the full 16-bit range, so that the maximum maps to 65535
+            let max = (1u32 << self.bits) - 1;
+            let data = self
+                .samples
+                .iter()
+                .map(|&s| u16::try_from((u32::from(s) * 65535 + max / 2) / max).unwrap_or(u16::MAX))
+                .collect();
+            if self.channels == 1 {
+                DynamicImage::ImageLuma16(
+                    ImageBuffer::from_raw(width, height, data).ok_or_else(buffer_error)?,
+                )
+            } else {
+                DynamicImage::ImageRgb16(
+                    ImageBuffer::from_raw(width, height, data).ok_or_else(buffer_error)?,
+                )
+            }
+        } else {
+            // Samples of 8 bits fit in `u8`
+            #[allow(clippy::cast_possible_truncation)]
+            let data = self.samples.iter().map(|&s| s as u8).collect();
+            if self.channels == 1 {
+                DynamicImage::ImageLuma8(
+                    ImageBuffer::from_raw(width, height, data).ok_or_else(buffer_error)?,
+                )
+            } else {
+                DynamicImage::ImageRgb8(
+                    ImageBuffer::from_raw(width, height, data).ok_or_else(buffer_error)?,
+                )
+            }
+        };
+
+        image.save_with_format(path, image::ImageFormat::Png)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern() -> Result<(), OutputError> {
+        assert_eq!(
+            Pattern::parse("out/%05d.ppm")?.format(42),
+            PathBuf::from("out/00042.ppm")
+        );
+        assert_eq!(
+            Pattern::parse("100%%_%3d.pgm")?.format(7),
+            PathBuf::from("100%_  7.pgm")
+        );
+        assert_eq!(Pattern::parse("%d")?.format(1234), PathBuf::from("1234"));
+        assert!(Pattern::parse("frame.ppm").is_err());
+        assert!(Pattern::parse("%d_%d.ppm").is_err());
+        assert!(Pattern::parse("%s.ppm").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn pnm() -> std::io::Result<()> {
+        let mut raster = Raster {
+            width: 2,
+            height: 1,
+            channels: 3,
+            bits: 8,
+            samples: vec![1, 2, 3, 4, 5, 6],
+        };
+        let mut out = Vec::new();
+        raster.write_pnm(&mut out)?;
+        assert_eq!(out, b"P6\n2 1\n255\n\x01\x02\x03\x04\x05\x06");
+
+        raster.channels = 1;
+        raster.bits = 10;
+        raster.samples = vec![0x3FF, 0x102];
+        out.clear();
+        raster.write_pnm(&mut out)?;
+        assert_eq!(out, b"P5\n2 1\n1023\n\x03\xFF\x01\x02");
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn png() -> testresult::TestResult {
+        let raster = Raster {
+            width: 3,
+            height: 1,
+            channels: 1,
+            bits: 10,
+            samples: vec![0, 512, 1023],
+        };
+        let dir = std::env::temp_dir().join(format!("vapoursynth4-rs-png-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("10bit.png");
+        raster.write_png(path.clone())?;
+        let image = image::open(&path);
+        std::fs::remove_dir_all(&dir)?;
+
+        assert_eq!(image?.into_luma16().into_raw(), [0, 32800, 65535]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "link-vs")]
+    fn write_images() -> testresult::TestResult {
+        use crate::{core::Core, key, map};
+
+        let core = Core::builder().build();
+        let std = core
+            .get_plugin_by_namespace(c"std")
+            .ok_or("std not found")?;
+        let args = map!(core; "width" => 4, "height" => 2, "length" => 3, "color" => [255, 0, 0])?;
+        let clip = std
+            .invoke(c"BlankClip", args)
+            .get_as::<VideoNode>(key!(c"clip"))?;
+
+        let dir = std::env::temp_dir().join(format!(
+            "vapoursynth4-rs-write-images-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir)?;
+        let pattern = dir.join("%02d.ppm");
+        clip.write_images(
+            pattern.to_str().ok_or("non-UTF-8 path")?,
+            1..,
+            ImageFormat::Pnm,
+        )?;
+
+        assert!(!dir.join("00.ppm").exists());
+        let data = std::fs::read(dir.join("02.ppm"))?;
+        assert_eq!(&data[..11], b"P6\n4 2\n255\n");
+        assert_eq!(&data[11..14], [255, 0, 0]);
+        std::fs::remove_dir_all(dir)?;
+
+        Ok(())
+    }
+}
@@ -124,12 +124,72 @@ impl Iterator for Plugins<'_> {
     }
 }
 
+/// Declares the entry point of a plugin, registering each `(filter, data)` pair.
+///
+/// The filter is any type implementing [`Filter`](crate::node::Filter), including generic
+/// adapters such as [`Simple<T>`](crate::node::Simple):
+///
+/// ```
+/// use std::ffi::CStr;
+///
+/// use vapoursynth4_rs::{
+///     VAPOURSYNTH_API_VERSION,
+///     core::CoreRef,
+///     declare_plugin,
+///     frame::{VideoFrame, VideoFrameMut},
+///     key,
+///     map::MapRef,
+///     node::{Simple, SimpleVideoFilter, VideoNode},
+/// };
+///
+/// struct Copy {
+///     input: VideoNode,
+/// }
+///
+/// impl SimpleVideoFilter for Copy {
+///     type Error = &'static CStr;
+///
+///     const NAME: &'static CStr = c"Copy";
+///     const ARGS: &'static CStr = c"clip:vnode;";
+///
+///     fn create(args: MapRef, _core: CoreRef) -> Result<Self, Self::Error> {
+///         let input = args
+///             .get_video_node(key!(c"clip"), 0)
+///             .map_err(|_| c"Copy: clip is required")?;
+///         Ok(Self { input })
+///     }
+///
+///     fn input(&self) -> &VideoNode {
+///         &self.input
+///     }
+///
+///     fn process(
+///         &self,
+///         _n: i32,
+///         _src: &VideoFrame,
+///         _dst: &mut VideoFrameMut,
+///         _core: CoreRef,
+///     ) -> Result<(), Self::Error> {
+///         Ok(())
+///     }
+/// }
+///
+/// declare_plugin!(
+///     c"com.example.copy",
+///     c"copy",
+///     c"Copies a clip",
+///     (1, 0),
+///     VAPOURSYNTH_API_VERSION,
+///     0,
+///     (Simple<Copy>, None)
+/// );
+/// ```
 #[macro_export]
 macro_rules! declare_plugin {
     ($id:literal, $name:literal, $desc:literal,
         $version:expr,
         $api_version:expr, $flags:expr
-        $(, ($filter:tt, $data:expr) )*
+        $(, ($filter:ty, $data:expr) )*
     ) => {
         #[unsafe(no_mangle)]
         pub unsafe extern "system-unwind" fn VapourSynthPluginInit2(
@@ -0,0 +1,48 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Source nodes reading media files in pure Rust, built on [`Core::video_source`]
+//! and [`Core::audio_source`].
+//!
+//! [`Core::video_source`]: crate::core::Core::video_source
+//! [`Core::audio_source`]: crate::core::Core::audio_source
+
+#[cfg(feature = "mmap")]
+mod raw;
+mod wav;
+mod y4m;
+
+#[cfg(feature = "mmap")]
+pub use raw::RawPacking;
+
+use thiserror::Error;
+
+use crate::frame::VideoFrameMut;
+
+#[derive(Debug, Error)]
+pub enum SourceError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Invalid header: {0}")]
+    InvalidHeader(String),
+    #[error("Unsupported format: {0}")]
+    Unsupported(String),
+    #[error("File is truncated: expected {expected} bytes, found {found}")]
+    Truncated { expected: u64, found: u64 },
+    #[error("Failed to create the source node")]
+    CreateNode,
+}
+
+/// Copies tightly packed rows of `row_size` bytes from `data` into `plane` of `frame`.
+pub(crate) fn copy_plane(frame: &mut VideoFrameMut, plane: i32, data: &[u8], row_size: usize) {
+    let stride = frame.stride(plane);
+    let mut dst = frame.plane_mut(plane);
+    for row in data.chunks_exact(row_size) {
+        // SAFETY: Each row of the plane has at least `row_size` bytes
+        unsafe { dst.copy_from_nonoverlapping(row.as_ptr(), row_size) };
+        dst = dst.wrapping_offset(stride);
+    }
+}
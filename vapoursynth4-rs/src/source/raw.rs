@@ -0,0 +1,355 @@
+use std::{borrow::Cow, ffi::CStr, fs::File, path::Path};
+
+use bon::bon;
+use memmap2::Mmap;
+
+use crate::{
+    ColorFamily, SampleType, VideoInfo,
+    core::Core,
+    frame::{VideoFormat, VideoFrameMut},
+    node::{VideoNode, mode::Parallel},
+};
+
+use super::{SourceError, copy_plane};
+
+/// How the samples of a frame are laid out in a raw video file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RawPacking {
+    /// The planes of the format stored one after another, without padding.
+    #[default]
+    Planar,
+    /// Interleaved `R`, `G`, `B` samples, for 8-bit `RGB` formats.
+    Rgb24,
+    /// A luma plane followed by interleaved `U`, `V` samples, for 8-bit 4:2:0 `YUV` formats.
+    Nv12,
+    /// Like [`Nv12`](Self::Nv12) with 16-bit little endian samples holding the value
+    /// in the high bits, for 10-bit or 16-bit 4:2:0 `YUV` formats.
+    P010,
+}
+
+impl RawPacking {
+    fn check(self, format: &VideoFormat) -> Result<(), SourceError> {
+        let is_420 = |bits: &[i32]| {
+            format.color_family == ColorFamily::YUV
+                && format.sample_type == SampleType::Integer
+                && bits.contains(&format.bits_per_sample)
+                && (format.sub_sampling_w, format.sub_sampling_h) == (1, 1)
+        };
+        let supported = match self {
+            Self::Planar => format.color_family != ColorFamily::Undefined,
+            Self::Rgb24 => {
+                format.color_family == ColorFamily::RGB
+                    && format.sample_type == SampleType::Integer
+                    && format.bits_per_sample == 8
+            }
+            Self::Nv12 => is_420(&[8]),
+            Self::P010 => is_420(&[10, 16]),
+        };
+
+        if supported {
+            Ok(())
+        } else {
+            Err(SourceError::Unsupported(format!(
+                "{self:?} packing for the given format"
+            )))
+        }
+    }
+}
+
+#[bon]
+impl Core {
+    /// Opens a headerless video file with frames of `packing` in `format` as a video node.
+    ///
+    /// The file is memory mapped, and frames are read starting at `offset` bytes.
+    /// Interleaved packings are split into the planes of `format`.
+    ///
+    /// ```ignore
+    /// let format = core.query_video_format(ColorFamily::YUV, SampleType::Integer, 8, 1, 1);
+    /// let node = core
+    ///     .raw_video_source("capture.nv12")
+    ///     .width(1920)
+    ///     .height(1080)
+    ///     .format(format)
+    ///     .fps((30000, 1001))
+    ///     .packing(RawPacking::Nv12)
+    ///     .call()?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Return [`SourceError`] if the file can not be read, `packing` does not fit `format`,
+    /// or the file does not end on a frame boundary.
+    #[builder]
+    pub fn raw_video_source(
+        &self,
+        #[builder(start_fn)] path: impl AsRef<Path>,
+        width: i32,
+        height: i32,
+        format: VideoFormat,
+        /// Frame rate as numerator and denominator.
+        fps: (i64, i64),
+        /// Bytes to skip at the start of the file.
+        #[builder(default)]
+        offset: u64,
+        #[builder(default)] packing: RawPacking,
+    ) -> Result<VideoNode, SourceError> {
+        packing.check(&format)?;
+        let layout = RawLayout::new(width, height, &format, packing)?;
+
+        let file = File::open(path)?;
+        // SAFETY: The file is only read, and must not be modified while the node is alive
+        let data = unsafe { Mmap::map(&file)? };
+        let frame_size = layout.frame_size();
+        let num_frames = frame_count(data.len() as u64, offset, frame_size as u64)?;
+
+        let info = VideoInfo {
+            format,
+            fps_num: fps.0,
+            fps_den: fps.1,
+            width,
+            height,
+            num_frames,
+        };
+        // Bounded by the file size
+        #[allow(clippy::cast_possible_truncation)]
+        let offset = offset as usize;
+
+        self.video_source(info, Parallel, move |n, frame, _| {
+            let start = offset + usize::try_from(n).unwrap_or(0) * frame_size;
+            let raw = &data[start..start + frame_size];
+            layout.copy_frame(frame, &layout.unpack(raw, packing));
+            Ok::<_, &CStr>(())
+        })
+        .ok_or(SourceError::CreateNode)
+    }
+}
+
+/// Returns the number of frames of `frame_size` bytes in a file of `len` bytes.
+fn frame_count(len: u64, offset: u64, frame_size: u64) -> Result<i32, SourceError> {
+    let size = len.saturating_sub(offset);
+    let frames = size / frame_size;
+    if frames == 0 || !size.is_multiple_of(frame_size) {
+        return Err(SourceError::Truncated {
+            expected: offset + (frames + 1) * frame_size,
+            found: len,
+        });
+    }
+
+    i32::try_from(frames).map_err(|_| SourceError::Unsupported("too many frames".into()))
+}
+
+/// Sizes of the planes of a frame without padding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RawLayout {
+    width: usize,
+    height: usize,
+    bytes: usize,
+    ssw: u32,
+    ssh: u32,
+    num_planes: usize,
+    /// The value of a P010 sample is shifted down by this.
+    shift: u32,
+}
+
+impl RawLayout {
+    fn new(
+        width: i32,
+        height: i32,
+        format: &VideoFormat,
+        packing: RawPacking,
+    ) -> Result<Self, SourceError> {
+        let unsupported = |what: &str| SourceError::Unsupported(what.to_owned());
+        let size = format!("frame size {width}x{height}");
+        let (Ok(w), Ok(h)) = (usize::try_from(width), usize::try_from(height)) else {
+            return Err(unsupported(&size));
+        };
+        let (Ok(ssw), Ok(ssh)) = (
+            u32::try_from(format.sub_sampling_w),
+            u32::try_from(format.sub_sampling_h),
+        ) else {
+            return Err(unsupported("subsampling of the given format"));
+        };
+        if w == 0 || h == 0 {
+            return Err(unsupported(&size));
+        }
+        if !w.is_multiple_of(1 << ssw) || !h.is_multiple_of(1 << ssh) {
+            return Err(unsupported(&format!(
+                "{size}, which is not a multiple of the subsampling"
+            )));
+        }
+
+        let shift = if packing == RawPacking::P010 {
+            u32::try_from(format.bits_per_sample)
+                .ok()
+                .and_then(|bits| 16u32.checked_sub(bits))
+                .ok_or_else(|| unsupported("P010 packing for the given format"))?
+        } else {
+            0
+        };
+
+        Ok(Self {
+            width: w,
+            height: h,
+            bytes: usize::try_from(format.bytes_per_sample)
+                .map_err(|_| unsupported("sample size of the given format"))?,
+            ssw,
+            ssh,
+            num_planes: usize::try_from(format.num_planes)
+                .map_err(|_| unsupported("planes of the given format"))?,
+            shift,
+        })
+    }
+
+    /// Row size in bytes and number of rows of each plane.
+    fn planes(&self) -> impl Iterator<Item = (usize, usize)> {
+        let luma = (self.width * self.bytes, self.height);
+        let chroma = (
+            (self.width >> self.ssw) * self.bytes,
+            self.height >> self.ssh,
+        );
+        [luma, chroma, chroma].into_iter().take(self.num_planes)
+    }
+
+    fn frame_size(&self) -> usize {
+        self.planes().map(|(row, rows)| row * rows).sum()
+    }
+
+    /// Rearranges a frame of `packing` into tightly packed planes.
+    fn unpack<'a>(&self, raw: &'a [u8], packing: RawPacking) -> Cow<'a, [u8]> {
+        let plane_size = self.width * self.height * self.bytes;
+        match packing {
+            RawPacking::Planar => Cow::Borrowed(raw),
+            RawPacking::Rgb24 => {
+                let mut planar = vec![0; raw.len()];
+                let (r, gb) = planar.split_at_mut(plane_size);
+                let (g, b) = gb.split_at_mut(plane_size);
+                for (i, px) in raw.chunks_exact(3).enumerate() {
+                    (r[i], g[i], b[i]) = (px[0], px[1], px[2]);
+                }
+                Cow::Owned(planar)
+            }
+            RawPacking::Nv12 | RawPacking::P010 => {
+                let mut planar = Vec::with_capacity(raw.len());
+                let (luma, chroma) = raw.split_at(plane_size);
+                let (mut u, mut v) = (Vec::new(), Vec::new());
+                for uv in chroma.chunks_exact(2 * self.bytes) {
+                    let (su, sv) = uv.split_at(self.bytes);
+                    u.extend_from_slice(su);
+                    v.extend_from_slice(sv);
+                }
+                planar.extend_from_slice(luma);
+                planar.extend(u);
+                planar.extend(v);
+
+                if packing == RawPacking::P010 && self.shift > 0 {
+                    for sample in planar.chunks_exact_mut(2) {
+                        let value = u16::from_le_bytes([sample[0], sample[1]]) >> self.shift;
+                        sample.copy_from_slice(&value.to_ne_bytes());
+                    }
+                }
+                Cow::Owned(planar)
+            }
+        }
+    }
+
+    fn copy_frame(&self, frame: &mut VideoFrameMut, mut data: &[u8]) {
+        for (plane, (row_size, rows)) in (0..).zip(self.planes()) {
+            let (plane_data, rest) = data.split_at(row_size * rows);
+            copy_plane(frame, plane, plane_data, row_size);
+            data = rest;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(color_family: ColorFamily, bits: i32, ss: i32) -> VideoFormat {
+        VideoFormat {
+            color_family,
+            sample_type: SampleType::Integer,
+            bits_per_sample: bits,
+            bytes_per_sample: (bits + 7) / 8,
+            sub_sampling_w: ss,
+            sub_sampling_h: ss,
+            num_planes: 3,
+        }
+    }
+
+    #[test]
+    fn packing() -> Result<(), SourceError> {
+        let rgb = format(ColorFamily::RGB, 8, 0);
+        RawPacking::Rgb24.check(&rgb)?;
+        assert!(RawPacking::Nv12.check(&rgb).is_err());
+        let layout = RawLayout::new(2, 1, &rgb, RawPacking::Rgb24)?;
+        assert_eq!(layout.frame_size(), 6);
+        assert_eq!(
+            &*layout.unpack(&[1, 2, 3, 4, 5, 6], RawPacking::Rgb24),
+            &[1, 4, 2, 5, 3, 6]
+        );
+
+        let yuv = format(ColorFamily::YUV, 8, 1);
+        RawPacking::Nv12.check(&yuv)?;
+        let layout = RawLayout::new(4, 2, &yuv, RawPacking::Nv12)?;
+        assert_eq!(layout.frame_size(), 12);
+        let raw: Vec<u8> = (0..12).collect();
+        assert_eq!(
+            &*layout.unpack(&raw, RawPacking::Nv12),
+            &[0, 1, 2, 3, 4, 5, 6, 7, 8, 10, 9, 11]
+        );
+
+        let yuv = format(ColorFamily::YUV, 10, 1);
+        RawPacking::P010.check(&yuv)?;
+        let layout = RawLayout::new(2, 2, &yuv, RawPacking::P010)?;
+        let raw: Vec<u8> = [1u16, 2, 3, 4, 5, 6]
+            .iter()
+            .flat_map(|v| (v << 6).to_le_bytes())
+            .collect();
+        let planar: Vec<u16> = layout
+            .unpack(&raw, RawPacking::P010)
+            .chunks_exact(2)
+            .map(|s| u16::from_ne_bytes([s[0], s[1]]))
+            .collect();
+        assert_eq!(planar, [1, 2, 3, 4, 5, 6]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn layout() -> Result<(), SourceError> {
+        let rgbs = VideoFormat {
+            sample_type: SampleType::Float,
+            ..format(ColorFamily::RGB, 32, 0)
+        };
+        RawPacking::Planar.check(&rgbs)?;
+        let layout = RawLayout::new(3, 1, &rgbs, RawPacking::Planar)?;
+        assert_eq!(layout.frame_size(), 36);
+
+        let yuv = format(ColorFamily::YUV, 8, 1);
+        assert!(RawLayout::new(4, 2, &yuv, RawPacking::Planar).is_ok());
+        for (width, height) in [(3, 2), (4, 3), (0, 2)] {
+            assert!(matches!(
+                RawLayout::new(width, height, &yuv, RawPacking::Planar),
+                Err(SourceError::Unsupported(_))
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn truncated() -> Result<(), SourceError> {
+        assert_eq!(frame_count(16 + 30, 16, 10)?, 3);
+        assert!(matches!(
+            frame_count(16 + 35, 16, 10),
+            Err(SourceError::Truncated {
+                expected: 56,
+                found: 51
+            })
+        ));
+        assert!(frame_count(8, 16, 10).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,498 @@
+use std::{
+    ffi::CString,
+    fs::File,
+    io::{ErrorKind, Read, Seek, SeekFrom},
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::{
+    AudioInfo, SampleType,
+    core::Core,
+    ffi::{self, VSAudioChannels as Ch},
+    frame::AudioFrameMut,
+    node::{AudioNode, mode::Parallel},
+    utils::ToCString,
+};
+
+use super::SourceError;
+
+impl Core {
+    /// Opens a PCM or float WAV (including `WAVE_FORMAT_EXTENSIBLE`) or Wave64 file
+    /// as an audio node.
+    ///
+    /// 16, 24 and 32 bit integer and 32 bit float samples are supported.
+    /// The channel mask is mapped to [`VSAudioChannels`](ffi::VSAudioChannels), or guessed from
+    /// the number of channels if it is missing.
+    ///
+    /// # Errors
+    ///
+    /// Return [`SourceError`] if the file can not be read, its header is invalid
+    /// or the sample format is unsupported.
+    pub fn wav_source(&self, path: impl AsRef<Path>) -> Result<AudioNode, SourceError> {
+        let mut file = File::open(path)?;
+        let wav = WavInfo::read(&mut file)?;
+
+        let info = AudioInfo {
+            format: self.query_audio_format(
+                wav.sample_type,
+                i32::from(wav.bits),
+                wav.channel_layout,
+            ),
+            sample_rate: i32::try_from(wav.sample_rate)
+                .map_err(|_| SourceError::Unsupported("sample rate is too large".into()))?,
+            num_samples: i64::try_from(wav.num_samples())
+                .map_err(|_| SourceError::Unsupported("too many samples".into()))?,
+            num_frames: 0,
+        };
+        let file = Mutex::new(file);
+
+        self.audio_source(info, Parallel, move |n, frame, _| {
+            let len = usize::try_from(frame.frame_length()).unwrap_or(0);
+            let start = u64::try_from(n).unwrap_or(0)
+                * u64::from(ffi::VS_AUDIO_FRAME_SAMPLES.unsigned_abs());
+            let mut data = vec![0; len * wav.block_align()];
+            {
+                let mut file = file
+                    .lock()
+                    .map_err(|_| c"wav: file lock is poisoned".to_owned())?;
+                file.seek(SeekFrom::Start(
+                    wav.data_offset + start * wav.block_align() as u64,
+                ))
+                .and_then(|_| file.read_exact(&mut data))
+                .map_err(|e| format!("wav: failed to read frame {n}: {e}").into_cstring_lossy())?;
+            }
+            wav.deinterleave(&data, frame);
+            Ok::<_, CString>(())
+        })
+        .ok_or(SourceError::CreateNode)
+    }
+}
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Wave64 GUIDs, whose first 4 bytes are the RIFF chunk ids.
+const W64_RIFF: [u8; 16] = *b"riff\x2E\x91\xCF\x11\xA5\xD6\x28\xDB\x04\xC1\x00\x00";
+const W64_WAVE: [u8; 16] = *b"wave\xF3\xAC\xD3\x11\x8C\xD1\x00\xC0\x4F\x8E\xDB\x8A";
+const W64_FMT: [u8; 16] = *b"fmt \xF3\xAC\xD3\x11\x8C\xD1\x00\xC0\x4F\x8E\xDB\x8A";
+const W64_DATA: [u8; 16] = *b"data\xF3\xAC\xD3\x11\x8C\xD1\x00\xC0\x4F\x8E\xDB\x8A";
+
+/// `VapourSynth` channels in the order of the bits of the WAVE channel mask.
+const WAVE_CHANNELS: [Ch; 18] = [
+    Ch::FrontLeft,
+    Ch::FrontRight,
+    Ch::FrontCenter,
+    Ch::LowFrequency,
+    Ch::BackLeft,
+    Ch::BackRight,
+    Ch::FrontLeftOFCenter,
+    Ch::FrontRightOFCenter,
+    Ch::BackCenter,
+    Ch::SideLeft,
+    Ch::SideRight,
+    Ch::TopCenter,
+    Ch::TopFrontLeft,
+    Ch::TopFrontCenter,
+    Ch::TopFrontRight,
+    Ch::TopBackLeft,
+    Ch::TopBackCenter,
+    Ch::TopBackRight,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WavInfo {
+    sample_type: SampleType,
+    /// Container size of a sample.
+    bits: u16,
+    channels: u16,
+    sample_rate: u32,
+    /// `VapourSynth` channel layout.
+    channel_layout: u64,
+    data_offset: u64,
+    data_size: u64,
+}
+
+impl WavInfo {
+    fn read(reader: &mut (impl Read + Seek)) -> Result<Self, SourceError> {
+        let invalid = |msg: &str| SourceError::InvalidHeader(msg.into());
+
+        let mut magic = [0; 12];
+        reader.read_exact(&mut magic)?;
+        let w64 = if &magic[..4] == b"RIFF" && &magic[8..] == b"WAVE" {
+            false
+        } else if magic[..] == W64_RIFF[..12] {
+            let mut rest = [0; 28];
+            reader.read_exact(&mut rest)?;
+            if rest[..4] != W64_RIFF[12..] || rest[12..] != W64_WAVE {
+                return Err(invalid("not a Wave64 file"));
+            }
+            true
+        } else {
+            return Err(invalid("not a WAV or Wave64 file"));
+        };
+
+        let mut fmt = None;
+        loop {
+            let (id, size) = if w64 {
+                let mut header = [0; 24];
+                reader.read_exact(&mut header).map_err(eof_as("data"))?;
+                let size = u64::from_le_bytes(header[16..].try_into().unwrap());
+                let id = match header[..16].try_into().unwrap() {
+                    W64_FMT => *b"fmt ",
+                    W64_DATA => *b"data",
+                    _ => [0; 4],
+                };
+                // The size includes the header
+                (
+                    id,
+                    size.checked_sub(24)
+                        .ok_or_else(|| invalid("invalid chunk size"))?,
+                )
+            } else {
+                let mut header = [0; 8];
+                reader.read_exact(&mut header).map_err(eof_as("data"))?;
+                let size = u32::from_le_bytes(header[4..].try_into().unwrap());
+                (header[..4].try_into().unwrap(), u64::from(size))
+            };
+
+            match &id {
+                b"fmt " => {
+                    let mut data = vec![0; usize::try_from(size).unwrap_or(0).min(64)];
+                    reader.read_exact(&mut data)?;
+                    skip_chunk(reader, size, data.len() as u64, w64)?;
+                    fmt = Some(data);
+                }
+                b"data" => {
+                    let fmt = fmt.ok_or_else(|| invalid("missing fmt chunk"))?;
+                    let data_offset = reader.stream_position()?;
+                    let file_size = reader.seek(SeekFrom::End(0))?;
+                    // Streamed files may not have the data size filled in
+                    let data_size = size.min(file_size - data_offset);
+                    return Self::parse_fmt(&fmt, data_offset, data_size);
+                }
+                _ => skip_chunk(reader, size, 0, w64)?,
+            }
+        }
+    }
+
+    fn parse_fmt(fmt: &[u8], data_offset: u64, data_size: u64) -> Result<Self, SourceError> {
+        if fmt.len() < 16 {
+            return Err(SourceError::InvalidHeader("fmt chunk is too short".into()));
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([fmt[i], fmt[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes(fmt[i..i + 4].try_into().unwrap());
+
+        let mut tag = u16_at(0);
+        let channels = u16_at(2);
+        let sample_rate = u32_at(4);
+        let bits = u16_at(14);
+        if channels == 0 {
+            return Err(SourceError::InvalidHeader(
+                "fmt chunk has no channels".into(),
+            ));
+        }
+        let mut mask = 0;
+        if tag == WAVE_FORMAT_EXTENSIBLE {
+            if fmt.len() < 40 {
+                return Err(SourceError::InvalidHeader("fmt chunk is too short".into()));
+            }
+            mask = u32_at(20);
+            tag = u16_at(24);
+        }
+
+        let sample_type = match (tag, bits) {
+            (WAVE_FORMAT_PCM, 16 | 24 | 32) => SampleType::Integer,
+            (WAVE_FORMAT_IEEE_FLOAT, 32) => SampleType::Float,
+            _ => {
+                return Err(SourceError::Unsupported(format!(
+                    "format tag {tag:#x} with {bits} bits"
+                )));
+            }
+        };
+
+        let wav = Self {
+            sample_type,
+            bits,
+            channels,
+            sample_rate,
+            channel_layout: channel_layout(mask, channels)?,
+            data_offset,
+            data_size,
+        };
+        if wav.block_align() == 0 {
+            return Err(SourceError::InvalidHeader("block align is zero".into()));
+        }
+
+        Ok(wav)
+    }
+
+    fn block_align(&self) -> usize {
+        usize::from(self.channels) * usize::from(self.bits / 8)
+    }
+
+    fn num_samples(&self) -> u64 {
+        self.data_size / self.block_align() as u64
+    }
+
+    /// Splits interleaved `data` into the channels of `frame`.
+    // Frame data allocated by VapourSynth is aligned for any sample type
+    #[allow(clippy::cast_ptr_alignment)]
+    fn deinterleave(&self, data: &[u8], frame: &mut AudioFrameMut) {
+        let bytes = usize::from(self.bits / 8);
+        for ch in 0..self.channels {
+            let dst = frame.channel_mut(i32::from(ch));
+            let samples = data
+                .chunks_exact(self.block_align())
+                .map(|block| &block[usize::from(ch) * bytes..][..bytes]);
+            // SAFETY: Each channel holds `frame_length` samples of the format
+            unsafe {
+                match bytes {
+                    2 => write_samples(
+                        dst.cast::<i16>(),
+                        samples.map(|s| i16::from_le_bytes([s[0], s[1]])),
+                    ),
+                    3 => write_samples(
+                        dst.cast::<i32>(),
+                        // Sign extend from the top byte
+                        samples.map(|s| i32::from_le_bytes([0, s[0], s[1], s[2]]) >> 8),
+                    ),
+                    _ => write_samples(
+                        dst.cast::<u32>(),
+                        samples.map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]])),
+                    ),
+                }
+            }
+        }
+    }
+}
+
+unsafe fn write_samples<T>(dst: *mut T, samples: impl Iterator<Item = T>) {
+    for (i, sample) in samples.enumerate() {
+        unsafe { dst.add(i).write(sample) };
+    }
+}
+
+fn eof_as(chunk: &'static str) -> impl Fn(std::io::Error) -> SourceError {
+    move |e| {
+        if e.kind() == ErrorKind::UnexpectedEof {
+            SourceError::InvalidHeader(format!("missing {chunk} chunk"))
+        } else {
+            e.into()
+        }
+    }
+}
+
+/// Skips the rest of a chunk of `size` bytes, of which `read` bytes are already read.
+fn skip_chunk(reader: &mut impl Seek, size: u64, read: u64, w64: bool) -> Result<(), SourceError> {
+    // RIFF chunks are aligned to 2 bytes, Wave64 chunks to 8 bytes
+    let align = if w64 { 8 } else { 2 };
+    let size = size.next_multiple_of(align) - read;
+    reader.seek(SeekFrom::Current(i64::try_from(size).map_err(|_| {
+        SourceError::InvalidHeader("invalid chunk size".into())
+    })?))?;
+    Ok(())
+}
+
+/// Maps a WAVE channel mask to a `VapourSynth` channel layout.
+fn channel_layout(mask: u32, channels: u16) -> Result<u64, SourceError> {
+    use Ch::{
+        BackLeft, BackRight, FrontCenter, FrontLeft, FrontRight, LowFrequency, SideLeft, SideRight,
+    };
+
+    let layout = |chs: &[Ch]| chs.iter().fold(0u64, |layout, &ch| layout | 1 << ch as u64);
+
+    let layout = if mask == 0 {
+        match channels {
+            1 => layout(&[FrontCenter]),
+            2 => layout(&[FrontLeft, FrontRight]),
+            3 => layout(&[FrontLeft, FrontRight, FrontCenter]),
+            4 => layout(&[FrontLeft, FrontRight, BackLeft, BackRight]),
+            5 => layout(&[FrontLeft, FrontRight, FrontCenter, BackLeft, BackRight]),
+            6 => layout(&[
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackLeft,
+                BackRight,
+            ]),
+            8 => layout(&[
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackLeft,
+                BackRight,
+                SideLeft,
+                SideRight,
+            ]),
+            n => layout(&WAVE_CHANNELS[..usize::from(n).min(WAVE_CHANNELS.len())]),
+        }
+    } else {
+        let chs: Vec<_> = (0..)
+            .zip(WAVE_CHANNELS)
+            .filter(|(bit, _)| mask & 1 << bit != 0)
+            .map(|(_, ch)| ch)
+            .collect();
+        layout(&chs)
+    };
+
+    if layout.count_ones() == u32::from(channels) {
+        Ok(layout)
+    } else {
+        Err(SourceError::Unsupported(format!(
+            "channel mask {mask:#x} for {channels} channels"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{mock, node::Node};
+
+    fn fmt(tag: u16, channels: u16, bits: u16, mask: Option<u32>) -> Vec<u8> {
+        let block_align = channels * bits / 8;
+        let mut fmt = Vec::new();
+        fmt.extend(
+            if mask.is_some() {
+                WAVE_FORMAT_EXTENSIBLE
+            } else {
+                tag
+            }
+            .to_le_bytes(),
+        );
+        fmt.extend(channels.to_le_bytes());
+        fmt.extend(48000u32.to_le_bytes());
+        fmt.extend((48000 * u32::from(block_align)).to_le_bytes());
+        fmt.extend(block_align.to_le_bytes());
+        fmt.extend(bits.to_le_bytes());
+        if let Some(mask) = mask {
+            fmt.extend(22u16.to_le_bytes());
+            fmt.extend(bits.to_le_bytes());
+            fmt.extend(mask.to_le_bytes());
+            fmt.extend(tag.to_le_bytes());
+            fmt.extend(&W64_DATA[2..]);
+        }
+        fmt
+    }
+
+    #[test]
+    fn wav() -> Result<(), SourceError> {
+        let fmt = fmt(WAVE_FORMAT_PCM, 2, 24, Some(0x3));
+        let mut file = b"RIFF\0\0\0\0WAVE".to_vec();
+        // An odd sized chunk with padding
+        file.extend(b"LIST\x03\0\0\0abc\0");
+        file.extend(b"fmt ");
+        file.extend(u32::try_from(fmt.len()).unwrap().to_le_bytes());
+        file.extend(&fmt);
+        file.extend(b"data\xFF\xFF\xFF\xFF");
+        let data_offset = file.len() as u64;
+        file.extend([0; 6 * 10]);
+
+        let wav = WavInfo::read(&mut Cursor::new(&file))?;
+        assert_eq!(wav.sample_type, SampleType::Integer);
+        assert_eq!((wav.bits, wav.channels, wav.sample_rate), (24, 2, 48000));
+        assert_eq!(wav.channel_layout, 0b11);
+        assert_eq!((wav.data_offset, wav.num_samples()), (data_offset, 10));
+
+        Ok(())
+    }
+
+    #[test]
+    fn w64() -> Result<(), SourceError> {
+        let fmt = fmt(WAVE_FORMAT_IEEE_FLOAT, 1, 32, None);
+        let mut file = W64_RIFF.to_vec();
+        file.extend(0u64.to_le_bytes());
+        file.extend(W64_WAVE);
+        file.extend(W64_FMT);
+        file.extend((fmt.len() as u64 + 24).to_le_bytes());
+        file.extend(&fmt);
+        file.extend(W64_DATA);
+        file.extend((24u64 + 4 * 3).to_le_bytes());
+        file.extend([0; 4 * 3]);
+
+        let wav = WavInfo::read(&mut Cursor::new(&file))?;
+        assert_eq!(wav.sample_type, SampleType::Float);
+        assert_eq!(wav.channel_layout, 1 << Ch::FrontCenter as u64);
+        assert_eq!(wav.num_samples(), 3);
+
+        assert!(matches!(
+            WavInfo::read(&mut Cursor::new(&file[..40])),
+            Err(SourceError::InvalidHeader(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn channels() {
+        assert_eq!(channel_layout(0, 6).ok(), Some(0b11_1111));
+        assert_eq!(
+            channel_layout(0x600 | 0x3, 4).ok(),
+            Some(
+                1 << Ch::FrontLeft as u64
+                    | 1 << Ch::FrontRight as u64
+                    | 1 << Ch::SideLeft as u64
+                    | 1 << Ch::SideRight as u64
+            )
+        );
+        assert!(channel_layout(0x3, 3).is_err());
+        assert!(WavInfo::parse_fmt(&fmt(WAVE_FORMAT_PCM, 1, 8, None), 0, 0).is_err());
+        assert!(matches!(
+            WavInfo::parse_fmt(&fmt(WAVE_FORMAT_PCM, 0, 16, None), 0, 0),
+            Err(SourceError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    // Frame data allocated by VapourSynth is aligned for any sample type
+    #[allow(clippy::cast_ptr_alignment)]
+    fn source() -> Result<(), Box<dyn std::error::Error>> {
+        let frame_samples = ffi::VS_AUDIO_FRAME_SAMPLES;
+        let num_samples = frame_samples + 5;
+        let sample = |i: i32, ch: i32| if ch == 0 { -i } else { i * 1000 - 4_000_000 };
+
+        let fmt = fmt(WAVE_FORMAT_PCM, 2, 24, None);
+        let mut file = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        file.extend(u32::try_from(fmt.len())?.to_le_bytes());
+        file.extend(&fmt);
+        file.extend(b"data");
+        file.extend((u32::try_from(num_samples)? * 6).to_le_bytes());
+        for i in 0..num_samples {
+            for ch in 0..2 {
+                file.extend(&sample(i, ch).to_le_bytes()[..3]);
+            }
+        }
+        let dir = std::env::temp_dir().join(format!("vapoursynth4-rs-wav-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("source.wav");
+        std::fs::write(&path, file)?;
+
+        let core = Core::builder().api(mock::api()).build();
+        let node = core.wav_source(&path);
+        std::fs::remove_dir_all(&dir)?;
+        let node = node?;
+        assert_eq!(node.info().num_samples, i64::from(num_samples));
+        assert_eq!(node.info().num_frames, 2);
+
+        let frame = node
+            .get_frame(1)
+            .map_err(|e| e.to_string_lossy().into_owned())?;
+        assert_eq!(frame.frame_length(), 5);
+        for ch in 0..2 {
+            // SAFETY: Each channel of the last frame holds 5 samples of 4 bytes
+            let samples = unsafe { std::slice::from_raw_parts(frame.channel(ch).cast::<i32>(), 5) };
+            let expected: Vec<_> = (frame_samples..num_samples)
+                .map(|i| sample(i, ch))
+                .collect();
+            assert_eq!(samples, expected);
+        }
+
+        Ok(())
+    }
+}
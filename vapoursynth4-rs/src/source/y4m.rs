@@ -0,0 +1,420 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::{
+    ColorFamily, SampleType, VideoInfo,
+    core::Core,
+    frame::{Frame, VideoFrameMut},
+    key,
+    map::MapPropertyError,
+    node::{VideoNode, mode::Parallel},
+    utils::ToCString,
+};
+
+use super::{SourceError, copy_plane};
+
+impl Core {
+    /// Opens a YUV4MPEG2 file as a video node with random access.
+    ///
+    /// The `C` tag is mapped to a YUV or gray format of up to 16 bits. Frames have
+    /// `_DurationNum`/`_DurationDen` set from `F`, `_SARNum`/`_SARDen` from `A`,
+    /// `_FieldBased` from `I` and `_ChromaLocation` from the chroma siting of `C`
+    /// when they are known.
+    ///
+    /// # Errors
+    ///
+    /// Return [`SourceError`] if the file can not be read, its header is invalid or unsupported,
+    /// or the last frame is truncated.
+    pub fn y4m_source(&self, path: impl AsRef<Path>) -> Result<VideoNode, SourceError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let header = Y4mHeader::read(&mut reader)?;
+        let frame_size = header.frame_size();
+        let offsets = index_frames(&mut reader, frame_size)?;
+
+        let color = &header.color;
+        let info = VideoInfo {
+            format: self.query_video_format(
+                color.family,
+                SampleType::Integer,
+                color.bits,
+                color.ssw,
+                color.ssh,
+            ),
+            fps_num: header.fps.0,
+            fps_den: header.fps.1,
+            width: header.width,
+            height: header.height,
+            num_frames: i32::try_from(offsets.len())
+                .map_err(|_| SourceError::Unsupported("too many frames".into()))?,
+        };
+        let file = Mutex::new(reader.into_inner());
+
+        self.video_source(info, Parallel, move |n, frame, _| {
+            let mut data = vec![0; frame_size];
+            {
+                let mut file = file.lock().map_err(|_| c"y4m: file lock is poisoned")?;
+                let offset = usize::try_from(n).map_or(0, |n| offsets[n]);
+                file.seek(SeekFrom::Start(offset))
+                    .and_then(|_| file.read_exact(&mut data))
+                    .map_err(|e| {
+                        format!("y4m: failed to read frame {n}: {e}").into_cstring_lossy()
+                    })?;
+            }
+            header.copy_frame(frame, &data);
+            header
+                .set_props(frame)
+                .map_err(|e| e.to_string().into_cstring_lossy())
+        })
+        .ok_or(SourceError::CreateNode)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Y4mColor {
+    family: ColorFamily,
+    bits: i32,
+    ssw: i32,
+    ssh: i32,
+    chroma_location: Option<i64>,
+}
+
+impl Y4mColor {
+    fn parse(tag: &str) -> Result<Self, SourceError> {
+        let unsupported = || SourceError::Unsupported(format!("colorspace `{tag}`"));
+        let bits = |s: &str| match s {
+            "" => Ok(8),
+            _ => s
+                .parse()
+                .ok()
+                .filter(|b| (8..=16).contains(b))
+                .ok_or_else(unsupported),
+        };
+
+        if let Some(depth) = tag.strip_prefix("mono") {
+            return Ok(Self {
+                family: ColorFamily::Gray,
+                bits: bits(depth)?,
+                ssw: 0,
+                ssh: 0,
+                chroma_location: None,
+            });
+        }
+
+        let (ssw, ssh) = match tag.get(..3) {
+            Some("420") => (1, 1),
+            Some("422") => (1, 0),
+            Some("444") => (0, 0),
+            Some("411") => (2, 0),
+            _ => return Err(unsupported()),
+        };
+        let (bits, chroma_location) = match &tag[3..] {
+            "" | "jpeg" if ssh == 1 => (8, Some(1)),
+            "mpeg2" if ssh == 1 => (8, Some(0)),
+            "paldv" if ssh == 1 => (8, Some(2)),
+            "" => (8, None),
+            rest => (bits(rest.strip_prefix('p').ok_or_else(unsupported)?)?, None),
+        };
+
+        Ok(Self {
+            family: ColorFamily::YUV,
+            bits,
+            ssw,
+            ssh,
+            chroma_location,
+        })
+    }
+
+    fn bytes_per_sample(&self) -> usize {
+        if self.bits > 8 { 2 } else { 1 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Y4mHeader {
+    width: i32,
+    height: i32,
+    fps: (i64, i64),
+    sar: Option<(i64, i64)>,
+    field_based: Option<i64>,
+    color: Y4mColor,
+}
+
+impl Y4mHeader {
+    fn read(reader: &mut impl BufRead) -> Result<Self, SourceError> {
+        let mut line = Vec::new();
+        reader.read_until(b'\n', &mut line)?;
+        let line = std::str::from_utf8(&line)
+            .map_err(|_| SourceError::InvalidHeader("not UTF-8".into()))?;
+        Self::parse(line.trim_end_matches('\n'))
+    }
+
+    fn parse(line: &str) -> Result<Self, SourceError> {
+        let invalid = |msg: &str| SourceError::InvalidHeader(msg.into());
+
+        let mut tags = line.split(' ');
+        if tags.next() != Some("YUV4MPEG2") {
+            return Err(invalid("missing YUV4MPEG2 signature"));
+        }
+
+        let (mut width, mut height, mut fps) = (None, None, None);
+        let mut header = Self {
+            width: 0,
+            height: 0,
+            fps: (0, 0),
+            sar: None,
+            field_based: None,
+            color: Y4mColor::parse("420jpeg")?,
+        };
+        for tag in tags.filter(|t| !t.is_empty()) {
+            let (name, value) = tag
+                .split_at_checked(1)
+                .ok_or_else(|| invalid("invalid tag"))?;
+            match name {
+                "W" => width = value.parse().ok(),
+                "H" => height = value.parse().ok(),
+                "F" => fps = Some(parse_ratio(value).ok_or_else(|| invalid("invalid F"))?),
+                "A" => header.sar = parse_ratio(value).filter(|&(n, d)| n > 0 && d > 0),
+                "I" => {
+                    header.field_based = match value {
+                        "p" => Some(0),
+                        "b" => Some(1),
+                        "t" => Some(2),
+                        _ => None,
+                    }
+                }
+                "C" => header.color = Y4mColor::parse(value)?,
+                _ => {}
+            }
+        }
+
+        header.width = width
+            .filter(|&w| w > 0)
+            .ok_or_else(|| invalid("missing W"))?;
+        header.height = height
+            .filter(|&h| h > 0)
+            .ok_or_else(|| invalid("missing H"))?;
+        header.fps = fps
+            .filter(|&(n, d)| n > 0 && d > 0)
+            .map(reduce)
+            .ok_or_else(|| invalid("missing F"))?;
+
+        let color = &header.color;
+        if header.width % (1 << color.ssw) != 0 || header.height % (1 << color.ssh) != 0 {
+            return Err(SourceError::Unsupported(
+                "dimensions not divisible by the chroma subsampling".into(),
+            ));
+        }
+
+        Ok(header)
+    }
+
+    /// Row size in bytes and number of rows of each plane.
+    fn planes(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let bytes = self.color.bytes_per_sample();
+        let (w, h) = (
+            self.width.unsigned_abs() as usize,
+            self.height.unsigned_abs() as usize,
+        );
+        let num_planes = if self.color.family == ColorFamily::Gray {
+            1
+        } else {
+            3
+        };
+        (0..num_planes).map(move |p| {
+            if p == 0 {
+                (w * bytes, h)
+            } else {
+                ((w >> self.color.ssw) * bytes, h >> self.color.ssh)
+            }
+        })
+    }
+
+    fn frame_size(&self) -> usize {
+        self.planes().map(|(row, rows)| row * rows).sum()
+    }
+
+    fn copy_frame(&self, frame: &mut VideoFrameMut, mut data: &[u8]) {
+        for (plane, (row_size, rows)) in (0..).zip(self.planes()) {
+            let (plane_data, rest) = data.split_at(row_size * rows);
+            copy_plane(frame, plane, plane_data, row_size);
+            data = rest;
+        }
+    }
+
+    fn set_props(&self, frame: &mut VideoFrameMut) -> Result<(), MapPropertyError> {
+        let Some(mut props) = frame.properties_mut() else {
+            return Ok(());
+        };
+        if let Some((num, den)) = self.sar {
+            props.insert(key!(c"_SARNum"), num)?;
+            props.insert(key!(c"_SARDen"), den)?;
+        }
+        if let Some(field_based) = self.field_based {
+            props.insert(key!(c"_FieldBased"), field_based)?;
+        }
+        if let Some(location) = self.color.chroma_location {
+            props.insert(key!(c"_ChromaLocation"), location)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_ratio(s: &str) -> Option<(i64, i64)> {
+    let (num, den) = s.split_once(':')?;
+    Some((num.parse().ok()?, den.parse().ok()?))
+}
+
+fn reduce((num, den): (i64, i64)) -> (i64, i64) {
+    let (mut a, mut b) = (num, den);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    (num / a, den / a)
+}
+
+/// Returns the offset of the data of each frame.
+fn index_frames(
+    reader: &mut (impl BufRead + Seek),
+    frame_size: usize,
+) -> Result<Vec<u64>, SourceError> {
+    let skip = i64::try_from(frame_size)
+        .map_err(|_| SourceError::Unsupported("frame size is too large".into()))?;
+    let mut offsets = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if !line.starts_with(b"FRAME") {
+            return Err(SourceError::InvalidHeader(format!(
+                "expected FRAME header of frame {}",
+                offsets.len()
+            )));
+        }
+        offsets.push(reader.stream_position()?);
+        reader.seek_relative(skip)?;
+    }
+
+    let found = reader.seek(SeekFrom::End(0))?;
+    if let Some(&last) = offsets.last() {
+        let expected = last + frame_size as u64;
+        if found < expected {
+            return Err(SourceError::Truncated { expected, found });
+        }
+    }
+
+    Ok(offsets)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{mock, node::Node};
+
+    #[test]
+    fn header() -> Result<(), SourceError> {
+        let header =
+            Y4mHeader::parse("YUV4MPEG2 W64 H32 F30000:1001 Ip A1:1 C420mpeg2 XYSCSS=420MPEG2")?;
+        assert_eq!((header.width, header.height), (64, 32));
+        assert_eq!(header.fps, (30000, 1001));
+        assert_eq!(header.sar, Some((1, 1)));
+        assert_eq!(header.field_based, Some(0));
+        assert_eq!(header.color.chroma_location, Some(0));
+        assert_eq!(header.frame_size(), 64 * 32 * 3 / 2);
+
+        let header = Y4mHeader::parse("YUV4MPEG2 W8 H2 F50:2 It A0:0 C422p10")?;
+        assert_eq!(header.fps, (25, 1));
+        assert_eq!(header.sar, None);
+        assert_eq!(header.field_based, Some(2));
+        assert_eq!(
+            (header.color.bits, header.color.ssw, header.color.ssh),
+            (10, 1, 0)
+        );
+        assert_eq!(header.frame_size(), 8 * 2 * 2 * 2);
+
+        let header = Y4mHeader::parse("YUV4MPEG2 W3 H3 F1:1 Cmono16")?;
+        assert_eq!(header.color.family, ColorFamily::Gray);
+        assert_eq!(header.frame_size(), 3 * 3 * 2);
+
+        assert!(Y4mHeader::parse("YUV4MPEG2 W3 H2 F1:1").is_err());
+        assert!(Y4mHeader::parse("YUV4MPEG2 W4 H2 F1:1 C444alpha").is_err());
+        assert!(Y4mHeader::parse("YUV4MPEG2 W4 H2").is_err());
+        assert!(Y4mHeader::parse("MPEG2 W4 H2 F1:1").is_err());
+        assert!(matches!(
+            Y4mHeader::parse("YUV4MPEG2 W4 H2 F1:1 Ä12"),
+            Err(SourceError::InvalidHeader(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn index() -> Result<(), SourceError> {
+        let data = b"YUV4MPEG2 W2 H2 F1:1 C444\nFRAME\n012345678901FRAME Ixyz\n012345678901";
+        let mut reader = Cursor::new(&data[..]);
+        let header = Y4mHeader::read(&mut reader)?;
+        assert_eq!(index_frames(&mut reader, header.frame_size())?, [32, 55]);
+
+        let mut reader = Cursor::new(&data[..data.len() - 1]);
+        Y4mHeader::read(&mut reader)?;
+        assert!(matches!(
+            index_frames(&mut reader, header.frame_size()),
+            Err(SourceError::Truncated { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn source() -> Result<(), Box<dyn std::error::Error>> {
+        let mut data = b"YUV4MPEG2 W4 H2 F25:1 A4:3 It C420mpeg2\n".to_vec();
+        for n in 0..2u8 {
+            data.extend_from_slice(b"FRAME\n");
+            data.extend((0..12).map(|i| n * 100 + i));
+        }
+        let dir = std::env::temp_dir().join(format!("vapoursynth4-rs-y4m-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("source.y4m");
+        std::fs::write(&path, data)?;
+
+        let core = Core::builder().api(mock::api()).build();
+        let node = core.y4m_source(&path);
+        std::fs::remove_dir_all(&dir)?;
+        let node = node?;
+        assert_eq!(node.info().num_frames, 2);
+
+        let frame = node
+            .get_frame(1)
+            .map_err(|e| e.to_string_lossy().into_owned())?;
+        for (plane, expected) in [
+            (0, &[100, 101, 102, 103][..]),
+            (1, &[108, 109]),
+            (2, &[110, 111]),
+        ] {
+            // SAFETY: The first row of each plane has `expected.len()` samples
+            let row = unsafe { std::slice::from_raw_parts(frame.plane(plane), expected.len()) };
+            assert_eq!(row, expected);
+        }
+        // SAFETY: The luma plane has two rows of four samples
+        let row = unsafe {
+            std::slice::from_raw_parts(frame.plane(0).wrapping_offset(frame.stride(0)), 4)
+        };
+        assert_eq!(row, [104, 105, 106, 107]);
+
+        let props = frame.properties().ok_or("no props")?;
+        assert_eq!(props.get_as::<i64>(key!(c"_SARNum"))?, 4);
+        assert_eq!(props.get_as::<i64>(key!(c"_SARDen"))?, 3);
+        assert_eq!(props.get_as::<i64>(key!(c"_FieldBased"))?, 2);
+        assert_eq!(props.get_as::<i64>(key!(c"_ChromaLocation"))?, 0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Helpers for testing a plugin crate from `cargo test`, without `VSScript`.
+//!
+//! [`PluginHarness`] loads the cdylib of the plugin into a fresh core with
+//! `std.LoadPlugin`, so its functions can be invoked by namespace:
+//!
+//! ```ignore
+//! let harness = PluginHarness::load(cdylib_path(env!("CARGO_PKG_NAME")))?;
+//! let core = harness.core();
+//! let clip = harness.invoke_clip(c"std", c"BlankClip", map!(core; "length" => 2)?)?;
+//! let out = harness.invoke_clip(c"invert", c"Filter", map!(core; "clip" => clip)?)?;
+//! assert_props(&get_frame(&out, 0)?, &map!(core; "_DurationDen" => 24)?);
+//! ```
+//!
+//! The cdylib is only built for integration tests, so put such tests in the `tests`
+//! directory of the plugin crate.
+//!
+//! [`assert_snapshot`] compares the frames of a node with a snapshot file to catch
+//! regressions in the output.
+
+mod snapshot;
+
+use std::{
+    borrow::Borrow,
+    env::consts::{DLL_PREFIX, DLL_SUFFIX},
+    ffi::CStr,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::{
+    core::Core,
+    frame::{Frame, VideoFrame},
+    key,
+    map::{Map, MapPropertyError, OwnedValue},
+    node::{Node, VideoNode},
+};
+
+pub use snapshot::*;
+
+#[derive(Debug, Error)]
+pub enum HarnessError {
+    #[error("Plugin not found: {0}")]
+    PluginNotFound(String),
+    #[error("Failed to load {}: {message}", path.display())]
+    LoadPlugin { path: PathBuf, message: String },
+    #[error("{function} failed: {message}")]
+    Invoke { function: String, message: String },
+    #[error("Failed to get frame {n}: {message}")]
+    Frame { n: i32, message: String },
+    #[error(transparent)]
+    Map(#[from] MapPropertyError),
+}
+
+/// A core with a plugin loaded from a cdylib.
+pub struct PluginHarness {
+    core: Core,
+}
+
+impl PluginHarness {
+    /// Loads the plugin at `path` into a new core without autoloaded plugins.
+    ///
+    /// # Errors
+    ///
+    /// Return [`HarnessError`] if the plugin can not be loaded.
+    #[cfg(feature = "link-vs")]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, HarnessError> {
+        Self::with_core(Core::builder().disable_auto_loading().build(), path)
+    }
+
+    /// Loads the plugin at `path` into `core`.
+    ///
+    /// # Errors
+    ///
+    /// Return [`HarnessError`] if `core` has no `std` plugin or the plugin can not be loaded.
+    pub fn with_core(core: Core, path: impl AsRef<Path>) -> Result<Self, HarnessError> {
+        let path = path.as_ref();
+        let load_error = |message: &str| HarnessError::LoadPlugin {
+            path: path.to_owned(),
+            message: message.to_owned(),
+        };
+
+        let harness = Self { core };
+        let mut args = harness.core.create_map();
+        args.insert(
+            key!(c"path"),
+            path.to_str()
+                .ok_or_else(|| load_error("not a UTF-8 path"))?,
+        )?;
+        harness
+            .invoke(c"std", c"LoadPlugin", args)
+            .map_err(|e| match e {
+                HarnessError::Invoke { message, .. } => load_error(&message),
+                e => e,
+            })?;
+
+        Ok(harness)
+    }
+
+    #[must_use]
+    pub fn core(&self) -> &Core {
+        &self.core
+    }
+
+    /// Invokes the function `name` of the plugin with `namespace`.
+    ///
+    /// # Errors
+    ///
+    /// Return [`HarnessError`] if the plugin does not exist or the function
+    /// returns an error.
+    pub fn invoke(
+        &self,
+        namespace: &CStr,
+        name: &CStr,
+        args: impl Borrow<Map>,
+    ) -> Result<Map, HarnessError> {
+        let plugin = self
+            .core
+            .get_plugin_by_namespace(namespace)
+            .ok_or_else(|| HarnessError::PluginNotFound(namespace.to_string_lossy().into()))?;
+        let out = plugin.invoke(name, args);
+        match out.get_error() {
+            Some(message) => Err(HarnessError::Invoke {
+                function: format!("{}.{}", namespace.to_string_lossy(), name.to_string_lossy()),
+                message: message.to_string_lossy().into(),
+            }),
+            None => Ok(out),
+        }
+    }
+
+    /// Invokes a function returning a video node in `clip`, see [`Self::invoke`].
+    ///
+    /// # Errors
+    ///
+    /// Return [`HarnessError`] if the invocation fails or does not return a clip.
+    pub fn invoke_clip(
+        &self,
+        namespace: &CStr,
+        name: &CStr,
+        args: impl Borrow<Map>,
+    ) -> Result<VideoNode, HarnessError> {
+        let out = self.invoke(namespace, name, args)?;
+        Ok(out.get_video_node(key!(c"clip"), 0)?)
+    }
+}
+
+/// Returns the path of the cdylib of `crate_name`, built next to the running test binary.
+///
+/// Test binaries are in `target/<profile>/deps`, and the cdylib of the package is built
+/// in `target/<profile>` when it has integration tests.
+///
+/// # Panics
+///
+/// Panic if the path of the test binary is unknown.
+#[must_use]
+pub fn cdylib_path(crate_name: &str) -> PathBuf {
+    let exe = std::env::current_exe().expect("failed to get the path of the test binary");
+    let mut dir = exe.parent().unwrap_or(Path::new("."));
+    if dir.ends_with("deps") {
+        dir = dir.parent().unwrap_or(dir);
+    }
+    dir.join(format!(
+        "{DLL_PREFIX}{}{DLL_SUFFIX}",
+        crate_name.replace('-', "_")
+    ))
+}
+
+/// Renders frame `n` of `node`.
+///
+/// # Errors
+///
+/// Return [`HarnessError::Frame`] with the error message of the filter.
+pub fn get_frame<N: Node>(node: &N, n: i32) -> Result<N::FrameType, HarnessError> {
+    node.get_frame(n).map_err(|e| HarnessError::Frame {
+        n,
+        message: e.to_string_lossy().into(),
+    })
+}
+
+/// Renders the frames `range` of `node`.
+///
+/// # Errors
+///
+/// Return [`HarnessError::Frame`] for the first frame that fails.
+pub fn get_frames<N: Node>(
+    node: &N,
+    range: impl IntoIterator<Item = i32>,
+) -> Result<Vec<N::FrameType>, HarnessError> {
+    range.into_iter().map(|n| get_frame(node, n)).collect()
+}
+
+/// Asserts that two video frames have the same format, size and samples.
+///
+/// # Panics
+///
+/// Panic with the plane and position of the first differing sample.
+#[track_caller]
+pub fn assert_frame_eq(left: &VideoFrame, right: &VideoFrame) {
+    let format = left.get_video_format();
+    assert_eq!(format, right.get_video_format(), "frame formats differ");
+
+    let bytes = usize::try_from(format.bytes_per_sample).unwrap_or(1);
+    for plane in 0..format.num_planes {
+        let size = |f: &VideoFrame| (f.frame_width(plane), f.frame_height(plane));
+        assert_eq!(size(left), size(right), "sizes of plane {plane} differ");
+
+        let (width, height) = size(left);
+        let row_size = usize::try_from(width).unwrap_or(0) * bytes;
+        for y in 0..isize::try_from(height).unwrap_or(0) {
+            let row = |f: &VideoFrame| {
+                let ptr = f.plane(plane).wrapping_offset(y * f.stride(plane));
+                // SAFETY: Each row of the plane holds `width` samples
+                unsafe { std::slice::from_raw_parts(ptr, row_size) }
+            };
+            let (l, r) = (row(left), row(right));
+            if let Some(i) = (0..row_size).find(|&i| l[i] != r[i]) {
+                let x = i / bytes;
+                let sample = x * bytes..(x + 1) * bytes;
+                panic!(
+                    "frames differ in plane {plane} at ({x}, {y}): {:?} != {:?}",
+                    &l[sample.clone()],
+                    &r[sample]
+                );
+            }
+        }
+    }
+}
+
+/// Asserts that the properties of `frame` include every key of `expected`
+/// with the same values.
+///
+/// # Panics
+///
+/// Panic with the first property that is missing or differs.
+#[track_caller]
+pub fn assert_props(frame: &impl Frame, expected: &Map) {
+    let props = frame.properties().expect("frame has no properties");
+    let expected = expected
+        .to_vec()
+        .unwrap_or_else(|e| panic!("failed to read the expected properties: {e}"));
+    for (key, values) in expected {
+        let actual = props
+            .get_all::<OwnedValue>(&key)
+            .unwrap_or_else(|e| panic!("property `{key}`: {e}"));
+        assert_eq!(actual, values, "property `{key}` differs");
+    }
+}
+
+/// Asserts that `result` is an error whose message contains `needle`.
+///
+/// # Panics
+///
+/// Panic if `result` is `Ok` or the message does not contain `needle`.
+#[track_caller]
+pub fn assert_error_contains<T>(result: Result<T, HarnessError>, needle: &str) {
+    match result {
+        Ok(_) => panic!("expected an error containing `{needle}`, but it succeeded"),
+        Err(e) => assert!(
+            e.to_string().contains(needle),
+            "error `{e}` does not contain `{needle}`"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use testresult::TestResult;
+
+    use super::*;
+    use crate::{ColorFamily, SampleType, frame::VideoFrameMut, map, mock};
+
+    fn core() -> Core {
+        Core::builder().api(mock::api()).build()
+    }
+
+    fn gray_frame(core: &Core, value: u8) -> VideoFrameMut {
+        let format = core.query_video_format(ColorFamily::Gray, SampleType::Integer, 8, 0, 0);
+        let mut frame = VideoFrameMut::new(core.new_video_frame(&format, 4, 2, None));
+        for y in 0..2 {
+            let row = frame.plane_mut(0).wrapping_offset(y * frame.stride(0));
+            // SAFETY: Each row holds 4 samples
+            unsafe { row.write_bytes(value, 4) };
+        }
+        frame
+    }
+
+    #[test]
+    fn harness() {
+        assert_error_contains(
+            PluginHarness::with_core(core(), "plugin.so"),
+            "Plugin not found: std",
+        );
+
+        let path = cdylib_path("sample-plugin");
+        assert!(!path.parent().is_some_and(|dir| dir.ends_with("deps")));
+        assert_eq!(
+            path.file_name().and_then(|name| name.to_str()),
+            Some(format!("{DLL_PREFIX}sample_plugin{DLL_SUFFIX}").as_str())
+        );
+    }
+
+    #[test]
+    fn frames() -> TestResult {
+        let core = core();
+        let mut frame = gray_frame(&core, 16);
+        frame
+            .properties_mut()
+            .ok_or("no props")?
+            .insert(key!(c"_Matrix"), 1)?;
+        let frame = frame.into_frame();
+
+        assert_frame_eq(&frame, &gray_frame(&core, 16).into_frame());
+        assert_props(&frame, &map!(core; "_Matrix" => 1)?);
+
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "frames differ in plane 0 at (1, 1): [16] != [17]")]
+    fn frames_differ() {
+        let core = core();
+        let left = gray_frame(&core, 16).into_frame();
+        let mut right = gray_frame(&core, 16);
+        let row = right.plane_mut(0).wrapping_offset(right.stride(0));
+        // SAFETY: The second row holds 4 samples
+        unsafe { row.add(1).write(17) };
+        assert_frame_eq(&left, &right.into_frame());
+    }
+}
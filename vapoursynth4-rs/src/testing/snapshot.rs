@@ -0,0 +1,313 @@
+use std::{
+    ffi::OsString,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bon::builder;
+
+use crate::{
+    frame::{Frame, VideoFrame},
+    map::OwnedValue,
+    node::VideoNode,
+};
+
+use super::get_frame;
+
+/// Set this environment variable to write the snapshots instead of comparing with them.
+pub const UPDATE_SNAPSHOTS_ENV: &str = "VAPOURSYNTH_UPDATE_SNAPSHOTS";
+
+/// Asserts that the frames of `node` match the snapshot at `path`.
+///
+/// The snapshot is a text file with a hash of every plane, and of the properties
+/// with `props(true)`. The samples are stored uncompressed in a sidecar file with
+/// a `.raw` suffix, so a mismatch reports the first differing sample. The sidecar
+/// holds every plane of the checked frames, e.g. about 3 MB per 1080p `YUV420P8`
+/// frame, so prefer a few small frames. It may be left out of version control, in
+/// which case a mismatch only reports the plane that differs.
+/// Run the test with [`UPDATE_SNAPSHOTS_ENV`] set to create or update the snapshot.
+///
+/// ```ignore
+/// let snapshot = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots/invert.snap");
+/// assert_snapshot(&node, snapshot).frames(&[0, 10]).props(true).call();
+/// ```
+///
+/// # Panics
+///
+/// Panic if the frames differ from the snapshot, the snapshot is missing, or a frame
+/// can not be rendered.
+#[builder]
+pub fn assert_snapshot(
+    #[builder(start_fn)] node: &VideoNode,
+    #[builder(start_fn)] path: impl AsRef<Path>,
+    /// The frames to check, all frames by default.
+    frames: Option<&[i32]>,
+    /// Whether to include the frame properties.
+    #[builder(default)]
+    props: bool,
+) {
+    let path = path.as_ref();
+    let all = (0..node.info().num_frames).collect::<Vec<_>>();
+    let snapshot = Snapshot::new(node, frames.unwrap_or(&all), props);
+
+    if std::env::var_os(UPDATE_SNAPSHOTS_ENV).is_some() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).expect("failed to create the snapshot directory");
+        }
+        fs::write(path, &snapshot.text).expect("failed to write the snapshot");
+        fs::write(raw_path(path), &snapshot.raw).expect("failed to write the snapshot data");
+        return;
+    }
+
+    let Ok(expected) = fs::read_to_string(path) else {
+        panic!(
+            "snapshot {} not found, run with {UPDATE_SNAPSHOTS_ENV}=1 to create it",
+            path.display()
+        );
+    };
+    if let Some(diff) = snapshot.diff(&expected, fs::read(raw_path(path)).ok().as_deref()) {
+        panic!(
+            "{diff} in snapshot {}, run with {UPDATE_SNAPSHOTS_ENV}=1 to update it",
+            path.display()
+        );
+    }
+}
+
+fn raw_path(path: &Path) -> PathBuf {
+    let mut raw = OsString::from(path);
+    raw.push(".raw");
+    raw.into()
+}
+
+/// The snapshot text and the samples of the planes in the same order.
+struct Snapshot {
+    text: String,
+    raw: Vec<u8>,
+}
+
+impl Snapshot {
+    fn new(node: &VideoNode, frames: &[i32], props: bool) -> Self {
+        let mut snapshot = Self {
+            text: String::new(),
+            raw: Vec::new(),
+        };
+        for &n in frames {
+            let frame = get_frame(node, n).unwrap_or_else(|e| panic!("{e}"));
+            snapshot.push_frame(n, &frame, props);
+        }
+        snapshot
+    }
+
+    fn push_frame(&mut self, n: i32, frame: &VideoFrame, props: bool) {
+        let format = frame.get_video_format();
+        let _ = writeln!(
+            self.text,
+            "frame {n} {:?} {:?} {}",
+            format.color_family, format.sample_type, format.bits_per_sample
+        );
+
+        let bytes = usize::try_from(format.bytes_per_sample).unwrap_or(1);
+        for plane in 0..format.num_planes {
+            let (width, height) = (frame.frame_width(plane), frame.frame_height(plane));
+            let start = self.raw.len();
+            let row_size = usize::try_from(width).unwrap_or(0) * bytes;
+            for y in 0..isize::try_from(height).unwrap_or(0) {
+                let ptr = frame.plane(plane).wrapping_offset(y * frame.stride(plane));
+                // SAFETY: Each row of the plane holds `width` samples
+                self.raw
+                    .extend_from_slice(unsafe { std::slice::from_raw_parts(ptr, row_size) });
+            }
+            let _ = writeln!(
+                self.text,
+                "plane {plane} {width}x{height}x{bytes} {:016x}",
+                fnv1a(&self.raw[start..])
+            );
+        }
+
+        if props {
+            let Some(map) = frame.properties() else {
+                return;
+            };
+            let mut entries = map
+                .to_vec()
+                .unwrap_or_else(|e| panic!("failed to read the properties of frame {n}: {e}"));
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (key, values) in entries {
+                let values = values.iter().map(describe).collect::<Vec<_>>();
+                let _ = writeln!(self.text, "prop {key} {}", values.join(", "));
+            }
+        }
+    }
+
+    /// Describes the first difference from the snapshot `expected` with the samples `raw`.
+    fn diff(&self, expected: &str, raw: Option<&[u8]>) -> Option<String> {
+        let mut frame = "snapshot";
+        // Offset of the current plane in the samples, which is the same for both
+        // snapshots as long as the lines are equal
+        let mut offset = 0;
+        let mut actual_lines = self.text.lines();
+        for line in expected.lines() {
+            let Some(actual) = actual_lines.next() else {
+                return Some(format!("{frame}: `{line}` is missing"));
+            };
+            let size = plane_size(line);
+            if line != actual {
+                let Some((len, bytes)) = size.filter(|&size| plane_size(actual) == Some(size))
+                else {
+                    return Some(format!("{frame}: `{actual}` differs from `{line}`"));
+                };
+                let plane = actual.split(' ').take(2).collect::<Vec<_>>().join(" ");
+                let sample = raw
+                    .and_then(|raw| raw.get(offset..offset + len))
+                    .zip(self.raw.get(offset..offset + len))
+                    .and_then(|(old, new)| first_difference(line, old, new, bytes))
+                    .unwrap_or_default();
+                return Some(format!("{frame}: {plane} differs{sample}"));
+            }
+            if line.starts_with("frame ") {
+                frame = line;
+            }
+            offset += size.map_or(0, |(len, _)| len);
+        }
+        actual_lines
+            .next()
+            .map(|line| format!("{frame}: `{line}` is not in the snapshot"))
+    }
+}
+
+/// Returns the number of bytes and the bytes per sample of a plane line.
+fn plane_size(line: &str) -> Option<(usize, usize)> {
+    let size = line.strip_prefix("plane ")?.split(' ').nth(1)?;
+    let mut dims = size.split('x').map(str::parse::<usize>);
+    let (width, height, bytes) = (dims.next()?.ok()?, dims.next()?.ok()?, dims.next()?.ok()?);
+    Some((width * height * bytes, bytes))
+}
+
+/// Describes the first differing sample of two planes.
+fn first_difference(line: &str, old: &[u8], new: &[u8], bytes: usize) -> Option<String> {
+    let width = line
+        .split(' ')
+        .nth(2)?
+        .split('x')
+        .next()?
+        .parse::<usize>()
+        .ok()?;
+    let i = old.iter().zip(new).position(|(a, b)| a != b)? / bytes;
+    let sample = i * bytes..(i + 1) * bytes;
+    Some(format!(
+        " at ({}, {}): {:?} != {:?}",
+        i % width,
+        i / width,
+        &new[sample.clone()],
+        &old[sample]
+    ))
+}
+
+fn describe(value: &OwnedValue) -> String {
+    match value {
+        OwnedValue::Int(v) => v.to_string(),
+        OwnedValue::Float(v) => format!("{v:?}"),
+        OwnedValue::Utf8(v) => format!("{v:?}"),
+        OwnedValue::Data(v) => format!("{v:?}"),
+        OwnedValue::VideoNode(_) => "<video node>".into(),
+        OwnedValue::AudioNode(_) => "<audio node>".into(),
+        OwnedValue::VideoFrame(_) => "<video frame>".into(),
+        OwnedValue::AudioFrame(_) => "<audio frame>".into(),
+        OwnedValue::Function(_) => "<function>".into(),
+    }
+}
+
+/// 64-bit FNV-1a, which is stable across platforms and Rust versions.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use testresult::TestResult;
+
+    use super::*;
+    use crate::{ColorFamily, SampleType, VideoInfo, core::Core, key, mock, node::mode::Parallel};
+
+    /// A 4x2 gray source with `value` at (1, 1) of frame 1.
+    fn source(core: &Core, value: u8) -> TestResult<VideoNode> {
+        let format = core.query_video_format(ColorFamily::Gray, SampleType::Integer, 8, 0, 0);
+        let info = VideoInfo {
+            format,
+            fps_num: 30,
+            fps_den: 1,
+            width: 4,
+            height: 2,
+            num_frames: 2,
+        };
+        let node = core.video_source(info, Parallel, move |n, frame, _| {
+            for y in 0..2 {
+                let row = frame.plane_mut(0).wrapping_offset(y * frame.stride(0));
+                // SAFETY: Each row holds 4 samples
+                unsafe { row.write_bytes(0, 4) };
+            }
+            if n == 1 {
+                let row = frame.plane_mut(0).wrapping_offset(frame.stride(0));
+                // SAFETY: The second row holds 4 samples
+                unsafe { row.add(1).write(value) };
+                frame
+                    .properties_mut()
+                    .ok_or(c"no props")?
+                    .insert(key!(c"_Value"), i64::from(value))
+                    .map_err(|_| c"failed to set prop")?;
+            }
+            Ok::<_, &CStr>(())
+        });
+        Ok(node.ok_or("failed to create source")?)
+    }
+
+    #[test]
+    fn snapshots() -> TestResult {
+        let core = Core::builder().api(mock::api()).build();
+        let expected = Snapshot::new(&source(&core, 16)?, &[0, 1], true);
+        assert_eq!(
+            expected.text,
+            "frame 0 Gray Integer 8\n\
+             plane 0 4x2x1 a8c7f832281a39c5\n\
+             prop _DurationDen 30\n\
+             prop _DurationNum 1\n\
+             frame 1 Gray Integer 8\n\
+             plane 0 4x2x1 335fa832769a2475\n\
+             prop _DurationDen 30\n\
+             prop _DurationNum 1\n\
+             prop _Value 16\n"
+        );
+        assert_eq!(expected.raw.len(), 16);
+        assert_eq!(expected.diff(&expected.text, Some(&expected.raw)), None);
+
+        let actual = Snapshot::new(&source(&core, 17)?, &[0, 1], true);
+        assert_eq!(
+            actual.diff(&expected.text, Some(&expected.raw)).as_deref(),
+            Some("frame 1 Gray Integer 8: plane 0 differs at (1, 1): [17] != [16]")
+        );
+        assert_eq!(
+            actual.diff(&expected.text, None).as_deref(),
+            Some("frame 1 Gray Integer 8: plane 0 differs")
+        );
+
+        let fewer = Snapshot::new(&source(&core, 16)?, &[0], false);
+        assert_eq!(
+            fewer.diff(
+                &Snapshot::new(&source(&core, 16)?, &[0, 1], false).text,
+                None
+            ),
+            Some("frame 0 Gray Integer 8: `frame 1 Gray Integer 8` is missing".into())
+        );
+        assert_eq!(
+            Snapshot::new(&source(&core, 16)?, &[0], true).diff(&fewer.text, None),
+            Some("frame 0 Gray Integer 8: `prop _DurationDen 30` is not in the snapshot".into())
+        );
+
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use std::ffi::CString;
+use std::{any::Any, ffi::CString};
 
 pub trait ToCString {
     fn into_cstring_lossy(self) -> CString;
@@ -17,6 +17,17 @@ impl ToCString for &str {
     }
 }
 
+/// Extracts the message of a panic payload from `panic!` or `std::panic::panic_any`.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> CString {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.into_cstring_lossy()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.as_str().into_cstring_lossy()
+    } else {
+        c"Unknown panic".into()
+    }
+}
+
 pub use crate::ffi::vs_make_version as make_version;
 
 pub use crate::ffi::helper::*;